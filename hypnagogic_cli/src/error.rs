@@ -2,6 +2,8 @@ use std::io;
 use std::path::PathBuf;
 
 use hypnagogic_core::config::error::ConfigError;
+use hypnagogic_core::operations::error::ProcessorError;
+use hypnagogic_core::operations::InputError;
 use thiserror::Error;
 use user_error::UFE;
 
@@ -18,11 +20,34 @@ pub enum Error {
         source_config: String,
         config_error: ConfigError,
     },
+    #[error("Invalid Input File")]
+    InvalidInput {
+        source_config: String,
+        input: PathBuf,
+        input_error: InputError,
+    },
     #[error("Template Not Found")]
     TemplateNotFound {
         source_config: String,
         template_string: String,
-        expected_path: PathBuf,
+        expected_paths: Vec<PathBuf>,
+    },
+    #[error("Blank icon states")]
+    BlankStates {
+        source_config: String,
+        states: Vec<String>,
+    },
+    #[error("Operation failed")]
+    OperationFailed {
+        source_config: String,
+        operation: &'static str,
+        error: ProcessorError,
+    },
+    #[error("Failed to write output")]
+    OutputFailed {
+        source_config: String,
+        path: PathBuf,
+        reason: String,
     },
     #[error("No template folder")]
     NoTemplateFolder(PathBuf),
@@ -30,6 +55,23 @@ pub enum Error {
     IO(#[from] io::Error),
 }
 
+impl Error {
+    /// Name of the config that caused this error, if it came from one
+    #[must_use]
+    pub fn source_config(&self) -> Option<&str> {
+        match self {
+            Error::InputNotFound { source_config, .. }
+            | Error::InvalidConfig { source_config, .. }
+            | Error::InvalidInput { source_config, .. }
+            | Error::TemplateNotFound { source_config, .. }
+            | Error::BlankStates { source_config, .. }
+            | Error::OperationFailed { source_config, .. }
+            | Error::OutputFailed { source_config, .. } => Some(source_config),
+            Error::NoTemplateFolder(_) | Error::IO(_) => None,
+        }
+    }
+}
+
 impl UFE for Error {
     fn summary(&self) -> String {
         format!("{}", self)
@@ -57,15 +99,57 @@ impl UFE for Error {
                     format!("{}", config_error),
                 ])
             }
+            Error::InvalidInput {
+                source_config,
+                input,
+                input_error,
+            } => {
+                Some(vec![
+                    format!("Failed to read the input {input:?} for config \"{source_config}\""),
+                    format!("{input_error}"),
+                ])
+            }
             Error::TemplateNotFound {
                 source_config,
                 template_string,
-                expected_path,
+                expected_paths,
             } => {
-                Some(vec![
+                let mut reasons = vec![
                     format!("Failed to find the template referenced in a config ({source_config})"),
                     format!("Config string was \"{template_string}\""),
-                    format!("Expected to find a config at {expected_path:?}"),
+                ];
+                for expected_path in expected_paths {
+                    reasons.push(format!("Expected to find a config at {expected_path:?}"));
+                }
+                Some(reasons)
+            }
+            Error::BlankStates {
+                source_config,
+                states,
+            } => {
+                Some(vec![
+                    format!("Config \"{source_config}\" generated completely blank icon states"),
+                    format!("Blank states: {}", states.join(", ")),
+                ])
+            }
+            Error::OperationFailed {
+                source_config,
+                operation,
+                error,
+            } => {
+                Some(vec![
+                    format!("{operation} failed for config \"{source_config}\""),
+                    format!("{error}"),
+                ])
+            }
+            Error::OutputFailed {
+                source_config,
+                path,
+                reason,
+            } => {
+                Some(vec![
+                    format!("Failed to write {path:?} for config \"{source_config}\""),
+                    reason.clone(),
                 ])
             }
             Error::NoTemplateFolder(folder) => {
@@ -97,12 +181,38 @@ impl UFE for Error {
                         .to_string(),
                 )
             }
+            Error::InvalidInput { .. } => {
+                Some(
+                    "Make sure the input is a valid png, dmi or webp, and that its extension \
+                     matches its contents"
+                        .to_string(),
+                )
+            }
             Error::TemplateNotFound { .. } => {
                 Some(
                     "Make sure you have spelled the template correctly, and that it exists"
                         .to_string(),
                 )
             }
+            Error::BlankStates { .. } => {
+                Some(
+                    "Check that the positions and cut settings of the config point at the right \
+                     parts of the input"
+                        .to_string(),
+                )
+            }
+            Error::OperationFailed { .. } => {
+                Some(
+                    "Check that the config's settings suit the input it's being run on".to_string(),
+                )
+            }
+            Error::OutputFailed { .. } => {
+                Some(
+                    "Make sure the output directory is writable, and that there's enough free \
+                     space"
+                        .to_string(),
+                )
+            }
             Error::NoTemplateFolder(_) => {
                 Some(
                     "Check that you have spelled your template dir correctly, and make sure it \
@@ -0,0 +1,154 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter};
+use std::path::{self, Path, PathBuf};
+use std::time::SystemTime;
+
+use serde::{Deserialize, Serialize};
+use tracing::{debug, warn};
+
+/// Where the manifest for a run over `input`, writing to `output`, is kept.
+///
+/// Outputs usually sit next to their inputs in a version controlled tree, and
+/// which outputs a config writes isn't known until it runs, so what each
+/// config built is tracked in a manifest in the user's cache directory rather
+/// than anywhere in that tree. Losing it just means the next run builds
+/// everything.
+///
+/// The file is named after a hash of the absolute paths. It has to stay the
+/// same across builds of hypnagogic, so it's FNV-1a rather than std's hasher,
+/// which makes no such promise.
+#[must_use]
+pub fn manifest_path(input: &Path, output: Option<&Path>) -> PathBuf {
+    let mut hash = FNV_OFFSET_BASIS;
+    for path in [Some(input), output].into_iter().flatten() {
+        // a separator byte, so moving bytes between the paths changes the hash
+        for byte in absolute(path)
+            .as_os_str()
+            .as_encoded_bytes()
+            .iter()
+            .chain([&0xFF])
+        {
+            hash ^= u64::from(*byte);
+            hash = hash.wrapping_mul(FNV_PRIME);
+        }
+    }
+    cache_dir()
+        .join("hypnagogic")
+        .join(format!("build-{hash:016x}.json"))
+}
+
+const FNV_OFFSET_BASIS: u64 = 0xCBF2_9CE4_8422_2325;
+const FNV_PRIME: u64 = 0x0100_0000_01B3;
+
+fn cache_dir() -> PathBuf {
+    env::var_os("XDG_CACHE_HOME")
+        .or_else(|| env::var_os("LOCALAPPDATA"))
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))
+        .unwrap_or_else(env::temp_dir)
+}
+
+/// Paths are stored absolute, so runs from different working directories
+/// share a manifest
+fn absolute(path: &Path) -> PathBuf {
+    path::absolute(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// What was built by the last incremental run, so configs whose inputs haven't
+/// changed since can be skipped
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct BuildManifest {
+    /// Version of hypnagogic that did the build
+    version: String,
+    /// Everything besides the files read that affects what gets written (flags,
+    /// template folders). Builds with different options aren't reused
+    options: String,
+    configs: BTreeMap<PathBuf, BuildRecord>,
+}
+
+/// When a config was last built, and what it wrote
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BuildRecord {
+    /// Time the build started. Anything modified after this needs building
+    /// again
+    pub built: SystemTime,
+    pub outputs: Vec<PathBuf>,
+}
+
+impl BuildManifest {
+    #[must_use]
+    pub fn new(version: &str, options: String) -> Self {
+        Self {
+            version: version.to_string(),
+            options,
+            configs: BTreeMap::new(),
+        }
+    }
+
+    /// Loads the manifest at `path`. A missing or unreadable manifest, or one
+    /// from a different version or with different options, is treated as
+    /// empty so everything gets built
+    #[must_use]
+    pub fn load(path: &Path, version: &str, options: String) -> Self {
+        let loaded = File::open(path)
+            .map(|file| serde_json::from_reader::<_, BuildManifest>(BufReader::new(file)));
+        match loaded {
+            Ok(Ok(manifest)) if manifest.version == version && manifest.options == options => {
+                manifest
+            }
+            Ok(Ok(_)) => {
+                debug!(path = ?path, "Build options changed, rebuilding everything");
+                Self::new(version, options)
+            }
+            Ok(Err(err)) => {
+                warn!(path = ?path, error = %err, "Couldn't read build manifest, rebuilding everything");
+                Self::new(version, options)
+            }
+            Err(_) => Self::new(version, options),
+        }
+    }
+
+    /// Writes the manifest to `path`, creating its folder if needed
+    /// # Errors
+    /// Errors if the file can't be written
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let writer = BufWriter::new(File::create(path)?);
+        serde_json::to_writer_pretty(writer, self)?;
+        Ok(())
+    }
+
+    /// The last build of `config`, if every one of its outputs still exists and
+    /// none of `dependencies` have been modified since
+    #[must_use]
+    pub fn up_to_date(&self, config: &Path, dependencies: &[PathBuf]) -> Option<&BuildRecord> {
+        let record = self.get(config)?;
+        let outputs_exist = record.outputs.iter().all(|output| output.exists());
+        // a file modified in the same instant the build started may have been
+        // modified after it read the file
+        let unchanged = dependencies.iter().all(|dependency| {
+            fs::metadata(dependency)
+                .and_then(|metadata| metadata.modified())
+                .is_ok_and(|modified| modified < record.built)
+        });
+        (outputs_exist && unchanged).then_some(record)
+    }
+
+    #[must_use]
+    pub fn get(&self, config: &Path) -> Option<&BuildRecord> {
+        self.configs.get(&absolute(config))
+    }
+
+    pub fn record(&mut self, config: &Path, mut record: BuildRecord) {
+        record.outputs = record
+            .outputs
+            .iter()
+            .map(|output| absolute(output))
+            .collect();
+        self.configs.insert(absolute(config), record);
+    }
+}
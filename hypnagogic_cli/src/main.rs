@@ -1,32 +1,55 @@
 mod error;
+mod incremental;
+mod summary;
+mod watch;
 
 use std::fs;
 use std::fs::{metadata, File};
-use std::io::BufReader;
+use std::io::{BufReader, Cursor};
 use std::path::{Path, PathBuf};
 use std::process::exit;
-use std::time::Instant;
+use std::time::{Instant, SystemTime};
 
 use anyhow::{anyhow, Result};
-use clap::Parser;
+use clap::{Parser, ValueEnum};
+use dmi::icon::{Icon, IconState};
 use hypnagogic_core::config::error::ConfigError;
 use hypnagogic_core::config::read_config;
 use hypnagogic_core::config::template_resolver::error::TemplateError;
 use hypnagogic_core::config::template_resolver::file_resolver::FileResolver;
+use hypnagogic_core::config::template_resolver::{
+    CachingResolver,
+    ChainResolver,
+    RecordingResolver,
+    TemplateResolver,
+};
+use hypnagogic_core::operations::error::ProcessorError;
 use hypnagogic_core::operations::{
     IconOperationConfig,
+    InputError,
     InputIcon,
     NamedIcon,
     OperationMode,
     OutputImage,
     ProcessorPayload,
 };
+use hypnagogic_core::util::icon_ops::{blank_states, flatten_to_sheet, save_indexed, write_gif};
+use image::ImageFormat;
 use rayon::prelude::*;
-use tracing::{debug, info, Level};
+use tracing::{debug, info, warn, Level};
 use user_error::UFE;
 use walkdir::WalkDir;
 
 use crate::error::Error;
+use crate::incremental::{manifest_path, BuildManifest, BuildRecord};
+use crate::summary::{
+    input_dimensions,
+    print_error_report,
+    print_stats,
+    print_summary,
+    IconSummary,
+    OutputSummary,
+};
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
@@ -40,22 +63,87 @@ struct Args {
     /// Print debug information and produce debug outputs
     #[arg(short, long)]
     debug: bool,
+    /// Format of log output. `json` writes one json object per event, for
+    /// other tools to parse
+    #[arg(long, value_enum, default_value_t = LogFormat::Text)]
+    log_format: LogFormat,
     /// Doesn't wait for a keypress after running. For CI or toolchain usage.
     #[arg(short = 'w', long)]
     dont_wait: bool,
+    /// Print a summary of what each config produced after processing
+    #[arg(short, long)]
+    summary: bool,
+    /// Keep processing the remaining configs when one fails, reporting every
+    /// failure at the end instead of stopping at the first
+    #[arg(long, alias = "continue-on-error")]
+    keep_going: bool,
+    /// Print totals of generated dmis, icon states and frames after processing
+    #[arg(long)]
+    stats: bool,
+    /// Warn about generated icon states that came out completely blank
+    #[arg(long)]
+    warn_blank: bool,
+    /// Fail if any generated icon states came out completely blank
+    #[arg(long)]
+    fail_blank: bool,
+    /// Pack every dmi an operation produces in to a single dmi. Pngs that fit
+    /// (such as debug corners) become states named after their path, anything
+    /// else is left out with a warning
+    #[arg(long)]
+    merge: bool,
+    /// Convert dmi outputs to png sheets (named `<name>-sheet.png`), or png
+    /// outputs to single state dmis
+    #[arg(long, value_enum)]
+    output_format: Option<OutputFormat>,
+    /// Also write a gif of this icon state's animation next to each dmi that
+    /// has it, for checking its timing outside of byond. Can be given more
+    /// than once
+    #[arg(long, value_name = "STATE")]
+    preview_gif: Vec<String>,
+    /// Write dmis as indexed color pngs where possible, which are much
+    /// smaller. Only applies to dmis with 256 colors or fewer
+    #[arg(long)]
+    indexed: bool,
+    /// Always write outputs, even if an identical file is already there.
+    /// Normally identical outputs are skipped so their timestamps don't change
+    #[arg(long, conflicts_with = "no_clobber")]
+    force: bool,
+    /// Never overwrite an existing file, skipping that output with a warning
+    #[arg(long)]
+    no_clobber: bool,
+    /// Run every config without writing any files, to check that they're valid
+    #[arg(long)]
+    dry_run: bool,
+    /// Skip configs that haven't changed since they were last built with this
+    /// flag, comparing the config, input and template modification times to
+    /// the last build. Builds are tracked in the user's cache directory
+    #[arg(long)]
+    incremental: bool,
+    /// After processing, keep watching the input for changes and re-run the
+    /// configs affected by each change
+    #[arg(long)]
+    watch: bool,
+    /// Number of threads to use, shared between files and the work within
+    /// each file. Defaults to the number of logical cpus
+    #[arg(short, long)]
+    jobs: Option<usize>,
     /// Output directory of folders. If not set, output will match the file tree
     /// and output adjacent to input
     #[arg(short, long)]
     output: Option<String>,
-    /// Location of the templates folder
-    #[arg(short, long, default_value_t = String::from("templates"))]
-    templates: String,
+    /// Location of the templates folder. Can be given more than once, earlier
+    /// folders take priority
+    #[arg(short, long, default_value = "templates")]
+    templates: Vec<String>,
     /// Input directory/file
     input: String,
 }
 
 const VERSION: &str = env!("CARGO_PKG_VERSION");
 
+/// Extensions searched for when a config doesn't specify its input's extension
+const INPUT_EXTENSIONS: [&str; 3] = ["png", "dmi", "webp"];
+
 fn main() -> Result<()> {
     let now = Instant::now();
     let args = Args::parse();
@@ -63,7 +151,23 @@ fn main() -> Result<()> {
         verbose,
         flatten,
         debug,
+        log_format,
         dont_wait,
+        summary,
+        stats,
+        keep_going,
+        warn_blank,
+        fail_blank,
+        dry_run,
+        incremental,
+        force,
+        no_clobber,
+        watch,
+        merge,
+        output_format,
+        preview_gif,
+        indexed,
+        jobs,
         output,
         templates,
         input,
@@ -71,27 +175,44 @@ fn main() -> Result<()> {
 
     println!("Hypnagogic CLI v{VERSION}");
 
-    // subscribers are of different generic types so can't be put into one binding
-    // this is why each branch has its own binding and call to set_global_default
-    if debug {
-        let subscriber = tracing_subscriber::fmt()
-            .pretty()
-            .with_max_level(Level::DEBUG)
-            .finish();
-        tracing::subscriber::set_global_default(subscriber)?;
+    let level = if debug {
+        Level::DEBUG
     } else if verbose {
-        let subscriber = tracing_subscriber::fmt()
-            .with_max_level(Level::INFO)
-            .compact()
-            .finish();
-        tracing::subscriber::set_global_default(subscriber)?;
+        Level::INFO
     } else {
-        let subscriber = tracing_subscriber::fmt()
-            .compact()
-            .with_max_level(Level::WARN)
-            .finish();
-        tracing::subscriber::set_global_default(subscriber)?;
+        Level::WARN
     };
+    // subscribers are of different generic types so can't be put into one binding
+    // this is why each branch has its own binding and call to set_global_default
+    match log_format {
+        LogFormat::Json => {
+            let subscriber = tracing_subscriber::fmt()
+                .json()
+                .with_max_level(level)
+                .finish();
+            tracing::subscriber::set_global_default(subscriber)?;
+        }
+        LogFormat::Text if debug => {
+            let subscriber = tracing_subscriber::fmt()
+                .pretty()
+                .with_max_level(level)
+                .finish();
+            tracing::subscriber::set_global_default(subscriber)?;
+        }
+        LogFormat::Text => {
+            let subscriber = tracing_subscriber::fmt()
+                .compact()
+                .with_max_level(level)
+                .finish();
+            tracing::subscriber::set_global_default(subscriber)?;
+        }
+    }
+
+    if let Some(jobs) = jobs {
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(jobs)
+            .build_global()?;
+    }
 
     if !Path::new(&input).exists() {
         return Err(anyhow!("Input path does not exist!"));
@@ -100,7 +221,9 @@ fn main() -> Result<()> {
     let files_to_process: Vec<PathBuf> = if metadata(&input)?.is_file() {
         vec![Path::new(&input).to_path_buf()]
     } else {
+        // sorted so runs over the same tree always process (and log) in the same order
         WalkDir::new(&input)
+            .sort_by_file_name()
             .into_iter()
             .filter_map(Result::ok)
             .filter(|e| e.file_type().is_file())
@@ -116,26 +239,142 @@ fn main() -> Result<()> {
     };
     debug!(files = ?files_to_process, "Files to process");
 
+    let blank_check = if fail_blank {
+        BlankCheck::Fail
+    } else if warn_blank {
+        BlankCheck::Warn
+    } else {
+        BlankCheck::Off
+    };
+    let overwrite = if force {
+        Overwrite::Always
+    } else if no_clobber {
+        Overwrite::Never
+    } else {
+        Overwrite::IfChanged
+    };
+    let output_options = OutputOptions {
+        flatten,
+        blank_check,
+        dry_run,
+        overwrite,
+        merge,
+        output_format,
+        preview_gifs: preview_gif,
+        indexed,
+        output,
+    };
+
+    // dry runs don't write anything, so there's nothing to skip or track
+    let incremental = (incremental && !dry_run).then(|| {
+        let path = manifest_path(
+            Path::new(&input),
+            output_options.output.as_deref().map(Path::new),
+        );
+        // anything that changes what gets written invalidates the last build
+        let options = format!("{:?}", (debug, &output_options, &templates));
+        let manifest = BuildManifest::load(&path, VERSION, options.clone());
+        (path, options, manifest)
+    });
+    let started = SystemTime::now();
+
+    // shared by every config, so each template is only loaded once per run
+    let resolver = match template_resolver(&templates) {
+        Ok(resolver) => CachingResolver::new(resolver),
+        Err(err) => {
+            err.into_ufe().print();
+            if !dont_wait {
+                dont_disappear::any_key_to_continue::default();
+            }
+            exit(1);
+        }
+    };
+
     let num_files = files_to_process.len();
     println!("Found {num_files} files!");
 
-    let result: Result<Vec<()>, Error> = files_to_process
-        .par_iter()
-        .map(|path| process_icon(flatten, debug, &output, &templates, path))
-        .collect();
+    let manifest = incremental.as_ref().map(|(_, _, manifest)| manifest);
+    let process = |path: &PathBuf| process_icon(debug, &output_options, manifest, &resolver, path);
+    let mut summaries = vec![];
+    let mut errors = vec![];
+    if keep_going {
+        let results: Vec<Result<IconSummary, Error>> =
+            files_to_process.par_iter().map(process).collect();
+        for result in results {
+            match result {
+                Ok(summary) => summaries.push(summary),
+                Err(err) => errors.push(err),
+            }
+        }
+    } else {
+        match files_to_process.par_iter().map(process).collect() {
+            Ok(all) => summaries = all,
+            Err(err) => errors.push(err),
+        }
+    }
+
+    // without --keep-going nothing past the first error is meaningful
+    if errors.is_empty() || keep_going {
+        if let Some((path, options, previous)) = &incremental {
+            save_manifest(path, options, previous, &summaries, started);
+        }
+        if summary {
+            print_summary(&summaries);
+        }
+        if stats {
+            print_stats(&summaries);
+        }
+        if dry_run {
+            let producing = summaries
+                .iter()
+                .filter(|summary| !summary.outputs.is_empty())
+                .count();
+            let outputs: usize = summaries.iter().map(|summary| summary.outputs.len()).sum();
+            println!("Dry run: {producing} configs would produce {outputs} outputs");
+        }
+    }
+
+    let up_to_date = summaries
+        .iter()
+        .filter(|summary| summary.up_to_date)
+        .count();
+    if up_to_date > 0 {
+        println!("Skipped {up_to_date} configs that were already up to date");
+    }
 
-    if let Err(err) = result {
-        err.into_ufe().print();
-        if !dont_wait {
-            dont_disappear::any_key_to_continue::default();
+    if errors.is_empty() {
+        println!(
+            "Successfully processed {num_files} files! (Took {:.2?})",
+            now.elapsed()
+        );
+    } else {
+        let failed = errors.len();
+        for err in &errors {
+            err.into_ufe().print();
+        }
+        if keep_going {
+            print_error_report(&errors);
+            println!("Failed to process {failed} of {num_files} files");
+        }
+        // errors are expected while editing, so they don't stop a watch
+        if !watch {
+            if !dont_wait {
+                dont_disappear::any_key_to_continue::default();
+            }
             exit(1);
         }
     }
 
-    println!(
-        "Successfully processed {num_files} files! (Took {:.2?})",
-        now.elapsed()
-    );
+    if watch {
+        return watch::watch(Path::new(&input), input_path_for_config, |config| {
+            // templates may have been edited since the last run
+            resolver.clear();
+            match process(config) {
+                Ok(_) => println!("Processed {config:?}"),
+                Err(err) => err.into_ufe().print(),
+            }
+        });
+    }
 
     if !dont_wait {
         dont_disappear::any_key_to_continue::default();
@@ -144,25 +383,119 @@ fn main() -> Result<()> {
     Ok(())
 }
 
+/// What to do about generated icon states that are completely blank
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+enum BlankCheck {
+    Off,
+    Warn,
+    Fail,
+}
+
+/// What to do when an output file already exists
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+enum Overwrite {
+    Always,
+    /// Only write if the contents would change
+    IfChanged,
+    Never,
+}
+
+/// How log events are written out
+#[derive(Copy, Clone, Eq, PartialEq, Debug, ValueEnum)]
+enum LogFormat {
+    /// Human readable text, pretty printed with `--debug`
+    Text,
+    Json,
+}
+
+/// Image format to force outputs to
+#[derive(Copy, Clone, Eq, PartialEq, Debug, ValueEnum)]
+enum OutputFormat {
+    Png,
+    Dmi,
+}
+
+impl OutputFormat {
+    /// Converts an output to this format. Gifs and text are left alone, as are
+    /// outputs that are already in this format
+    /// # Errors
+    /// Errors if a dmi is too large to lay out as a png sheet
+    fn convert(self, image: OutputImage) -> Result<OutputImage, ProcessorError> {
+        Ok(match (self, image) {
+            (OutputFormat::Png, OutputImage::Dmi(dmi)) => {
+                // roughly square sheets are easier to look over than one long row
+                let columns = (dmi.states.len() as f64).sqrt().ceil() as usize;
+                OutputImage::Png(flatten_to_sheet(&dmi, columns)?)
+            }
+            (OutputFormat::Dmi, OutputImage::Png(png)) => {
+                OutputImage::Dmi(Icon {
+                    width: png.width(),
+                    height: png.height(),
+                    states: vec![IconState {
+                        dirs: 1,
+                        frames: 1,
+                        images: vec![png],
+                        ..Default::default()
+                    }],
+                    ..Default::default()
+                })
+            }
+            (_, image) => image,
+        })
+    }
+}
+
+/// Settings for where and how outputs get written
+#[derive(Clone, Debug)]
+struct OutputOptions {
+    /// Output as flat files instead of mirroring directory tree
+    flatten: bool,
+    blank_check: BlankCheck,
+    /// Skip writing anything
+    dry_run: bool,
+    overwrite: Overwrite,
+    /// Pack all dmi outputs in to one dmi
+    merge: bool,
+    /// Format to convert outputs to, if any
+    output_format: Option<OutputFormat>,
+    /// Icon states to write animated gif previews of
+    preview_gifs: Vec<String>,
+    /// Write dmis with an indexed palette where possible
+    indexed: bool,
+    /// Output directory, if not set outputs are written adjacent to inputs
+    output: Option<String>,
+}
+
+/// Builds a resolver searching each of the template folders in order
+#[allow(clippy::result_large_err)]
+fn template_resolver(templates: &[String]) -> Result<ChainResolver, Error> {
+    let resolvers = templates
+        .iter()
+        .map(|templates| {
+            FileResolver::new(Path::new(templates))
+                .map(|resolver| Box::new(resolver) as Box<dyn TemplateResolver + Send + Sync>)
+                .map_err(|_err| Error::NoTemplateFolder(PathBuf::from(templates)))
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+    Ok(ChainResolver(resolvers))
+}
+
 /// Gnarly, effectful function hoisted out here so that I can still use ? but
 /// parallelize with rayon
 #[allow(clippy::result_large_err)]
 fn process_icon(
-    flatten: bool,
     debug: bool,
-    output: &Option<String>,
-    templates: &String,
+    output_options: &OutputOptions,
+    manifest: Option<&BuildManifest>,
+    resolver: &impl TemplateResolver,
     path: &PathBuf,
-) -> Result<(), Error> {
+) -> Result<IconSummary, Error> {
     info!(path = ?path, "Found toml at path");
     let in_file_toml = File::open(path.as_path())?;
     let mut in_toml_reader = BufReader::new(in_file_toml);
-    let config = read_config(
-        &mut in_toml_reader,
-        FileResolver::new(Path::new(&templates))
-            .map_err(|_err| Error::NoTemplateFolder(PathBuf::from(templates)))?,
-    )
-    .map_err(|err| {
+    // notes down which templates were used, as changing them changes the outputs
+    let recording = RecordingResolver::new(resolver);
+    let config = read_config(&mut in_toml_reader, &recording).map_err(|err| {
         let source_config = path
             .clone()
             .file_name()
@@ -177,7 +510,20 @@ fn process_icon(
                         Error::TemplateNotFound {
                             source_config,
                             template_string,
-                            expected_path,
+                            expected_paths: vec![expected_path],
+                        }
+                    }
+                    TemplateError::NotFoundInAny(template_string, expected_paths) => {
+                        Error::TemplateNotFound {
+                            source_config,
+                            template_string,
+                            expected_paths,
+                        }
+                    }
+                    err @ (TemplateError::RecursionLimit(_) | TemplateError::CyclicTemplate(_)) => {
+                        Error::InvalidConfig {
+                            source_config,
+                            config_error: err.into(),
                         }
                     }
                     TemplateError::TOMLError(err) => {
@@ -189,27 +535,32 @@ fn process_icon(
                     TemplateError::IOError(err) => err.into(),
                 }
             }
-            ConfigError::Toml(err) => {
-                Error::InvalidConfig {
-                    source_config,
-                    config_error: ConfigError::Toml(err),
-                }
-            }
-            ConfigError::Config(_) => {
+            ConfigError::IO(err) => err.into(),
+            ConfigError::Toml(_)
+            | ConfigError::Json(_)
+            | ConfigError::Yaml(_)
+            | ConfigError::Config(_)
+            | ConfigError::UnknownKeys(_)
+            | ConfigError::UnsupportedVersion { .. } => {
                 Error::InvalidConfig {
                     source_config,
                     config_error: err,
                 }
             }
-            _ => panic!("Unexpected error: {:#?}", err),
         }
     })?;
-
-    let mut input_icon_path = path.clone();
-    // funny hack: for double extensioned files (eg, .png.toml) calling
-    // set_extension with a blank string clears out the second extension,
-    // (.png.toml -> .png)
-    input_icon_path.set_extension("");
+    let operation = config.operation_name();
+    info!(path = ?path, operation, "Read config");
+
+    let input_icon_path = input_path_for_config(path);
+    if path.with_extension("").extension().is_none() && input_icon_path.exists() {
+        let found_name = input_icon_path.file_name().unwrap().to_str().unwrap();
+        warn!(
+            config = ?path,
+            input = ?input_icon_path,
+            "Config is missing the input's extension, prefer naming it \"{found_name}.toml\""
+        );
+    }
 
     if !input_icon_path.exists() {
         let source_config = path.file_name().unwrap().to_str().unwrap().to_string();
@@ -226,29 +577,150 @@ fn process_icon(
             search_dir,
         });
     }
+
+    if let Some(manifest) = manifest {
+        let mut dependencies = recording.into_sources();
+        dependencies.push(path.clone());
+        dependencies.push(input_icon_path.clone());
+        if let Some(record) = manifest.up_to_date(path, &dependencies) {
+            info!(path = ?path, "Outputs are up to date, skipping");
+            return Ok(IconSummary {
+                config: path.clone(),
+                operation,
+                input_dimensions: (0, 0),
+                outputs: record
+                    .outputs
+                    .iter()
+                    .map(|output| OutputSummary::existing(output))
+                    .collect(),
+                up_to_date: true,
+            });
+        }
+    }
+
+    let invalid_input = |input_error: InputError| {
+        Error::InvalidInput {
+            source_config: path.file_name().unwrap().to_str().unwrap().to_string(),
+            input: input_icon_path.clone(),
+            input_error,
+        }
+    };
     let actual_extension = input_icon_path
         .extension()
-        .unwrap()
-        .to_os_string()
-        .into_string()
-        .unwrap();
+        .map(|extension| extension.to_string_lossy().into_owned())
+        .unwrap_or_default();
     let icon_file = File::open(&input_icon_path)?;
     let mut reader = BufReader::new(icon_file);
-    // todo: prettify this error
-    let input = InputIcon::from_reader(&mut reader, &actual_extension).unwrap();
+    let input = InputIcon::from_reader(&mut reader, &actual_extension).map_err(invalid_input)?;
 
     let mode = if debug {
         OperationMode::Debug
     } else {
         OperationMode::Standard
     };
-    // TODO: Operation error handling
-    let out = config.do_operation(&input, mode).unwrap();
+    let operation_failed = |error: ProcessorError| {
+        Error::OperationFailed {
+            source_config: path.file_name().unwrap().to_str().unwrap().to_string(),
+            operation,
+            error,
+        }
+    };
+    let mut out = config
+        .do_operation(&input, mode)
+        .map_err(operation_failed)?;
 
-    if let Some(output) = &output {
-        let output_path = Path::new(output);
-        fs::create_dir_all(output_path)?;
+    if output_options.merge {
+        if let Some(merged) = out
+            .clone()
+            .merge_into_single_dmi()
+            .map_err(operation_failed)?
+        {
+            out = ProcessorPayload::from_icon(merged);
+        }
+    }
+
+    let outputs = handle_payload(out, path, &input_icon_path, operation, output_options)?;
+
+    Ok(IconSummary {
+        config: path.clone(),
+        operation,
+        input_dimensions: input_dimensions(&input),
+        outputs,
+        up_to_date: false,
+    })
+}
+
+/// Records what this run built, carrying over anything that was skipped for
+/// being up to date. Configs that failed or no longer exist are dropped, so
+/// they're built again next time
+fn save_manifest(
+    path: &Path,
+    options: &str,
+    previous: &BuildManifest,
+    summaries: &[IconSummary],
+    started: SystemTime,
+) {
+    let mut manifest = BuildManifest::new(VERSION, options.to_string());
+    for summary in summaries {
+        let record = if summary.up_to_date {
+            previous.get(&summary.config).cloned()
+        } else {
+            Some(BuildRecord {
+                built: started,
+                outputs: summary
+                    .outputs
+                    .iter()
+                    .map(|output| output.path.clone())
+                    .collect(),
+            })
+        };
+        if let Some(record) = record {
+            manifest.record(&summary.config, record);
+        }
+    }
+    if let Err(err) = manifest.save(path) {
+        warn!(path = ?path, error = %err, "Failed to write build manifest");
+    }
+}
+
+/// Works out which input a config reads. The returned path may not exist.
+fn input_path_for_config(config: &Path) -> PathBuf {
+    // funny hack: for double extensioned files (eg, .png.toml) calling
+    // set_extension with a blank string clears out the second extension,
+    // (.png.toml -> .png)
+    let input_icon_path = config.with_extension("");
+    if input_icon_path.extension().is_some() {
+        return input_icon_path;
     }
+    // single extensioned configs (eg, foo.toml) leave no extension behind, so look
+    // for an adjacent input with a supported extension instead
+    INPUT_EXTENSIONS
+        .iter()
+        .map(|extension| input_icon_path.with_extension(extension))
+        .find(|candidate| candidate.exists())
+        .unwrap_or(input_icon_path)
+}
+
+/// Works out where each output of an operation should go, then writes them
+#[allow(clippy::result_large_err)]
+fn handle_payload(
+    payload: ProcessorPayload,
+    config_path: &Path,
+    input_icon_path: &Path,
+    operation: &'static str,
+    output_options: &OutputOptions,
+) -> Result<Vec<OutputSummary>, Error> {
+    let OutputOptions {
+        flatten,
+        blank_check,
+        dry_run,
+        overwrite,
+        output_format,
+        preview_gifs,
+        indexed,
+        output,
+        ..
+    } = output_options;
 
     let process_path = |path: PathBuf, named_img: Option<&NamedIcon>| -> PathBuf {
         debug!(path = ?path, img = ?named_img, "Processing path");
@@ -267,7 +739,7 @@ fn process_icon(
             path = PathBuf::from(output).join(&path);
         }
 
-        if !flatten {
+        if !*flatten {
             path.push(parent_path);
         }
         path.push(processed_path);
@@ -278,49 +750,195 @@ fn process_icon(
 
     let mut out_paths: Vec<(PathBuf, OutputImage)> = vec![];
 
-    match out {
+    match payload {
         ProcessorPayload::Single(inner) => {
-            let mut processed_path = process_path(input_icon_path.clone(), None);
+            let mut processed_path = process_path(input_icon_path.to_path_buf(), None);
             processed_path.set_extension(inner.extension());
             out_paths.push((processed_path, *inner));
         }
         ProcessorPayload::SingleNamed(named) => {
-            let mut processed_path = process_path(input_icon_path.clone(), Some(&named));
+            let mut processed_path = process_path(input_icon_path.to_path_buf(), Some(&named));
             processed_path.set_extension(named.image.extension());
             out_paths.push((processed_path, named.image))
         }
         ProcessorPayload::MultipleNamed(icons) => {
             for icon in icons {
-                let mut processed_path = process_path(input_icon_path.clone(), Some(&icon));
+                let mut processed_path = process_path(input_icon_path.to_path_buf(), Some(&icon));
                 processed_path.set_extension(icon.image.extension());
                 out_paths.push((processed_path, icon.image))
             }
         }
     }
 
-    for (mut path, icon) in out_paths {
-        let parent_dir = path.parent().expect(
-            "Failed to get parent? (this is a program error, not a config error! Please report!)",
-        );
+    let source_config = config_path
+        .file_name()
+        .unwrap()
+        .to_str()
+        .unwrap()
+        .to_string();
+
+    // previews are made from the dmis, so have to happen before they're converted
+    for state in preview_gifs {
+        let previews: Vec<(PathBuf, OutputImage)> = out_paths
+            .iter()
+            .filter_map(|(path, image)| {
+                let gif = image.state_animation(state)?;
+                let stem = path.file_stem().unwrap().to_str().unwrap();
+                // state names can be anything, keep them from breaking the path
+                let state: String = state
+                    .chars()
+                    .map(|c| {
+                        if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                            c
+                        } else {
+                            '_'
+                        }
+                    })
+                    .collect();
+                Some((path.with_file_name(format!("{stem}-{state}.gif")), gif))
+            })
+            .collect();
+        if previews.is_empty() {
+            warn!(
+                config = source_config,
+                state, "No dmi has the state to preview"
+            );
+        }
+        out_paths.extend(previews);
+    }
 
-        fs::create_dir_all(parent_dir).expect(
-            "Failed to create dirs (This is a program error, not a config error! Please report!)",
-        );
+    if let Some(format) = output_format {
+        out_paths = out_paths
+            .into_iter()
+            .map(|(path, image)| {
+                let from_dmi = matches!(image, OutputImage::Dmi(_));
+                let image = format.convert(image).map_err(|error| {
+                    Error::OperationFailed {
+                        source_config: source_config.clone(),
+                        operation,
+                        error,
+                    }
+                })?;
+                let path = if from_dmi && matches!(image, OutputImage::Png(_)) {
+                    // a sheet named after the dmi would land on top of a png input
+                    let stem = path.file_stem().unwrap().to_str().unwrap();
+                    path.with_file_name(format!("{stem}-sheet.png"))
+                } else {
+                    path.with_extension(image.extension())
+                };
+                Ok((path, image))
+            })
+            .collect::<Result<_, Error>>()?;
+    }
 
-        let mut file = File::create(path.as_path()).expect(
-            "Failed to create output file (This is a program error, not a config error! Please \
-             report!)",
-        );
+    // payloads may come out of unordered maps, keep outputs reproducible
+    out_paths.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+    // outputs go next to their input by default, never replace the input itself
+    if let Ok(input) = fs::canonicalize(input_icon_path) {
+        let clobbered = out_paths
+            .iter()
+            .find(|(path, _)| fs::canonicalize(path).is_ok_and(|path| path == input));
+        if let Some((path, _)) = clobbered {
+            return Err(Error::OutputFailed {
+                source_config,
+                path: path.clone(),
+                reason: "The output would overwrite the config's input".to_string(),
+            });
+        }
+    }
 
-        // TODO: figure out a better thing to do than just the unwrap
-        match icon {
-            OutputImage::Png(png) => {
-                png.save(&mut path).unwrap();
+    if *blank_check != BlankCheck::Off {
+        let states: Vec<String> = out_paths
+            .iter()
+            .filter_map(|(_, icon)| {
+                match icon {
+                    OutputImage::Dmi(dmi) => Some(blank_states(dmi)),
+                    OutputImage::Png(_) | OutputImage::Gif { .. } | OutputImage::Text(_) => None,
+                }
+            })
+            .flatten()
+            .collect();
+        if !states.is_empty() {
+            if *blank_check == BlankCheck::Fail {
+                return Err(Error::BlankStates {
+                    source_config,
+                    states,
+                });
             }
-            OutputImage::Dmi(dmi) => {
-                dmi.save(&mut file).unwrap();
+            warn!(config = source_config, states = ?states, "Generated blank icon states");
+        }
+    }
+
+    if *dry_run {
+        return Ok(out_paths
+            .into_iter()
+            .map(|(path, icon)| OutputSummary::new(path, &icon, None))
+            .collect());
+    }
+
+    if let Some(output) = &output {
+        let output_path = Path::new(output);
+        fs::create_dir_all(output_path)?;
+    }
+
+    let output_failed = |path: &Path, reason: String| {
+        Error::OutputFailed {
+            source_config: source_config.clone(),
+            path: path.to_path_buf(),
+            reason,
+        }
+    };
+    let mut summaries = vec![];
+    for (path, icon) in out_paths {
+        let bytes =
+            encode_output(&icon, *indexed).map_err(|reason| output_failed(&path, reason))?;
+        let file_size = Some(bytes.len() as u64);
+
+        let skip = match overwrite {
+            Overwrite::Always => false,
+            Overwrite::IfChanged => fs::read(&path).is_ok_and(|existing| existing == bytes),
+            Overwrite::Never => path.exists(),
+        };
+        if skip {
+            if *overwrite == Overwrite::Never {
+                warn!(path = ?path, "Output already exists, not overwriting it");
+            } else {
+                debug!(path = ?path, "Output unchanged, not rewriting it");
             }
+            summaries.push(OutputSummary::new(path, &icon, file_size));
+            continue;
         }
+
+        let parent_dir = path.parent().expect(
+            "Failed to get parent? (this is a program error, not a config error! Please report!)",
+        );
+        fs::create_dir_all(parent_dir).map_err(|err| output_failed(parent_dir, err.to_string()))?;
+        fs::write(&path, &bytes).map_err(|err| output_failed(&path, err.to_string()))?;
+
+        summaries.push(OutputSummary::new(path, &icon, file_size));
     }
-    Ok(())
+    Ok(summaries)
+}
+
+/// Encodes an output to the bytes written for it
+fn encode_output(image: &OutputImage, indexed: bool) -> Result<Vec<u8>, String> {
+    let mut bytes = vec![];
+    match image {
+        OutputImage::Png(png) => {
+            png.write_to(&mut Cursor::new(&mut bytes), ImageFormat::Png)
+                .map_err(|err| err.to_string())?;
+        }
+        OutputImage::Dmi(dmi) if indexed => {
+            save_indexed(dmi, &mut bytes).map_err(|err| err.to_string())?;
+        }
+        OutputImage::Dmi(dmi) => {
+            dmi.save(&mut bytes).map_err(|err| err.to_string())?;
+        }
+        OutputImage::Text(text) => bytes.extend_from_slice(text.as_bytes()),
+        OutputImage::Gif { frames, delays } => {
+            write_gif(&mut bytes, frames, delays).map_err(|err| err.to_string())?;
+        }
+    }
+    Ok(bytes)
 }
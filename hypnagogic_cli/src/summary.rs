@@ -0,0 +1,221 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use hypnagogic_core::operations::{InputIcon, OutputImage};
+use image::GenericImageView;
+
+use crate::error::Error;
+
+/// What a single config produced, collected during processing so it can be
+/// printed once all files are done
+#[derive(Debug, Clone)]
+pub struct IconSummary {
+    pub config: PathBuf,
+    pub operation: &'static str,
+    /// Not known for configs skipped for being up to date
+    pub input_dimensions: (u32, u32),
+    pub outputs: Vec<OutputSummary>,
+    /// Skipped by an incremental build, as nothing it depends on had changed
+    pub up_to_date: bool,
+}
+
+/// A single output file of a config
+#[derive(Debug, Clone)]
+pub struct OutputSummary {
+    pub path: PathBuf,
+    /// Number of icon states, `None` for outputs that aren't dmis
+    pub states: Option<usize>,
+    /// Total frames across all icon states, `None` for outputs that aren't dmis
+    pub frames: Option<usize>,
+    /// `None` for outputs that aren't images
+    pub dimensions: Option<(u32, u32)>,
+    /// Size of the written file in bytes, `None` if nothing was written
+    pub file_size: Option<u64>,
+}
+
+impl OutputSummary {
+    #[must_use]
+    pub fn new(path: PathBuf, image: &OutputImage, file_size: Option<u64>) -> Self {
+        let (states, dimensions) = match image {
+            OutputImage::Png(png) => (None, Some(png.dimensions())),
+            OutputImage::Dmi(dmi) => (Some(dmi.states.len()), Some((dmi.width, dmi.height))),
+            OutputImage::Gif { frames, .. } => {
+                (None, frames.first().map(|frame| frame.dimensions()))
+            }
+            OutputImage::Text(_) => (None, None),
+        };
+        let frames = match image {
+            OutputImage::Dmi(dmi) => {
+                Some(dmi.states.iter().map(|state| state.frames as usize).sum())
+            }
+            _ => None,
+        };
+        Self {
+            path,
+            states,
+            frames,
+            dimensions,
+            file_size,
+        }
+    }
+
+    /// An output left in place by an earlier build. Only its size is known
+    /// without reading it back
+    #[must_use]
+    pub fn existing(path: &Path) -> Self {
+        Self {
+            file_size: std::fs::metadata(path).ok().map(|metadata| metadata.len()),
+            path: path.to_path_buf(),
+            states: None,
+            frames: None,
+            dimensions: None,
+        }
+    }
+}
+
+#[must_use]
+pub fn input_dimensions(input: &InputIcon) -> (u32, u32) {
+    match input {
+        InputIcon::DynamicImage(img) => img.dimensions(),
+        InputIcon::Dmi(icon) => (icon.width, icon.height),
+    }
+}
+
+/// Prints a table of everything produced, one block per config
+pub fn print_summary(summaries: &[IconSummary]) {
+    const HEADERS: [&str; 4] = ["Output", "States", "Dimensions", "Size"];
+
+    for summary in summaries {
+        let (in_width, in_height) = summary.input_dimensions;
+        if summary.up_to_date {
+            println!(
+                "\n{} ({}, up to date)",
+                summary.config.display(),
+                summary.operation
+            );
+        } else {
+            println!(
+                "\n{} ({}, input {in_width}x{in_height})",
+                summary.config.display(),
+                summary.operation
+            );
+        }
+
+        let rows: Vec<[String; 4]> = summary
+            .outputs
+            .iter()
+            .map(|output| {
+                [
+                    output.path.display().to_string(),
+                    output
+                        .states
+                        .map_or_else(|| "-".to_string(), |states| states.to_string()),
+                    output.dimensions.map_or_else(
+                        || "-".to_string(),
+                        |(width, height)| format!("{width}x{height}"),
+                    ),
+                    output
+                        .file_size
+                        .map_or_else(|| "-".to_string(), format_size),
+                ]
+            })
+            .collect();
+
+        let mut widths = HEADERS.map(str::len);
+        for row in &rows {
+            for (width, cell) in widths.iter_mut().zip(row) {
+                *width = (*width).max(cell.len());
+            }
+        }
+
+        println!(
+            "  {:<w0$}  {:>w1$}  {:>w2$}  {:>w3$}",
+            HEADERS[0],
+            HEADERS[1],
+            HEADERS[2],
+            HEADERS[3],
+            w0 = widths[0],
+            w1 = widths[1],
+            w2 = widths[2],
+            w3 = widths[3],
+        );
+        for [path, states, dimensions, size] in rows {
+            println!(
+                "  {path:<w0$}  {states:>w1$}  {dimensions:>w2$}  {size:>w3$}",
+                w0 = widths[0],
+                w1 = widths[1],
+                w2 = widths[2],
+                w3 = widths[3],
+            );
+        }
+    }
+}
+
+/// Prints totals of everything produced across all configs, and calls out any
+/// config whose dmis came out with no states at all
+pub fn print_stats(summaries: &[IconSummary]) {
+    let dmis: Vec<&OutputSummary> = summaries
+        .iter()
+        .flat_map(|summary| &summary.outputs)
+        .filter(|output| output.states.is_some())
+        .collect();
+    let states: usize = dmis.iter().filter_map(|output| output.states).sum();
+    let frames: usize = dmis.iter().filter_map(|output| output.frames).sum();
+
+    println!("\nConfigs      {}", summaries.len());
+    println!("Dmis         {}", dmis.len());
+    println!("Icon states  {states}");
+    println!("Frames       {frames}");
+
+    let empty: Vec<&IconSummary> = summaries
+        .iter()
+        .filter(|summary| {
+            let dmi_states: Vec<usize> = summary
+                .outputs
+                .iter()
+                .filter_map(|output| output.states)
+                .collect();
+            !dmi_states.is_empty() && dmi_states.iter().sum::<usize>() == 0
+        })
+        .collect();
+    if !empty.is_empty() {
+        println!("\nConfigs that produced no icon states:");
+        for summary in empty {
+            println!("  {}", summary.config.display());
+        }
+    }
+}
+
+/// Prints how many of each kind of error happened, and which configs they
+/// came from, largest group first
+pub fn print_error_report(errors: &[Error]) {
+    let mut by_kind: BTreeMap<String, Vec<&Error>> = BTreeMap::new();
+    for error in errors {
+        by_kind.entry(error.to_string()).or_default().push(error);
+    }
+    let mut groups: Vec<(String, Vec<&Error>)> = by_kind.into_iter().collect();
+    groups.sort_by_key(|(_, errors)| std::cmp::Reverse(errors.len()));
+
+    println!("\nFailures by kind:");
+    for (kind, errors) in groups {
+        println!("  {kind} ({})", errors.len());
+        let mut configs: Vec<&str> = errors
+            .iter()
+            .filter_map(|error| error.source_config())
+            .collect();
+        configs.sort_unstable();
+        for config in configs {
+            println!("    {config}");
+        }
+    }
+}
+
+fn format_size(bytes: u64) -> String {
+    if bytes < 1024 {
+        format!("{bytes} B")
+    } else if bytes < 1024 * 1024 {
+        format!("{:.1} KiB", bytes as f64 / 1024.0)
+    } else {
+        format!("{:.1} MiB", bytes as f64 / (1024.0 * 1024.0))
+    }
+}
@@ -0,0 +1,80 @@
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::channel;
+use std::time::Duration;
+
+use anyhow::Result;
+use notify::{Event, EventKind, RecursiveMode, Watcher};
+use tracing::{debug, warn};
+
+/// How long things have to go quiet for before changes are processed, so an
+/// editor saving a file several times (or a batch of files changing at once)
+/// only causes one run
+const DEBOUNCE: Duration = Duration::from_millis(250);
+
+/// Watches `input` until the process is killed, calling `process` with every
+/// config whose config file or input changed. `input_for_config` works out
+/// which input a config reads, so changes to outputs (which often sit next to
+/// the inputs) don't cause configs to run again.
+/// # Errors
+/// Errors if `input` can't be watched
+pub fn watch(
+    input: &Path,
+    input_for_config: impl Fn(&Path) -> PathBuf,
+    mut process: impl FnMut(&PathBuf),
+) -> Result<()> {
+    let (sender, receiver) = channel();
+    let mut watcher = notify::recommended_watcher(sender)?;
+    watcher.watch(input, RecursiveMode::Recursive)?;
+    println!("Watching {input:?} for changes, press Ctrl+C to stop");
+
+    loop {
+        // block until something changes, then keep collecting until it goes quiet
+        let mut changed = BTreeSet::new();
+        collect_changes(receiver.recv()?, &mut changed);
+        while let Ok(event) = receiver.recv_timeout(DEBOUNCE) {
+            collect_changes(event, &mut changed);
+        }
+        debug!(changed = ?changed, "Files changed");
+
+        let configs: BTreeSet<PathBuf> = changed
+            .iter()
+            .flat_map(|path| affected_configs(path, &input_for_config))
+            .collect();
+        for config in &configs {
+            process(config);
+        }
+    }
+}
+
+fn collect_changes(event: notify::Result<Event>, changed: &mut BTreeSet<PathBuf>) {
+    match event {
+        Ok(event) => {
+            if matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)) {
+                changed.extend(event.paths);
+            }
+        }
+        Err(err) => warn!(error = ?err, "Error while watching for changes"),
+    }
+}
+
+/// Configs that need to run again because `path` changed. Either `path` is a
+/// config itself, or it's the input of a config next to it.
+fn affected_configs(path: &Path, input_for_config: impl Fn(&Path) -> PathBuf) -> Vec<PathBuf> {
+    if path
+        .extension()
+        .is_some_and(|extension| extension == "toml")
+    {
+        return if path.is_file() {
+            vec![path.to_path_buf()]
+        } else {
+            vec![]
+        };
+    }
+    let mut double_extension = path.as_os_str().to_os_string();
+    double_extension.push(".toml");
+    [PathBuf::from(double_extension), path.with_extension("toml")]
+        .into_iter()
+        .filter(|config| config.is_file() && input_for_config(config) == path)
+        .collect()
+}
@@ -7,7 +7,533 @@ mod regressions {
     use super::*;
 
     test_dir!("basic_cut");
+    test_dir!("double_extension");
     test_dir!("simple_cuts");
     test_dir!("tall_cuts");
     test_dir!("tall_cuts_with_vis");
 }
+
+mod compare {
+    use std::fs;
+
+    use image::{DynamicImage, GenericImageView, Rgba};
+    use util::deep_dir_compare::{deep_compare_path, pixel_diff, CompareFailureReasonError};
+
+    use super::*;
+
+    #[test]
+    fn pixel_diff_marks_changes() {
+        let mut a = DynamicImage::new_rgba8(2, 1);
+        a.as_mut_rgba8()
+            .unwrap()
+            .put_pixel(0, 0, Rgba([0, 0, 200, 200]));
+        let mut b = a.clone();
+        b.as_mut_rgba8()
+            .unwrap()
+            .put_pixel(1, 0, Rgba([255, 0, 0, 255]));
+
+        let diff = pixel_diff(&a, &b);
+        assert_eq!(diff.get_pixel(0, 0), Rgba([0, 0, 200, 50]));
+        assert_eq!(diff.get_pixel(1, 0), Rgba([255, 0, 255, 255]));
+    }
+
+    #[test]
+    fn reports_missing_and_extra_files() {
+        let expected = tempfile::tempdir().unwrap();
+        let actual = tempfile::tempdir().unwrap();
+        fs::write(expected.path().join("same.txt"), "same").unwrap();
+        fs::write(actual.path().join("same.txt"), "same").unwrap();
+        fs::write(expected.path().join("changed.txt"), "before").unwrap();
+        fs::write(actual.path().join("changed.txt"), "after").unwrap();
+        fs::write(expected.path().join("missing.txt"), "").unwrap();
+        fs::write(actual.path().join("extra.txt"), "").unwrap();
+
+        let failures = deep_compare_path(expected.path(), actual.path()).unwrap_err();
+        let reasons: Vec<(String, &CompareFailureReasonError)> = failures
+            .iter()
+            .map(|failure| {
+                assert_eq!(failure.a.file_name(), failure.b.file_name());
+                let name = failure.a.file_name().unwrap().to_str().unwrap().to_string();
+                (name, &failure.source)
+            })
+            .collect();
+        assert_eq!(reasons.len(), 3);
+        assert!(matches!(
+            reasons[0],
+            (ref name, CompareFailureReasonError::DifferentFileContents) if name == "changed.txt"
+        ));
+        assert!(matches!(
+            reasons[1],
+            (ref name, CompareFailureReasonError::MissingFile) if name == "missing.txt"
+        ));
+        assert!(matches!(
+            reasons[2],
+            (ref name, CompareFailureReasonError::ExtraFile) if name == "extra.txt"
+        ));
+    }
+}
+
+mod flags {
+    use std::path::Path;
+
+    use util::deep_dir_compare::deep_compare_path;
+    use util::run::run_with_args;
+
+    use super::*;
+
+    #[test]
+    fn dry_run_writes_nothing() {
+        let dir = tempfile::tempdir().unwrap();
+        let out_dir = dir.path().join("OUTPUT");
+        let input = concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/test_files/simple_cuts/input"
+        );
+
+        let output = run_with_args(vec![
+            "--dry-run".to_string(),
+            "--output".to_string(),
+            out_dir.to_str().unwrap().to_string(),
+            input.to_string(),
+        ])
+        .unwrap()
+        .output()
+        .unwrap();
+
+        assert!(output.status.success());
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        assert!(stdout.contains("Dry run: 2 configs would produce 2 outputs"));
+        assert!(!out_dir.exists());
+    }
+
+    #[test]
+    fn stats_counts_outputs() {
+        let input = concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/test_files/simple_cuts/input"
+        );
+
+        let output = run_with_args(vec![
+            "--dry-run".to_string(),
+            "--stats".to_string(),
+            input.to_string(),
+        ])
+        .unwrap()
+        .output()
+        .unwrap();
+
+        assert!(output.status.success());
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        assert!(stdout.contains("Configs      2"));
+        assert!(stdout.contains("Dmis         2"));
+        assert!(!stdout.contains("produced no icon states"));
+    }
+
+    #[test]
+    fn json_logs() {
+        let input = concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/test_files/simple_cuts/input"
+        );
+
+        let output = run_with_args(vec![
+            "--log-format".to_string(),
+            "json".to_string(),
+            "--verbose".to_string(),
+            "--dry-run".to_string(),
+            input.to_string(),
+        ])
+        .unwrap()
+        .output()
+        .unwrap();
+
+        assert!(output.status.success());
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        let read_configs: Vec<&str> = stdout
+            .lines()
+            .filter(|line| line.starts_with('{') && line.contains(r#""message":"Read config""#))
+            .collect();
+        assert_eq!(read_configs.len(), 2);
+        assert!(read_configs
+            .iter()
+            .all(|line| line.contains(r#""operation":"BitmaskSlice""#)));
+    }
+
+    #[test]
+    fn output_format_png_writes_sheets() {
+        let dir = tempfile::tempdir().unwrap();
+        let input =
+            Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/test_files/simple_cuts/input");
+
+        let output = run_with_args(vec![
+            "--output-format".to_string(),
+            "png".to_string(),
+            "--flatten".to_string(),
+            "--output".to_string(),
+            dir.path().to_str().unwrap().to_string(),
+            input.to_str().unwrap().to_string(),
+        ])
+        .unwrap()
+        .output()
+        .unwrap();
+
+        assert!(output.status.success());
+        let written: Vec<_> = std::fs::read_dir(dir.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect();
+        let mut names: Vec<&str> = written
+            .iter()
+            .map(|path| path.file_name().unwrap().to_str().unwrap())
+            .collect();
+        names.sort_unstable();
+        assert_eq!(names, ["4-corners-sheet.png", "5-corners-sheet.png"]);
+    }
+
+    #[test]
+    fn png_sheets_leave_inputs_alone() {
+        let dir = tempfile::tempdir().unwrap();
+        let input =
+            Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/test_files/simple_cuts/input");
+        for file in ["4-corners.png", "4-corners.toml"] {
+            std::fs::copy(input.join(file), dir.path().join(file)).unwrap();
+        }
+        let original = std::fs::read(dir.path().join("4-corners.png")).unwrap();
+
+        let output = run_with_args(vec![
+            "--output-format".to_string(),
+            "png".to_string(),
+            dir.path().to_str().unwrap().to_string(),
+        ])
+        .unwrap()
+        .output()
+        .unwrap();
+
+        assert!(output.status.success());
+        assert_eq!(
+            std::fs::read(dir.path().join("4-corners.png")).unwrap(),
+            original
+        );
+        assert!(dir.path().join("4-corners-sheet.png").exists());
+    }
+
+    #[test]
+    fn preview_gif_of_a_state() {
+        use image::codecs::gif::GifDecoder;
+        use image::{imageops, AnimationDecoder, RgbaImage};
+
+        let dir = tempfile::tempdir().unwrap();
+        let input =
+            Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/test_files/simple_cuts/input");
+        // two frames, the second with the red channel flipped so they differ
+        let first = image::open(input.join("4-corners.png")).unwrap().to_rgba8();
+        let mut second = first.clone();
+        for pixel in second.pixels_mut() {
+            pixel.0[0] = 255 - pixel.0[0];
+        }
+        let mut sheet = RgbaImage::new(first.width(), first.height() * 2);
+        imageops::overlay(&mut sheet, &first, 0, 0);
+        imageops::overlay(&mut sheet, &second, 0, i64::from(first.height()));
+        sheet.save(dir.path().join("anim.png")).unwrap();
+        std::fs::write(
+            dir.path().join("anim.toml"),
+            "template = \"bitmask/slice-32x32\"\n[animation]\ndelays = [1, 3]\n",
+        )
+        .unwrap();
+
+        let output = run_with_args(vec![
+            "--preview-gif".to_string(),
+            "0".to_string(),
+            dir.path().to_str().unwrap().to_string(),
+        ])
+        .unwrap()
+        .output()
+        .unwrap();
+
+        assert!(output.status.success());
+        assert!(dir.path().join("anim.dmi").exists());
+        let gif = std::fs::File::open(dir.path().join("anim-0.gif")).unwrap();
+        let frames = GifDecoder::new(gif)
+            .unwrap()
+            .into_frames()
+            .collect_frames()
+            .unwrap();
+        let delays: Vec<std::time::Duration> =
+            frames.iter().map(|frame| frame.delay().into()).collect();
+        assert_eq!(
+            delays,
+            [
+                std::time::Duration::from_millis(100),
+                std::time::Duration::from_millis(300)
+            ]
+        );
+        assert_ne!(frames[0].buffer(), frames[1].buffer());
+    }
+
+    #[test]
+    fn indexed_matches_rgba() {
+        let dir = tempfile::tempdir().unwrap();
+        let test_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/test_files/simple_cuts");
+
+        let output = run_with_args(vec![
+            "--indexed".to_string(),
+            "--flatten".to_string(),
+            "--output".to_string(),
+            dir.path().to_str().unwrap().to_string(),
+            test_dir.join("input").to_str().unwrap().to_string(),
+        ])
+        .unwrap()
+        .output()
+        .unwrap();
+
+        assert!(output.status.success());
+        let expected = test_dir.join("expected/input");
+        deep_compare_path(&expected, dir.path()).unwrap();
+        for name in ["4-corners.dmi", "5-corners.dmi"] {
+            let size = |path: &Path| std::fs::metadata(path.join(name)).unwrap().len();
+            assert!(size(dir.path()) < size(&expected));
+        }
+    }
+
+    #[test]
+    fn unwritable_output_is_reported() {
+        let dir = tempfile::tempdir().unwrap();
+        // a directory where the output file should go can't be written over
+        std::fs::create_dir(dir.path().join("4-corners.dmi")).unwrap();
+        let input = concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/test_files/simple_cuts/input"
+        );
+
+        let output = run_with_args(vec![
+            "--flatten".to_string(),
+            "--output".to_string(),
+            dir.path().to_str().unwrap().to_string(),
+            input.to_string(),
+        ])
+        .unwrap()
+        .output()
+        .unwrap();
+
+        assert!(!output.status.success());
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        assert!(stderr.contains("Failed to write output"));
+        assert!(stderr.contains("4-corners.dmi"));
+        assert!(!stderr.contains("panicked"));
+    }
+
+    #[test]
+    fn overwriting_outputs() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/test_files/simple_cuts/input"
+        );
+        let run = |extra: &[&str]| {
+            let mut args: Vec<String> = extra.iter().map(ToString::to_string).collect();
+            args.extend([
+                "--flatten".to_string(),
+                "--output".to_string(),
+                dir.path().to_str().unwrap().to_string(),
+                input.to_string(),
+            ]);
+            let output = run_with_args(args).unwrap().output().unwrap();
+            assert!(output.status.success());
+            output
+        };
+        let out_file = dir.path().join("4-corners.dmi");
+        let old_time = std::time::SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(1000);
+        let backdate = || {
+            std::fs::File::options()
+                .write(true)
+                .open(&out_file)
+                .unwrap()
+                .set_modified(old_time)
+                .unwrap();
+        };
+        let modified = || std::fs::metadata(&out_file).unwrap().modified().unwrap();
+
+        run(&[]);
+        let written = std::fs::read(&out_file).unwrap();
+
+        // identical outputs are left alone
+        backdate();
+        run(&[]);
+        assert_eq!(modified(), old_time);
+
+        run(&["--force"]);
+        assert_ne!(modified(), old_time);
+        assert_eq!(std::fs::read(&out_file).unwrap(), written);
+
+        std::fs::write(&out_file, "hand edited").unwrap();
+        let output = run(&["--no-clobber"]);
+        assert_eq!(std::fs::read(&out_file).unwrap(), b"hand edited");
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        assert!(stdout.contains("Output already exists"));
+
+        run(&[]);
+        assert_eq!(std::fs::read(&out_file).unwrap(), written);
+    }
+
+    #[test]
+    fn incremental_skips_unchanged_configs() {
+        let dir = tempfile::tempdir().unwrap();
+        let local_templates = dir.path().join("local_templates");
+        let input_dir = dir.path().join("input");
+        std::fs::create_dir_all(&local_templates).unwrap();
+        std::fs::create_dir_all(&input_dir).unwrap();
+        let template = local_templates.join("local.toml");
+        std::fs::write(&template, "template = \"bitmask/slice-32x32\"").unwrap();
+        let input =
+            Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/test_files/simple_cuts/input");
+        std::fs::copy(input.join("4-corners.png"), input_dir.join("4-corners.png")).unwrap();
+        std::fs::write(input_dir.join("4-corners.toml"), "template = \"local\"").unwrap();
+
+        let cache = dir.path().join("cache");
+
+        let skipped = || {
+            let output = run_with_args(vec![
+                "--incremental".to_string(),
+                "--templates".to_string(),
+                local_templates.to_str().unwrap().to_string(),
+                input_dir.to_str().unwrap().to_string(),
+            ])
+            .unwrap()
+            .env("XDG_CACHE_HOME", &cache)
+            .output()
+            .unwrap();
+            assert!(output.status.success());
+            let stdout = String::from_utf8(output.stdout).unwrap();
+            stdout.contains("Skipped 1 configs that were already up to date")
+        };
+        let touch = |path: &Path| {
+            std::fs::File::options()
+                .write(true)
+                .open(path)
+                .unwrap()
+                .set_modified(std::time::SystemTime::now())
+                .unwrap();
+        };
+        let out_file = input_dir.join("4-corners.dmi");
+
+        assert!(!skipped());
+        assert!(out_file.exists());
+        // builds are tracked in the cache, not next to the inputs
+        assert_eq!(
+            std::fs::read_dir(cache.join("hypnagogic")).unwrap().count(),
+            1
+        );
+        assert_eq!(std::fs::read_dir(&input_dir).unwrap().count(), 3);
+        assert!(skipped());
+
+        // templates the config pulls in count as inputs too
+        touch(&template);
+        assert!(!skipped());
+        assert!(skipped());
+
+        touch(&input_dir.join("4-corners.png"));
+        assert!(!skipped());
+
+        std::fs::remove_file(&out_file).unwrap();
+        assert!(!skipped());
+        assert!(out_file.exists());
+        assert!(skipped());
+    }
+
+    #[test]
+    fn layered_template_dirs() {
+        let dir = tempfile::tempdir().unwrap();
+        let local_templates = dir.path().join("local_templates");
+        let input_dir = dir.path().join("input");
+        std::fs::create_dir_all(&local_templates).unwrap();
+        std::fs::create_dir_all(&input_dir).unwrap();
+        std::fs::write(
+            local_templates.join("local.toml"),
+            "template = \"bitmask/slice-32x32\"",
+        )
+        .unwrap();
+        let input =
+            Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/test_files/simple_cuts/input");
+        std::fs::copy(input.join("4-corners.png"), input_dir.join("4-corners.png")).unwrap();
+
+        let run = |config: &str| {
+            std::fs::write(input_dir.join("4-corners.toml"), config).unwrap();
+            run_with_args(vec![
+                "--templates".to_string(),
+                local_templates.to_str().unwrap().to_string(),
+                "--dry-run".to_string(),
+                input_dir.to_str().unwrap().to_string(),
+            ])
+            .unwrap()
+            .output()
+            .unwrap()
+        };
+
+        assert!(run("template = \"local\"").status.success());
+
+        let missing = run("template = \"nowhere\"");
+        assert!(!missing.status.success());
+        let stderr = String::from_utf8(missing.stderr).unwrap();
+        assert!(stderr.contains("local_templates"));
+        assert_eq!(stderr.matches("Expected to find a config at").count(), 2);
+    }
+
+    #[test]
+    fn keep_going_reports_every_failure() {
+        let dir = tempfile::tempdir().unwrap();
+        let input =
+            Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/test_files/simple_cuts/input");
+        for file in ["4-corners.png", "4-corners.toml"] {
+            std::fs::copy(input.join(file), dir.path().join(file)).unwrap();
+        }
+        for broken in ["a.toml", "b.toml"] {
+            std::fs::write(dir.path().join(broken), "not = [valid").unwrap();
+        }
+
+        let output = run_with_args(vec![
+            "--keep-going".to_string(),
+            "--dry-run".to_string(),
+            dir.path().to_str().unwrap().to_string(),
+        ])
+        .unwrap()
+        .output()
+        .unwrap();
+
+        assert!(!output.status.success());
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        assert!(stdout.contains("Dry run: 1 configs would produce 1 outputs"));
+        assert!(stdout.contains("Invalid Config File (2)\n    a.toml\n    b.toml"));
+        assert!(stdout.contains("Failed to process 2 of 3 files"));
+    }
+
+    #[test]
+    fn keep_going_reports_corrupt_inputs() {
+        let dir = tempfile::tempdir().unwrap();
+        let input =
+            Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/test_files/simple_cuts/input");
+        for file in ["4-corners.png", "4-corners.toml"] {
+            std::fs::copy(input.join(file), dir.path().join(file)).unwrap();
+        }
+        std::fs::copy(
+            input.join("4-corners.toml"),
+            dir.path().join("bad.png.toml"),
+        )
+        .unwrap();
+        std::fs::write(dir.path().join("bad.png"), "not a png").unwrap();
+
+        let output = run_with_args(vec![
+            "--keep-going".to_string(),
+            "--dry-run".to_string(),
+            dir.path().to_str().unwrap().to_string(),
+        ])
+        .unwrap()
+        .output()
+        .unwrap();
+
+        assert_eq!(output.status.code(), Some(1));
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        assert!(stdout.contains("Dry run: 1 configs would produce 1 outputs"));
+        assert!(stdout.contains("Invalid Input File (1)\n    bad.png.toml"));
+        assert!(stdout.contains("Failed to process 1 of 2 files"));
+    }
+}
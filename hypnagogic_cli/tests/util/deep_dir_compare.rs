@@ -1,8 +1,10 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{self, File};
 use std::path::{Path, PathBuf};
 
+use dmi::error::DmiError;
 use dmi::icon::Icon;
-use image::DynamicImage;
+use image::{DynamicImage, GenericImageView, ImageError, Rgba, RgbaImage};
 use thiserror::Error;
 use tracing::error;
 use walkdir::WalkDir;
@@ -83,12 +85,64 @@ pub fn compare_dmi(dmi1: &Icon, dmi2: &Icon) -> Result<(), DmiCompareError> {
     }
 }
 
+/// Color that differing pixels are marked with by `pixel_diff`
+const DIFF_COLOR: Rgba<u8> = Rgba([255, 0, 255, 255]);
+
+/// Builds an image highlighting where two frames differ. Differing pixels,
+/// including any only covered by the larger frame, are magenta. Matching
+/// pixels are kept but faded so the differences stand out.
+pub fn pixel_diff(a: &DynamicImage, b: &DynamicImage) -> DynamicImage {
+    let width = a.width().max(b.width());
+    let height = a.height().max(b.height());
+    let mut out = RgbaImage::new(width, height);
+    for (x, y, pixel) in out.enumerate_pixels_mut() {
+        let pixel_a = a.in_bounds(x, y).then(|| a.get_pixel(x, y));
+        let pixel_b = b.in_bounds(x, y).then(|| b.get_pixel(x, y));
+        *pixel = match (pixel_a, pixel_b) {
+            (Some(pixel_a), Some(pixel_b)) if pixel_a == pixel_b => {
+                let Rgba([r, g, b, alpha]) = pixel_a;
+                Rgba([r, g, b, alpha / 4])
+            }
+            _ => DIFF_COLOR,
+        };
+    }
+    DynamicImage::ImageRgba8(out)
+}
+
+/// Writes a `pixel_diff` of every mismatched frame pair in a
+/// `DifferentIconStatePixelData` error to `dir`, named `{state}-{index}.png`.
+/// Other errors have no pixel data, so nothing is written for them.
+pub fn write_pixel_diffs(error: &DmiCompareError, dir: &Path) -> Result<Vec<PathBuf>, ImageError> {
+    let DmiCompareError::DifferentIconStatePixelData(states) = error else {
+        return Ok(vec![]);
+    };
+    fs::create_dir_all(dir)?;
+    let mut written = vec![];
+    for (state, frames) in states {
+        for (index, (a, b)) in frames.iter().enumerate() {
+            let path = dir.join(format!("{state}-{index}.png"));
+            pixel_diff(a, b).save(&path)?;
+            written.push(path);
+        }
+    }
+    written.sort();
+    Ok(written)
+}
+
 #[derive(Debug, Error)]
 pub enum CompareFailureReasonError {
     #[error("Error comparing DMIs: {0}")]
     DmiCompareError(#[from] DmiCompareError),
+    #[error("Error loading DMI: {0}")]
+    DmiLoadError(#[from] DmiError),
     #[error("Error walking directory: {0}")]
     IoError(#[from] std::io::Error),
+    #[error("File only exists in the first tree")]
+    MissingFile,
+    #[error("File only exists in the second tree")]
+    ExtraFile,
+    #[error("File contents differ")]
+    DifferentFileContents,
 }
 
 #[derive(Debug)]
@@ -104,36 +158,71 @@ impl CompareFailureError {
     }
 }
 
+/// Every file under `root`, keyed by its path relative to `root`
+fn collect_files(root: &Path) -> Result<BTreeMap<PathBuf, PathBuf>, walkdir::Error> {
+    let mut files = BTreeMap::new();
+    for entry in WalkDir::new(root) {
+        let entry = entry?;
+        if entry.file_type().is_file() {
+            let relative = entry.path().strip_prefix(root).unwrap().to_path_buf();
+            files.insert(relative, entry.into_path());
+        }
+    }
+    Ok(files)
+}
+
+/// Compares two files, as dmis if they have the `dmi` extension and byte for
+/// byte otherwise
+fn compare_file(a: &Path, b: &Path) -> Result<(), CompareFailureReasonError> {
+    if a.extension().is_some_and(|extension| extension == "dmi") {
+        let dmi1 = Icon::load(File::open(a)?)?;
+        let dmi2 = Icon::load(File::open(b)?)?;
+        compare_dmi(&dmi1, &dmi2)?;
+    } else if fs::read(a)? != fs::read(b)? {
+        return Err(CompareFailureReasonError::DifferentFileContents);
+    }
+    Ok(())
+}
+
+/// Compares every file in two directory trees, matching files up by their
+/// path relative to each root. Files that only exist in one tree are reported
+/// as missing or extra.
 pub fn deep_compare_path(path1: &Path, path2: &Path) -> Result<(), Vec<CompareFailureError>> {
-    let path1_iter = WalkDir::new(path1).into_iter();
-    let path2_iter = WalkDir::new(path2).into_iter();
-
-    let res: Vec<_> = path1_iter
-        .zip(path2_iter)
-        .filter_map(|(entry1, entry2)| {
-            if let (Ok(entry1), Ok(entry2)) = (entry1, entry2) {
-                if entry1.file_type().is_file() && entry2.file_type().is_file() {
-                    let file1 = std::fs::File::open(entry1.path()).unwrap();
-                    let dmi1 = Icon::load(file1).unwrap();
-
-                    let file2 = std::fs::File::open(entry2.path()).unwrap();
-                    let dmi2 = Icon::load(file2).unwrap();
-                    let res = compare_dmi(&dmi1, &dmi2);
-                    res.err().map(|inner_err| {
-                        CompareFailureError::new(
-                            entry1.into_path(),
-                            entry2.into_path(),
-                            CompareFailureReasonError::DmiCompareError(inner_err),
-                        )
-                    })
-                } else {
-                    None
-                }
-            } else {
-                panic!("Unable to walk directory (check ownership and permissions)");
-            }
+    let walk = |root: &Path| {
+        collect_files(root).unwrap_or_else(|err| {
+            panic!("Unable to walk directory {root:?} (check ownership and permissions): {err}")
         })
-        .collect();
+    };
+    let files1 = walk(path1);
+    let files2 = walk(path2);
+
+    let mut res = vec![];
+    for (relative, file1) in &files1 {
+        let Some(file2) = files2.get(relative) else {
+            res.push(CompareFailureError::new(
+                file1.clone(),
+                path2.join(relative),
+                CompareFailureReasonError::MissingFile,
+            ));
+            continue;
+        };
+        if let Err(reason) = compare_file(file1, file2) {
+            res.push(CompareFailureError::new(
+                file1.clone(),
+                file2.clone(),
+                reason,
+            ));
+        }
+    }
+    for (relative, file2) in &files2 {
+        if !files1.contains_key(relative) {
+            res.push(CompareFailureError::new(
+                path1.join(relative),
+                file2.clone(),
+                CompareFailureReasonError::ExtraFile,
+            ));
+        }
+    }
 
     if res.is_empty() {
         Ok(())
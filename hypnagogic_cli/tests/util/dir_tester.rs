@@ -1,6 +1,10 @@
 use std::fs::read_to_string;
 
-use crate::util::deep_dir_compare::deep_compare_path;
+use crate::util::deep_dir_compare::{
+    deep_compare_path,
+    write_pixel_diffs,
+    CompareFailureReasonError,
+};
 use crate::util::run::run_with_args;
 
 pub struct DirTester {
@@ -33,7 +37,16 @@ impl DirTester {
         let res = deep_compare_path(&expected_path, &out_dir);
 
         if let Err(res) = res {
-            panic!("Deep compare failed: {res:?}");
+            // written out so the differences can be looked at (or uploaded by CI)
+            let diff_dir = self.dir.join("diff-OUTPUT");
+            let mut diffs = vec![];
+            for failure in &res {
+                if let CompareFailureReasonError::DmiCompareError(err) = &failure.source {
+                    let stem = failure.a.file_stem().unwrap();
+                    diffs.extend(write_pixel_diffs(err, &diff_dir.join(stem)).unwrap());
+                }
+            }
+            panic!("Deep compare failed: {res:?}\nPixel diffs: {diffs:?}");
         }
     }
 }
@@ -1,7 +1,7 @@
 use std::collections::BTreeMap;
 
 use fixed_map::Map;
-use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
 
 use crate::util::corners::{CornerType, Side};
 
@@ -83,13 +83,12 @@ impl<'de> Deserialize<'de> for Positions {
     where
         D: Deserializer<'de>,
     {
-        Deserialize::deserialize(deserializer).map(|PositionsHelper { map }| {
-            let mut result = Map::new();
-            for (k, v) in map {
-                result.insert(k.as_str().into(), v);
-            }
-            Positions(result)
-        })
+        let PositionsHelper { map } = Deserialize::deserialize(deserializer)?;
+        let mut result = Map::new();
+        for (k, v) in map {
+            result.insert(k.parse().map_err(de::Error::custom)?, v);
+        }
+        Ok(Positions(result))
     }
 }
 
@@ -104,6 +103,13 @@ impl Default for Positions {
     }
 }
 
+/// Parses a signature key from a config table, erroring rather than panicking
+/// on keys that don't fit in a `u8`
+fn parse_signature<E: de::Error>(key: &str) -> Result<u8, E> {
+    key.parse()
+        .map_err(|_| E::custom(format!("\"{key}\" is not a valid adjacency signature")))
+}
+
 #[derive(Clone, Eq, PartialEq, Debug, Default)]
 pub struct Prefabs(pub BTreeMap<u8, u32>);
 
@@ -133,13 +139,12 @@ impl<'de> Deserialize<'de> for Prefabs {
     where
         D: Deserializer<'de>,
     {
-        Deserialize::deserialize(deserializer).map(|PrefabsHelper { map }| {
-            let mut result = BTreeMap::new();
-            for (k, v) in map {
-                result.insert(k.parse().unwrap(), v);
-            }
-            Prefabs(result)
-        })
+        let PrefabsHelper { map } = Deserialize::deserialize(deserializer)?;
+        let mut result = BTreeMap::new();
+        for (k, v) in map {
+            result.insert(parse_signature(&k)?, v);
+        }
+        Ok(Prefabs(result))
     }
 }
 
@@ -172,19 +177,110 @@ impl<'de> Deserialize<'de> for PrefabOverlays {
     where
         D: Deserializer<'de>,
     {
-        Deserialize::deserialize(deserializer).map(|PrefabOverlaysHelper { map }| {
-            let mut result = BTreeMap::new();
-            for (k, v) in map {
-                result.insert(k.parse().unwrap(), v);
-            }
-            PrefabOverlays(result)
-        })
+        let PrefabOverlaysHelper { map } = Deserialize::deserialize(deserializer)?;
+        let mut result = BTreeMap::new();
+        for (k, v) in map {
+            result.insert(parse_signature(&k)?, v);
+        }
+        Ok(PrefabOverlays(result))
+    }
+}
+
+/// Descriptions of what adjacency signatures represent, keyed by signature
+#[derive(Clone, Eq, PartialEq, Debug, Default)]
+pub struct StateDocs(pub BTreeMap<u8, String>);
+
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(transparent)]
+struct StateDocsHelper {
+    map: BTreeMap<String, String>,
+}
+
+impl Serialize for StateDocs {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut map = BTreeMap::new();
+
+        for (k, v) in &self.0 {
+            map.insert(k.to_string(), v.clone());
+        }
+
+        StateDocsHelper { map }.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for StateDocs {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let StateDocsHelper { map } = Deserialize::deserialize(deserializer)?;
+        let mut result = BTreeMap::new();
+        for (k, v) in map {
+            result.insert(parse_signature(&k)?, v);
+        }
+        Ok(StateDocs(result))
     }
 }
 
 #[derive(Clone, PartialEq, Debug, Default, Serialize, Deserialize)]
 pub struct Animation {
     pub delays: Vec<f32>,
+    /// Delays for specific directions. When these differ from `delays` on a
+    /// config producing dirs, `split_dirs` has to be set, which outputs a
+    /// separate `{signature}-{dir}` state per direction
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub per_direction_delays: Option<DirectionalDelays>,
+}
+
+/// Delays for specific directions, overriding `Animation::delays` for that
+/// direction
+#[derive(Clone, PartialEq, Debug)]
+pub struct DirectionalDelays(pub Map<Side, Vec<f32>>);
+
+impl DirectionalDelays {
+    #[must_use]
+    pub fn get(&self, key: Side) -> Option<&Vec<f32>> {
+        self.0.get(key)
+    }
+}
+
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(transparent)]
+struct DirectionalDelaysHelper {
+    map: BTreeMap<String, Vec<f32>>,
+}
+
+impl Serialize for DirectionalDelays {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut map = BTreeMap::new();
+
+        for (k, v) in self.0.iter() {
+            map.insert(k.to_string(), v.clone());
+        }
+
+        DirectionalDelaysHelper { map }.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for DirectionalDelays {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let DirectionalDelaysHelper { map } = Deserialize::deserialize(deserializer)?;
+        let mut result = Map::new();
+        for (k, v) in map {
+            result.insert(k.parse().map_err(de::Error::custom)?, v);
+        }
+        Ok(DirectionalDelays(result))
+    }
 }
 
 #[derive(Clone, Eq, PartialEq, Debug)]
@@ -223,13 +319,12 @@ impl<'de> Deserialize<'de> for SlicePoint {
     where
         D: Deserializer<'de>,
     {
-        Deserialize::deserialize(deserializer).map(|SlicePointHelper { map }| {
-            let mut result = Map::new();
-            for (k, v) in map {
-                result.insert(k.as_str().into(), v);
-            }
-            SlicePoint(result)
-        })
+        let SlicePointHelper { map } = Deserialize::deserialize(deserializer)?;
+        let mut result = Map::new();
+        for (k, v) in map {
+            result.insert(k.parse().map_err(de::Error::custom)?, v);
+        }
+        Ok(SlicePoint(result))
     }
 }
 
@@ -243,3 +338,19 @@ impl Default for SlicePoint {
         SlicePoint(map)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn invalid_keys_are_errors() {
+        let delays: DirectionalDelays = toml::from_str("north = [1.0]").unwrap();
+        assert_eq!(delays.get(Side::North), Some(&vec![1.0]));
+
+        let error = toml::from_str::<DirectionalDelays>("nroth = [1.0]").unwrap_err();
+        assert!(error.to_string().contains("\"nroth\" is not a side"));
+        assert!(toml::from_str::<SlicePoint>("up = 4").is_err());
+        assert!(toml::from_str::<Positions>("convexx = 0").is_err());
+    }
+}
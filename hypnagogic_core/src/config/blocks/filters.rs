@@ -0,0 +1,131 @@
+use dmi::icon::IconState;
+use image::imageops::FilterType;
+use image::DynamicImage;
+use serde::{Deserialize, Serialize};
+
+use crate::util::color::Color;
+use crate::util::icon_ops::{
+    alpha_threshold,
+    normalize_transparent,
+    quantize_to_palette,
+    recolor,
+    snap_to_palette,
+};
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FlipAxis {
+    Horizontal,
+    Vertical,
+}
+
+/// Resampling filter used when scaling icons
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScaleFilter {
+    /// Keeps hard pixel edges, what you want for pixel art
+    Nearest,
+    /// Bilinear
+    Triangle,
+    CatmullRom,
+    Gaussian,
+    Lanczos3,
+}
+
+impl From<ScaleFilter> for FilterType {
+    fn from(filter: ScaleFilter) -> Self {
+        match filter {
+            ScaleFilter::Nearest => FilterType::Nearest,
+            ScaleFilter::Triangle => FilterType::Triangle,
+            ScaleFilter::CatmullRom => FilterType::CatmullRom,
+            ScaleFilter::Gaussian => FilterType::Gaussian,
+            ScaleFilter::Lanczos3 => FilterType::Lanczos3,
+        }
+    }
+}
+
+/// A single post processing step, applied to every output frame
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "filter")]
+pub enum FilterStep {
+    /// Makes pixels fully transparent below `threshold` alpha, and fully
+    /// opaque otherwise
+    AlphaThreshold { threshold: u8 },
+    /// Replaces one exact color with another
+    Recolor { from: Color, to: Color },
+    /// Clears the color of fully transparent pixels
+    NormalizeTransparent,
+    /// Snaps every visible pixel to the nearest color in the palette
+    SnapToPalette { palette: Vec<Color> },
+    /// Snaps the color of every visible pixel to the nearest palette color by
+    /// rgb distance, keeping each pixel's alpha
+    QuantizeToPalette { palette: Vec<Color> },
+    /// Mirrors the frame along an axis
+    Flip { axis: FlipAxis },
+}
+
+impl FilterStep {
+    pub fn apply(&self, image: &mut DynamicImage) {
+        match self {
+            FilterStep::AlphaThreshold { threshold } => alpha_threshold(image, *threshold),
+            FilterStep::Recolor { from, to } => recolor(image, *from, *to),
+            FilterStep::NormalizeTransparent => normalize_transparent(image),
+            FilterStep::SnapToPalette { palette } => snap_to_palette(image, palette),
+            FilterStep::QuantizeToPalette { palette } => quantize_to_palette(image, palette),
+            FilterStep::Flip { axis } => {
+                *image = match axis {
+                    FlipAxis::Horizontal => image.fliph(),
+                    FlipAxis::Vertical => image.flipv(),
+                };
+            }
+        }
+    }
+}
+
+/// Applies each step in order to every frame of every state
+pub fn apply_filters(steps: &[FilterStep], states: &mut [IconState]) {
+    for image in states.iter_mut().flat_map(|state| state.images.iter_mut()) {
+        for step in steps {
+            step.apply(image);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{GenericImageView, Rgba, RgbaImage};
+
+    use super::*;
+
+    #[derive(Deserialize)]
+    struct Chain {
+        post_process: Vec<FilterStep>,
+    }
+
+    #[test]
+    fn chain_applies_in_order() {
+        let chain: Chain = toml::from_str(
+            r##"
+            [[post_process]]
+            filter = "AlphaThreshold"
+            threshold = 128
+
+            [[post_process]]
+            filter = "Recolor"
+            from = "#FF0000"
+            to = "#0000FF"
+            "##,
+        )
+        .unwrap();
+
+        let red = DynamicImage::ImageRgba8(RgbaImage::from_pixel(2, 2, Rgba([255, 0, 0, 200])));
+        let mut states = vec![IconState {
+            images: vec![red],
+            ..Default::default()
+        }];
+        apply_filters(&chain.post_process, &mut states);
+
+        // the recolor only matches because the threshold already made the pixel opaque
+        assert_eq!(states[0].images[0].get_pixel(0, 0), Rgba([0, 0, 255, 255]));
+    }
+}
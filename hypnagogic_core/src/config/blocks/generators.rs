@@ -1,9 +1,11 @@
-use serde::{Deserialize, Serialize};
+use image::DynamicImage;
+use serde::de::IntoDeserializer;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
 use crate::generation::rect::{Border, BorderStyle};
-use crate::generation::text::Alignment;
+use crate::generation::text::{Alignment, TextStyle, VerticalAlignment};
 use crate::util::color::Color;
-use crate::util::icon_ops::pick_contrasting_colors;
+use crate::util::icon_ops::{colors_in_image, pick_contrasting_colors};
 
 #[derive(Debug, Clone, Copy, Eq, PartialEq, Default, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
@@ -20,8 +22,8 @@ fn white() -> Color {
     Color::new(255, 255, 255, 255)
 }
 
-fn black() -> Color {
-    Color::new(0, 0, 0, 255)
+fn black_text() -> TextColor {
+    TextColor::Color(Color::new(0, 0, 0, 255))
 }
 
 fn bottom_right() -> Position {
@@ -40,21 +42,121 @@ fn default_alignment() -> Alignment {
     Alignment::Right
 }
 
+/// What the map icon is filled with, under the text and borders
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Background {
+    /// Filled with `base_color`
+    #[default]
+    Solid,
+    /// Blends from `from` at the top/left to `to` at the bottom/right
+    Gradient {
+        from: Color,
+        to: Color,
+        direction: GradientDirection,
+    },
+}
+
+impl Background {
+    /// The color the background looks like overall, for picking text colors
+    #[must_use]
+    pub fn average(self, base_color: Color) -> Color {
+        match self {
+            Background::Solid => base_color,
+            Background::Gradient { from, to, .. } => from.lerp(to, 0.5),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GradientDirection {
+    /// Top to bottom
+    Vertical,
+    /// Left to right
+    Horizontal,
+}
+
+/// Color of map icon text. Written in configs as either a color, or `"auto"`
+/// to pick black or white depending on how light the base color is
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum TextColor {
+    Auto,
+    Color(Color),
+}
+
+impl TextColor {
+    /// Luminance above which auto text is black rather than white
+    pub const AUTO_THRESHOLD: f32 = 0.5;
+
+    /// The color text should be drawn in over `base_color`
+    #[must_use]
+    pub fn resolve(self, base_color: Color) -> Color {
+        match self {
+            TextColor::Color(color) => color,
+            TextColor::Auto if base_color.luminance() > Self::AUTO_THRESHOLD => {
+                Color::new_rgb(0, 0, 0)
+            }
+            TextColor::Auto => Color::new_rgb(255, 255, 255),
+        }
+    }
+}
+
+impl Serialize for TextColor {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match self {
+            TextColor::Auto => serializer.serialize_str("auto"),
+            TextColor::Color(color) => color.serialize(serializer),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for TextColor {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let string = String::deserialize(deserializer)?;
+        if string == "auto" {
+            return Ok(TextColor::Auto);
+        }
+        Color::deserialize(string.into_deserializer()).map(TextColor::Color)
+    }
+}
+
 #[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
 pub struct MapIcon {
     pub icon_state_name: String,
+    /// Picks `base_color`, `text_color` and the outer border color from the
+    /// colors of the input, instead of using the configured ones
     #[serde(default)]
-    pub automatic: bool,
+    pub derive_colors_from_input: bool,
     #[serde(default = "white")]
     pub base_color: Color,
     #[serde(default)]
+    pub background: Background,
+    /// Rounds the corners of the background and borders, leaving the pixels
+    /// outside of them transparent. 0 keeps the corners square
+    #[serde(default)]
+    pub corner_radius: u32,
+    #[serde(default)]
     pub text: Option<String>,
-    #[serde(default = "black")]
-    pub text_color: Color,
+    #[serde(default = "black_text")]
+    pub text_color: TextColor,
     #[serde(default = "bottom_right")]
     pub text_position: Position,
     #[serde(default = "default_alignment")]
     pub text_alignment: Alignment,
+    /// Overrides where the text sits vertically. If unset, it follows
+    /// `text_position`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub text_vertical_alignment: Option<VerticalAlignment>,
+    #[serde(default)]
+    pub text_style: TextStyle,
     #[serde(default)]
     pub inner_border: Option<Border>,
     #[serde(default = "default_outer_border")]
@@ -65,12 +167,16 @@ impl Default for MapIcon {
     fn default() -> Self {
         Self {
             icon_state_name: "map_icon".to_string(),
-            automatic: false,
+            derive_colors_from_input: false,
             base_color: Color::new(255, 255, 255, 255),
+            background: Background::Solid,
+            corner_radius: 0,
             text: Some("DEF".to_string()),
-            text_color: Color::new(0, 0, 0, 255),
+            text_color: TextColor::Color(Color::new(0, 0, 0, 255)),
             text_position: Position::BottomRight,
             text_alignment: Alignment::Right,
+            text_vertical_alignment: None,
+            text_style: TextStyle::default(),
             inner_border: None,
             outer_border: Some(Border {
                 style: BorderStyle::Solid,
@@ -81,13 +187,33 @@ impl Default for MapIcon {
 }
 
 impl MapIcon {
+    /// The map icon to generate for `input`, with colors derived from it if
+    /// `derive_colors_from_input` is set
+    #[must_use]
+    pub fn for_input(&self, input: &DynamicImage) -> MapIcon {
+        // collecting the colors means going over the whole input
+        if !self.derive_colors_from_input {
+            return self.clone();
+        }
+        let mut map_icon = self.clone();
+        let visible: Vec<Color> = colors_in_image(input)
+            .into_iter()
+            .filter(|color| color.alpha != 0)
+            .collect();
+        map_icon.gen_colors(&visible);
+        map_icon
+    }
+
+    /// Uses the darkest and lightest of `colors` for the background and the
+    /// text/border respectively. Does nothing unless `derive_colors_from_input`
+    /// is set, or if there are no colors.
     pub fn gen_colors(&mut self, colors: &[Color]) {
-        if !self.automatic {
+        if !self.derive_colors_from_input || colors.is_empty() {
             return;
         }
         let sorted_colors = pick_contrasting_colors(colors);
         self.base_color = sorted_colors.0;
-        self.text_color = sorted_colors.1;
+        self.text_color = TextColor::Color(sorted_colors.1);
         self.outer_border = Some(Border {
             style: BorderStyle::Solid,
             color: sorted_colors.1,
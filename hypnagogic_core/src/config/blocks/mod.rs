@@ -1,2 +1,3 @@
 pub mod cutters;
+pub mod filters;
 pub mod generators;
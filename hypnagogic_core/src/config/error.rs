@@ -1,6 +1,7 @@
 use thiserror::Error;
 
 use crate::config::template_resolver::error::TemplateError;
+use crate::config::unknown_keys::UnknownKey;
 
 #[derive(Debug, Error)]
 pub enum ConfigError {
@@ -8,10 +9,25 @@ pub enum ConfigError {
     Template(#[from] TemplateError),
     #[error("Error while parsing config into toml:\n{0}")]
     Toml(#[from] toml::de::Error),
-    #[error("error in config")]
+    #[error("Error while parsing config from json:\n{0}")]
+    Json(#[from] serde_json::Error),
+    #[error("Error while parsing config from yaml:\n{0}")]
+    Yaml(#[from] serde_yaml::Error),
+    #[error("Error in config:\n{0}")]
     Config(String),
+    #[error("Config is version {found}, but the newest version this supports is {latest}")]
+    UnsupportedVersion { found: u32, latest: u32 },
+    #[error("Unknown keys in config, these do nothing:\n{}", list_unknown_keys(.0))]
+    UnknownKeys(Vec<UnknownKey>),
     #[error("Generic IO Error: {0}")]
     IO(#[from] std::io::Error),
 }
 
+fn list_unknown_keys(keys: &[UnknownKey]) -> String {
+    keys.iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
 pub type ConfigResult<T> = Result<T, ConfigError>;
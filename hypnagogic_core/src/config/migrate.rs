@@ -0,0 +1,134 @@
+use std::io::Read;
+
+use toml::map::Map;
+use toml::Value;
+
+use crate::config::config_from_value;
+use crate::config::error::{ConfigError, ConfigResult};
+use crate::config::template_resolver::TemplateResolver;
+use crate::operations::IconOperation;
+
+/// Blocks that cutter2 configs wrote as separate `_x` and `_y` scalars
+const SPLIT_BLOCKS: [&str; 4] = [
+    "icon_size",
+    "output_icon_pos",
+    "output_icon_size",
+    "cut_pos",
+];
+
+/// Reads a legacy cutter2 yaml config and converts it to an operation.
+///
+/// cutter2 only ever did bitmask slicing, so configs without a `mode` are
+/// treated as `BitmaskSlice`. Templates are resolved the same as toml configs.
+/// # Errors
+/// Errors if the yaml can't be parsed, or the migrated config is invalid
+pub fn from_cutter2_yaml<R: Read>(
+    input: &mut R,
+    resolver: impl TemplateResolver,
+) -> ConfigResult<IconOperation> {
+    config_from_value(migrate_cutter2_yaml(input)?, resolver)
+}
+
+/// Converts a legacy cutter2 yaml config in to the equivalent toml value,
+/// without resolving templates. Useful for writing the migrated config back
+/// out with `toml::to_string`.
+/// # Errors
+/// Errors if the yaml can't be parsed, or isn't a cutter2 config
+pub fn migrate_cutter2_yaml<R: Read>(input: &mut R) -> ConfigResult<Value> {
+    let Value::Table(mut table) = serde_yaml::from_reader(input)? else {
+        return Err(ConfigError::Config(
+            "cutter2 config must be a mapping".to_string(),
+        ));
+    };
+
+    for block in SPLIT_BLOCKS {
+        let x = table.remove(&format!("{block}_x"));
+        let y = table.remove(&format!("{block}_y"));
+        let (x, y) = match (x, y) {
+            (None, None) => continue,
+            (Some(x), Some(y)) => (x, y),
+            _ => {
+                return Err(ConfigError::Config(format!(
+                    "{block}_x and {block}_y must be set together"
+                )));
+            }
+        };
+        if table.contains_key(block) {
+            return Err(ConfigError::Config(format!(
+                "{block} is set both as a block and as {block}_x/{block}_y"
+            )));
+        }
+        let mut xy = Map::new();
+        xy.insert("x".to_string(), x);
+        xy.insert("y".to_string(), y);
+        table.insert(block.to_string(), Value::Table(xy));
+    }
+
+    table
+        .entry("mode")
+        .or_insert_with(|| Value::String("BitmaskSlice".to_string()));
+
+    Ok(Value::Table(table))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::File;
+    use std::io::Cursor;
+    use std::path::Path;
+
+    use super::*;
+    use crate::config::read_config;
+    use crate::config::template_resolver::file_resolver::FileResolver;
+    use crate::config::template_resolver::NullResolver;
+
+    #[test]
+    fn migrates_split_scalars() {
+        let yaml = r"
+produce_dirs: true
+smooth_diagonally: false
+icon_size_x: 32
+icon_size_y: 48
+output_icon_pos_x: 0
+output_icon_pos_y: 0
+output_icon_size_x: 32
+output_icon_size_y: 48
+cut_pos_x: 16
+cut_pos_y: 24
+positions:
+  convex: 0
+  concave: 1
+  horizontal: 2
+  vertical: 3
+";
+        let toml = r#"
+        mode = "BitmaskSlice"
+        produce_dirs = true
+        smooth_diagonally = false
+        icon_size = { x = 32, y = 48 }
+        output_icon_pos = { x = 0, y = 0 }
+        output_icon_size = { x = 32, y = 48 }
+        cut_pos = { x = 16, y = 24 }
+        positions = { convex = 0, concave = 1, horizontal = 2, vertical = 3 }
+        "#;
+
+        let migrated = from_cutter2_yaml(&mut Cursor::new(yaml), NullResolver).unwrap();
+        let expected = read_config(&mut Cursor::new(toml), NullResolver).unwrap();
+        assert_eq!(migrated, expected);
+
+        assert!(matches!(
+            migrate_cutter2_yaml(&mut Cursor::new("icon_size_x: 32")),
+            Err(ConfigError::Config(message)) if message.contains("icon_size_y")
+        ));
+    }
+
+    #[test]
+    fn migrates_templated_fixtures() {
+        let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+        let resolver = FileResolver::new(&root.join("../templates")).unwrap();
+        for fixture in ["4-corners.yaml", "5-corners.yaml"] {
+            let mut file = File::open(root.join("tests/res").join(fixture)).unwrap();
+            from_cutter2_yaml(&mut file, resolver.clone()).unwrap();
+        }
+    }
+}
@@ -1,22 +1,38 @@
+use std::collections::HashSet;
 use std::io::{read_to_string, Read, Seek};
 
-use serde::Deserialize;
 use template_resolver::TemplateResolver;
 use toml::map::Map;
 use toml::Value;
-use tracing::{debug, trace};
+use tracing::{debug, trace, warn};
 
-use crate::config::error::ConfigResult;
-use crate::config::template_resolver::error::TemplateResult;
+use crate::config::error::{ConfigError, ConfigResult};
+use crate::config::template_resolver::error::{TemplateError, TemplateResult};
+use crate::config::unknown_keys::deserialize_operation;
 use crate::operations::IconOperation;
 use crate::util::deep_merge_toml;
 
 pub mod blocks;
 pub mod error;
+pub mod migrate;
 pub mod template_resolver;
+pub mod unknown_keys;
 
 pub const LATEST_VERSION: &str = "1";
 
+/// How many templates deep a config can go before resolution gives up
+pub const MAX_TEMPLATE_DEPTH: usize = 100;
+
+/// Keys that have been renamed, as (table, old name, new name). Old names are
+/// moved over to the new ones before a config is read, so older configs keep
+/// working
+const RENAMED_KEYS: [(&str, &str, &str); 1] =
+    [("map_icon", "automatic", "derive_colors_from_input")];
+
+/// Top level keys from older versions that nothing reads anymore. They're
+/// dropped before a config is read rather than reported as unknown
+const RETIRED_KEYS: [&str; 1] = ["file_prefix"];
+
 #[tracing::instrument(skip(resolver, input))]
 pub fn read_config<R: Read + Seek>(
     input: &mut R,
@@ -25,13 +41,92 @@ pub fn read_config<R: Read + Seek>(
     let reader_string = read_to_string(input)?;
     let toml_value = toml::from_str(&reader_string)?;
 
-    let result_value = resolve_templates(toml_value, resolver)?;
+    config_from_value(toml_value, resolver)
+}
+
+/// Same as `read_config`, but for configs written as json. Templates are still
+/// resolved with `resolver`, so they can be toml.
+#[tracing::instrument(skip(resolver, input))]
+pub fn read_config_json<R: Read + Seek>(
+    input: &mut R,
+    resolver: impl TemplateResolver,
+) -> ConfigResult<IconOperation> {
+    let reader_string = read_to_string(input)?;
+    // json is deserialized straight in to a toml value, so template resolution
+    // and merging are shared with toml configs
+    let toml_value = serde_json::from_str(&reader_string)?;
+
+    config_from_value(toml_value, resolver)
+}
+
+pub(crate) fn config_from_value(
+    value: Value,
+    resolver: impl TemplateResolver,
+) -> ConfigResult<IconOperation> {
+    let mut result_value = resolve_templates(value, resolver)?;
+    check_version(&mut result_value)?;
+    rename_legacy_keys(&mut result_value);
+    drop_retired_keys(&mut result_value);
 
-    let out_icon_mode: IconOperation = IconOperation::deserialize(result_value)?;
+    let (out_icon_mode, unknown) = deserialize_operation(&result_value)?;
     debug!(config = ?out_icon_mode, "Deserialized");
+    if !unknown.is_empty() {
+        return Err(ConfigError::UnknownKeys(unknown));
+    }
     Ok(out_icon_mode)
 }
 
+/// Checks the optional top level `version` key against `LATEST_VERSION`, and
+/// removes it so it isn't mistaken for part of the operation. Configs without
+/// a version are assumed to be the latest.
+fn check_version(value: &mut Value) -> ConfigResult<()> {
+    let Some(version) = value
+        .as_table_mut()
+        .and_then(|table| table.remove("version"))
+    else {
+        return Ok(());
+    };
+    let found = match &version {
+        Value::Integer(number) => u32::try_from(*number).ok(),
+        Value::String(string) => string.parse().ok(),
+        _ => None,
+    }
+    .ok_or_else(|| {
+        ConfigError::Config(format!("version must be a whole number, found {version}"))
+    })?;
+    let latest = LATEST_VERSION.parse().unwrap();
+    if found > latest {
+        return Err(ConfigError::UnsupportedVersion { found, latest });
+    }
+    Ok(())
+}
+
+/// Moves any keys in `RENAMED_KEYS` over to their new names. If both names are
+/// set, the new one wins
+fn rename_legacy_keys(value: &mut Value) {
+    for (table_name, old, new) in RENAMED_KEYS {
+        let Some(table) = value.get_mut(table_name).and_then(Value::as_table_mut) else {
+            continue;
+        };
+        if let Some(old_value) = table.remove(old) {
+            warn!("`{table_name}.{old}` has been renamed to `{table_name}.{new}`");
+            table.entry(new).or_insert(old_value);
+        }
+    }
+}
+
+/// Removes any keys in `RETIRED_KEYS`
+fn drop_retired_keys(value: &mut Value) {
+    let Some(table) = value.as_table_mut() else {
+        return;
+    };
+    for key in RETIRED_KEYS {
+        if table.remove(key).is_some() {
+            debug!("Ignoring retired key `{key}`");
+        }
+    }
+}
+
 /// Seeks out template string from a value and returns it as a `Some(String)`
 /// If not found, returns `None`
 /// SIDE EFFECT: removes it from the `Value` if it finds it!
@@ -59,18 +154,26 @@ pub fn resolve_templates(first: Value, resolver: impl TemplateResolver) -> Templ
 
     // push the first on to the stack to be resolved
     stack.push(current.clone());
-    let mut recursion_cap = 0;
+    // names of every template resolved so far, for error reporting
+    let mut chain: Vec<String> = vec![];
+    let mut visited: HashSet<String> = HashSet::new();
     // Drill in to templates and resolve until no new ones found
-    while recursion_cap < 100 {
-        if let Some(template) = &extracted_template {
-            current = resolver.resolve(template.as_str())?;
-            extracted_template = extract_template_string(&mut current);
-            trace!(value = ?current, "Resolved config");
-            stack.push(current.clone());
-            recursion_cap += 1;
-        } else {
-            break;
+    while let Some(template) = extracted_template.take() {
+        if !visited.insert(template.clone()) {
+            let start = chain.iter().position(|name| *name == template).unwrap();
+            let mut cycle = chain.split_off(start);
+            cycle.push(template);
+            return Err(TemplateError::CyclicTemplate(cycle));
+        }
+        if chain.len() >= MAX_TEMPLATE_DEPTH {
+            chain.push(template);
+            return Err(TemplateError::RecursionLimit(chain));
         }
+        current = resolver.resolve(template.as_str())?;
+        chain.push(template);
+        extracted_template = extract_template_string(&mut current);
+        trace!(value = ?current, "Resolved config");
+        stack.push(current.clone());
     }
     trace!(num_in_chain = ?stack.len(), stack = ?stack, "Finished resolving templates");
     // merge stack in to one hashmap
@@ -161,6 +264,53 @@ mod test {
         use super::*;
         use crate::config::resolve_templates;
 
+        /// Every template points at the next one, forever
+        struct EndlessResolver;
+
+        impl TemplateResolver for EndlessResolver {
+            fn resolve(&self, input: &str) -> TemplateResult {
+                let depth: usize = input.parse().unwrap();
+                Ok(toml::from_str(&format!("template = \"{}\"", depth + 1)).unwrap())
+            }
+        }
+
+        struct CyclicResolver;
+
+        impl TemplateResolver for CyclicResolver {
+            fn resolve(&self, input: &str) -> TemplateResult {
+                let next = match input {
+                    // a leads in to the b -> c -> b cycle
+                    "a" | "c" => "b",
+                    "b" => "c",
+                    _ => panic!("Malformed test"),
+                };
+                Ok(toml::from_str(&format!("template = \"{next}\"")).unwrap())
+            }
+        }
+
+        #[test]
+        fn cyclic_templates() {
+            let input: Value = toml::from_str(r#"template = "a""#).unwrap();
+
+            let result = resolve_templates(input, CyclicResolver);
+            assert!(matches!(
+                result,
+                Err(TemplateError::CyclicTemplate(cycle)) if cycle == vec!["b", "c", "b"]
+            ));
+        }
+
+        #[test]
+        fn recursion_limit() {
+            let input: Value = toml::from_str(r#"template = "0""#).unwrap();
+
+            let result = resolve_templates(input, EndlessResolver);
+            assert!(matches!(
+                result,
+                Err(TemplateError::RecursionLimit(chain))
+                    if chain.len() == MAX_TEMPLATE_DEPTH + 1 && chain[0] == "0"
+            ));
+        }
+
         #[test]
         fn flattening_simple() {
             let input_string = r#"
@@ -212,9 +362,167 @@ mod test {
     }
 
     mod config {
+        use std::io::Cursor;
+
         use super::*;
+        use crate::config::template_resolver::NullResolver;
         use crate::operations::cutters::bitmask_slice::BitmaskSlice;
 
+        #[test]
+        fn json_matches_toml() {
+            let toml_config = r#"
+            mode = "BitmaskSlice"
+            produce_dirs = true
+            smooth_diagonally = false
+            [icon_size]
+            x = 32
+            y = 32
+            [output_icon_pos]
+            x = 0
+            y = 0
+            [output_icon_size]
+            x = 32
+            y = 32
+            [positions]
+            convex = 0
+            concave = 1
+            horizontal = 2
+            vertical = 3
+            [cut_pos]
+            x = 16
+            y = 16
+            "#;
+            let json_config = r#"{
+                "mode": "BitmaskSlice",
+                "produce_dirs": true,
+                "smooth_diagonally": false,
+                "icon_size": { "x": 32, "y": 32 },
+                "output_icon_pos": { "x": 0, "y": 0 },
+                "output_icon_size": { "x": 32, "y": 32 },
+                "positions": { "convex": 0, "concave": 1, "horizontal": 2, "vertical": 3 },
+                "cut_pos": { "x": 16, "y": 16 }
+            }"#;
+
+            let from_toml = read_config(&mut Cursor::new(toml_config), NullResolver).unwrap();
+            let from_json = read_config_json(&mut Cursor::new(json_config), NullResolver).unwrap();
+            assert_eq!(from_toml, from_json);
+        }
+
+        #[test]
+        fn renamed_keys() {
+            let config = |map_icon: &str| {
+                format!(
+                    r#"
+                    mode = "BitmaskSlice"
+                    produce_dirs = false
+                    smooth_diagonally = false
+                    [icon_size]
+                    x = 32
+                    y = 32
+                    [output_icon_pos]
+                    x = 0
+                    y = 0
+                    [output_icon_size]
+                    x = 32
+                    y = 32
+                    [positions]
+                    convex = 0
+                    concave = 1
+                    horizontal = 2
+                    vertical = 3
+                    [cut_pos]
+                    x = 16
+                    y = 16
+                    [map_icon]
+                    icon_state_name = "map"
+                    {map_icon}
+                    "#
+                )
+            };
+            let derives = |map_icon: &str| {
+                let IconOperation::BitmaskSlice(BitmaskSlice {
+                    map_icon: Some(map_icon),
+                    ..
+                }) = read_config(&mut Cursor::new(config(map_icon)), NullResolver).unwrap()
+                else {
+                    panic!("Expected a bitmask slice with a map icon");
+                };
+                map_icon.derive_colors_from_input
+            };
+
+            assert!(derives("automatic = true"));
+            assert!(!derives("automatic = false"));
+            assert!(derives("derive_colors_from_input = true"));
+            assert!(!derives(
+                "automatic = true\nderive_colors_from_input = false"
+            ));
+        }
+
+        #[test]
+        fn version_check() {
+            let config = |version: &str| {
+                format!(
+                    r#"
+                    version = {version}
+                    mode = "BitmaskSlice"
+                    produce_dirs = false
+                    smooth_diagonally = false
+                    [icon_size]
+                    x = 32
+                    y = 32
+                    [output_icon_pos]
+                    x = 0
+                    y = 0
+                    [output_icon_size]
+                    x = 32
+                    y = 32
+                    [positions]
+                    convex = 0
+                    concave = 1
+                    horizontal = 2
+                    vertical = 3
+                    [cut_pos]
+                    x = 16
+                    y = 16
+                    "#
+                )
+            };
+            let read = |version: &str| read_config(&mut Cursor::new(config(version)), NullResolver);
+
+            assert!(read("1").is_ok());
+            assert!(read(&format!("\"{LATEST_VERSION}\"")).is_ok());
+            assert!(matches!(
+                read("2"),
+                Err(ConfigError::UnsupportedVersion {
+                    found: 2,
+                    latest: 1
+                })
+            ));
+            assert!(matches!(read("\"one\""), Err(ConfigError::Config(_))));
+        }
+
+        #[test]
+        fn bad_signature_key_errors() {
+            let toml_config = r#"
+            mode = "BitmaskSlice"
+            [icon_size]
+            x = 32
+            y = 32
+            [positions]
+            convex = 0
+            concave = 1
+            horizontal = 2
+            vertical = 3
+            [cut_pos]
+            x = 16
+            y = 16
+            [prefabs]
+            300 = 4
+            "#;
+
+            assert!(read_config(&mut Cursor::new(toml_config), NullResolver).is_err());
+        }
+
         #[test]
         fn symmetrical_serialize() {
             let config: IconOperation = BitmaskSlice::default().into();
@@ -0,0 +1,71 @@
+use std::env;
+use std::path::PathBuf;
+
+use toml::Value;
+use tracing::debug;
+
+use crate::config::template_resolver::error::{TemplateError, TemplateResult};
+use crate::config::template_resolver::TemplateResolver;
+
+/// Prefix of the environment variables templates are read from
+pub const TEMPLATE_VAR_PREFIX: &str = "HYPNA_TEMPLATE_";
+
+/// Loads templates from environment variables, so templates can be injected
+/// without touching the filesystem. A template named `foo` is read from
+/// `HYPNA_TEMPLATE_FOO`.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+pub struct EnvResolver;
+
+impl EnvResolver {
+    /// Name of the environment variable that holds the template `input`.
+    /// Anything that isn't valid in a variable name becomes an underscore, so
+    /// `walls/metal` is read from `HYPNA_TEMPLATE_WALLS_METAL`
+    #[must_use]
+    pub fn var_name(input: &str) -> String {
+        let name: String = input
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() {
+                    c.to_ascii_uppercase()
+                } else {
+                    '_'
+                }
+            })
+            .collect();
+        format!("{TEMPLATE_VAR_PREFIX}{name}")
+    }
+}
+
+impl TemplateResolver for EnvResolver {
+    #[tracing::instrument]
+    fn resolve(&self, input: &str) -> TemplateResult {
+        let var_name = Self::var_name(input);
+        let Ok(toml_string) = env::var(&var_name) else {
+            return Err(TemplateError::FailedToFindTemplate(
+                input.to_string(),
+                PathBuf::from(var_name),
+            ));
+        };
+        let deserialized: Value = toml::from_str(&toml_string)?;
+        debug!(deserialized = ?deserialized, "Deserialized template");
+        Ok(deserialized)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolves_from_env() {
+        env::set_var("HYPNA_TEMPLATE_ENV_RESOLVER_TEST", "value = 1");
+
+        let resolved = EnvResolver.resolve("env_resolver/test").unwrap();
+        assert_eq!(resolved, toml::from_str::<Value>("value = 1").unwrap());
+
+        assert!(matches!(
+            EnvResolver.resolve("env_resolver_missing"),
+            Err(TemplateError::FailedToFindTemplate(..))
+        ));
+    }
+}
@@ -3,10 +3,21 @@ use std::path::PathBuf;
 use thiserror::Error;
 use toml::Value;
 
+use crate::config::MAX_TEMPLATE_DEPTH;
+
 #[derive(Debug, Error)]
 pub enum TemplateError {
     #[error("Failed to find template: `{0}`, expected `{1}`")]
     FailedToFindTemplate(String, PathBuf),
+    #[error("Failed to find template: `{0}`, searched `{1:?}`")]
+    NotFoundInAny(String, Vec<PathBuf>),
+    #[error(
+        "Template chain is deeper than {MAX_TEMPLATE_DEPTH} templates: {}",
+        .0.join(" -> ")
+    )]
+    RecursionLimit(Vec<String>),
+    #[error("Templates form a cycle: {}", .0.join(" -> "))]
+    CyclicTemplate(Vec<String>),
     #[error("Generic toml parse error while resolving template: {0}")]
     TOMLError(#[from] toml::de::Error),
     #[error("Generic IO Error when attempting to resolve template: {0}")]
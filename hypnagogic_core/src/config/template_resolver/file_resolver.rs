@@ -42,6 +42,31 @@ impl FileResolver {
     }
 }
 
+impl FileResolver {
+    /// Finds the file a template is loaded from
+    /// # Errors
+    /// Errors if the template doesn't exist, or is outside of the template dir
+    fn template_path(&self, input: &str) -> Result<PathBuf, TemplateError> {
+        let mut pathbuf = self.path.clone();
+        pathbuf.push(Path::new(input));
+
+        debug!(canon = ?pathbuf, "Full path parsed");
+
+        let toml_path = pathbuf.with_extension("toml");
+
+        // canonicalizing both resolves any `..` and confirms the file exists
+        match fs::canonicalize(&toml_path) {
+            Ok(canon) if canon.starts_with(&self.path) => Ok(canon),
+            _ => {
+                Err(TemplateError::FailedToFindTemplate(
+                    input.to_string(),
+                    toml_path,
+                ))
+            }
+        }
+    }
+}
+
 impl Default for FileResolver {
     fn default() -> Self {
         FileResolver::new(Path::new("templates")).expect("templates folder does not exist")
@@ -49,23 +74,12 @@ impl Default for FileResolver {
 }
 
 impl TemplateResolver for FileResolver {
+    /// Templates can be in subfolders of the template dir, referenced like
+    /// `walls/metal`. Anything resolving to outside of the template dir is
+    /// rejected.
     #[tracing::instrument(skip(input))]
     fn resolve(&self, input: &str) -> TemplateResult {
-        let mut pathbuf = self.path.clone();
-        pathbuf.push(Path::new(input));
-
-        debug!(canon = ?pathbuf, "Full path parsed");
-
-        let toml_path = pathbuf.with_extension("toml");
-
-        pathbuf = if toml_path.exists() {
-            toml_path
-        } else {
-            return Err(TemplateError::FailedToFindTemplate(
-                input.to_string(),
-                toml_path,
-            ));
-        };
+        let pathbuf = self.template_path(input)?;
 
         trace!("Found template at {:?}", pathbuf);
 
@@ -74,4 +88,47 @@ impl TemplateResolver for FileResolver {
         debug!(deserialized = ?deserialized, "Deserialized template");
         Ok(deserialized)
     }
+
+    fn source_path(&self, input: &str) -> Option<PathBuf> {
+        self.template_path(input).ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn repo_templates() -> FileResolver {
+        FileResolver::new(&Path::new(env!("CARGO_MANIFEST_DIR")).join("../templates")).unwrap()
+    }
+
+    #[test]
+    fn resolves_subdirectories() {
+        assert!(repo_templates().resolve("bitmask/slice-32x32").is_ok());
+        assert!(matches!(
+            repo_templates().resolve("bitmask/missing"),
+            Err(TemplateError::FailedToFindTemplate(..))
+        ));
+    }
+
+    #[test]
+    fn source_path() {
+        let path = repo_templates().source_path("bitmask/slice-32x32").unwrap();
+        assert!(path.is_absolute());
+        assert!(path.ends_with("templates/bitmask/slice-32x32.toml"));
+        assert_eq!(repo_templates().source_path("bitmask/missing"), None);
+        assert_eq!(repo_templates().source_path("../hypnastic_conf"), None);
+    }
+
+    #[test]
+    fn rejects_path_traversal() {
+        // exists, but outside of the template dir
+        assert!(Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("../hypnastic_conf.toml")
+            .exists());
+        assert!(matches!(
+            repo_templates().resolve("../hypnastic_conf"),
+            Err(TemplateError::FailedToFindTemplate(..))
+        ));
+    }
 }
@@ -1,8 +1,13 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
 use toml::map::Map;
 use toml::Value;
 
-use crate::config::template_resolver::error::TemplateResult;
+use crate::config::template_resolver::error::{TemplateError, TemplateResult};
 
+pub mod env_resolver;
 pub mod error;
 pub mod file_resolver;
 
@@ -12,6 +17,22 @@ pub trait TemplateResolver {
     /// # Errors
     /// Throws an error if resolution fails
     fn resolve(&self, input: &str) -> TemplateResult;
+
+    /// The file a template is loaded from, for tracking what a config depends
+    /// on. `None` if the template isn't found, or doesn't come from a file
+    fn source_path(&self, _input: &str) -> Option<PathBuf> {
+        None
+    }
+}
+
+impl<T: TemplateResolver + ?Sized> TemplateResolver for &T {
+    fn resolve(&self, input: &str) -> TemplateResult {
+        (**self).resolve(input)
+    }
+
+    fn source_path(&self, input: &str) -> Option<PathBuf> {
+        (**self).source_path(input)
+    }
 }
 
 /// Simple resolver that always returns default templatedconfig
@@ -25,3 +46,226 @@ impl TemplateResolver for NullResolver {
         Ok(Value::Table(Map::new()))
     }
 }
+
+/// Tries each resolver in order, returning the first template found. Useful for
+/// layering project specific templates over shared ones.
+/// If no resolver finds the template, the error lists everywhere that was
+/// searched. Any other error (such as a template that fails to parse) is
+/// returned straight away.
+pub struct ChainResolver(pub Vec<Box<dyn TemplateResolver + Send + Sync>>);
+
+impl TemplateResolver for ChainResolver {
+    fn resolve(&self, input: &str) -> TemplateResult {
+        let mut searched: Vec<PathBuf> = vec![];
+        for resolver in &self.0 {
+            match resolver.resolve(input) {
+                Ok(value) => return Ok(value),
+                Err(TemplateError::FailedToFindTemplate(_, expected)) => searched.push(expected),
+                Err(TemplateError::NotFoundInAny(_, expected)) => searched.extend(expected),
+                Err(error) => return Err(error),
+            }
+        }
+        Err(TemplateError::NotFoundInAny(input.to_string(), searched))
+    }
+
+    /// The source of whichever resolver `resolve` would take the template from,
+    /// so a later resolver's file isn't reported for a template an earlier one
+    /// found without a file
+    fn source_path(&self, input: &str) -> Option<PathBuf> {
+        for resolver in &self.0 {
+            if let Some(path) = resolver.source_path(input) {
+                return Some(path);
+            }
+            if resolver.resolve(input).is_ok() {
+                return None;
+            }
+        }
+        None
+    }
+}
+
+/// Remembers every template the wrapped resolver finds, so templates shared by
+/// many configs are only loaded once. Failed lookups aren't cached.
+/// Safe to share between threads, so one can be used for a whole parallel run.
+pub struct CachingResolver<T> {
+    inner: T,
+    cache: Mutex<HashMap<String, Value>>,
+}
+
+impl<T: TemplateResolver> CachingResolver<T> {
+    pub fn new(inner: T) -> Self {
+        Self {
+            inner,
+            cache: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Forgets everything cached, for when templates may have changed
+    pub fn clear(&self) {
+        self.cache.lock().unwrap().clear();
+    }
+}
+
+impl<T: TemplateResolver> TemplateResolver for CachingResolver<T> {
+    fn resolve(&self, input: &str) -> TemplateResult {
+        if let Some(value) = self.cache.lock().unwrap().get(input) {
+            return Ok(value.clone());
+        }
+        // not holding the lock while resolving, so other threads aren't blocked on IO.
+        // two threads may both load the same template, which is harmless
+        let value = self.inner.resolve(input)?;
+        self.cache
+            .lock()
+            .unwrap()
+            .insert(input.to_string(), value.clone());
+        Ok(value)
+    }
+
+    fn source_path(&self, input: &str) -> Option<PathBuf> {
+        self.inner.source_path(input)
+    }
+}
+
+/// Passes lookups through to the wrapped resolver, noting down the file behind
+/// every template that was found. Wrapping the resolver a config is read with
+/// gives the templates that config was built from.
+pub struct RecordingResolver<T> {
+    inner: T,
+    sources: Mutex<Vec<PathBuf>>,
+}
+
+impl<T: TemplateResolver> RecordingResolver<T> {
+    pub fn new(inner: T) -> Self {
+        Self {
+            inner,
+            sources: Mutex::new(vec![]),
+        }
+    }
+
+    /// Files of every template found so far, in the order they were found.
+    /// Templates that don't come from a file aren't included
+    pub fn into_sources(self) -> Vec<PathBuf> {
+        self.sources.into_inner().unwrap()
+    }
+}
+
+impl<T: TemplateResolver> TemplateResolver for RecordingResolver<T> {
+    fn resolve(&self, input: &str) -> TemplateResult {
+        let value = self.inner.resolve(input)?;
+        if let Some(path) = self.inner.source_path(input) {
+            self.sources.lock().unwrap().push(path);
+        }
+        Ok(value)
+    }
+
+    fn source_path(&self, input: &str) -> Option<PathBuf> {
+        self.inner.source_path(input)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+
+    struct SingleResolver(&'static str, i64);
+
+    impl TemplateResolver for SingleResolver {
+        fn resolve(&self, input: &str) -> TemplateResult {
+            if input == self.0 {
+                Ok(Value::Integer(self.1))
+            } else {
+                Err(TemplateError::FailedToFindTemplate(
+                    input.to_string(),
+                    PathBuf::from(self.0),
+                ))
+            }
+        }
+
+        fn source_path(&self, input: &str) -> Option<PathBuf> {
+            (input == self.0).then(|| PathBuf::from(format!("{}-{}.toml", self.0, self.1)))
+        }
+    }
+
+    #[test]
+    fn chain_resolver_falls_back() {
+        let chain = ChainResolver(vec![
+            Box::new(SingleResolver("local", 1)),
+            Box::new(SingleResolver("shared", 2)),
+            Box::new(SingleResolver("local", 3)),
+        ]);
+        assert_eq!(chain.resolve("local").unwrap(), Value::Integer(1));
+        assert_eq!(chain.resolve("shared").unwrap(), Value::Integer(2));
+        assert!(matches!(
+            chain.resolve("missing"),
+            Err(TemplateError::NotFoundInAny(_, paths))
+                if paths == vec![PathBuf::from("local"), PathBuf::from("shared"), PathBuf::from("local")]
+        ));
+        assert!(ChainResolver(vec![]).resolve("missing").is_err());
+        assert_eq!(
+            chain.source_path("local"),
+            Some(PathBuf::from("local-1.toml"))
+        );
+        assert_eq!(chain.source_path("missing"), None);
+
+        // found first by a resolver with no file behind it
+        let shadowed = ChainResolver(vec![
+            Box::new(NullResolver),
+            Box::new(SingleResolver("local", 1)),
+        ]);
+        assert_eq!(shadowed.source_path("local"), None);
+    }
+
+    #[test]
+    fn recording_resolver_notes_sources() {
+        let chain = ChainResolver(vec![
+            Box::new(SingleResolver("local", 1)),
+            Box::new(SingleResolver("shared", 2)),
+            Box::new(NullResolver),
+        ]);
+        let recording = RecordingResolver::new(&chain);
+        recording.resolve("shared").unwrap();
+        recording.resolve("local").unwrap();
+        // found by the null resolver, which has no file behind it
+        recording.resolve("missing").unwrap();
+        assert_eq!(
+            recording.into_sources(),
+            vec![
+                PathBuf::from("shared-2.toml"),
+                PathBuf::from("local-1.toml")
+            ]
+        );
+    }
+
+    struct CountingResolver(AtomicUsize);
+
+    impl TemplateResolver for CountingResolver {
+        fn resolve(&self, input: &str) -> TemplateResult {
+            self.0.fetch_add(1, Ordering::Relaxed);
+            SingleResolver("found", 1).resolve(input)
+        }
+    }
+
+    fn resolve_found(resolver: impl TemplateResolver) -> TemplateResult {
+        resolver.resolve("found")
+    }
+
+    #[test]
+    fn caching_resolver_caches_found_templates() {
+        let caching = CachingResolver::new(CountingResolver(AtomicUsize::new(0)));
+        let calls = || caching.inner.0.load(Ordering::Relaxed);
+        assert_eq!(caching.resolve("found").unwrap(), Value::Integer(1));
+        // references resolve too, so one cache can be lent to every config
+        assert_eq!(resolve_found(&caching).unwrap(), Value::Integer(1));
+        assert_eq!(calls(), 1);
+
+        assert!(caching.resolve("missing").is_err());
+        assert!(caching.resolve("missing").is_err());
+        assert_eq!(calls(), 3);
+
+        caching.clear();
+        caching.resolve("found").unwrap();
+        assert_eq!(calls(), 4);
+    }
+}
@@ -0,0 +1,311 @@
+//! Finds keys in a config that nothing reads. Configs are merged from
+//! templates before they're deserialized, so rather than denying unknown
+//! fields on every struct, the keys skipped while deserializing the merged
+//! value are collected with `serde_ignored`.
+
+use std::fmt::{Display, Formatter};
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_ignored::Path;
+use toml::Value;
+
+use crate::operations::cutters::bitmask_dir_visibility::BitmaskDirectionalVis;
+use crate::operations::cutters::bitmask_slice::BitmaskSlice;
+use crate::operations::cutters::bitmask_windows::BitmaskWindows;
+use crate::operations::effects::color_swap::ColorSwap;
+use crate::operations::effects::damage_overlay::DamageOverlay;
+use crate::operations::format_converter::frames_to_dirs::{DirsToFrames, FramesToDirs};
+use crate::operations::IconOperation;
+
+/// A key in a config that doesn't match any field
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownKey {
+    /// Dotted path to the key, ie `map_icon.text_colour`
+    pub path: String,
+    /// The closest known field name, if any are close enough to be a typo
+    pub suggestion: Option<String>,
+}
+
+impl Display for UnknownKey {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "`{}`", self.path)?;
+        if let Some(suggestion) = &self.suggestion {
+            write!(f, " (did you mean `{suggestion}`?)")?;
+        }
+        Ok(())
+    }
+}
+
+/// Deserializes an operation from `value`, along with every key in it that no
+/// field read
+/// # Errors
+/// Errors if `value` isn't a valid operation
+pub fn deserialize_operation(
+    value: &Value,
+) -> Result<(IconOperation, Vec<UnknownKey>), toml::de::Error> {
+    // `mode` tags the enum, and serde buffers tagged content before handing it
+    // to the variant, which hides skipped keys. Dispatch on it here instead.
+    let Some(mode) = value.get("mode").and_then(Value::as_str) else {
+        return Ok((IconOperation::deserialize(value.clone())?, vec![]));
+    };
+    let mut fields = value.clone();
+    if let Some(table) = fields.as_table_mut() {
+        table.remove("mode");
+    }
+    match mode {
+        "BitmaskSlice" => variant::<BitmaskSlice>(&fields),
+        "BitmaskDirectionalVis" => {
+            // the slice config is flattened in, which hides skipped keys the
+            // same way, so they're collected by reading it on its own
+            let (operation, _) = variant::<BitmaskDirectionalVis>(&fields)?;
+            if let Some(table) = fields.as_table_mut() {
+                table.remove("slice_point");
+                table.remove("mask_color");
+            }
+            let (_, unknown) = deserialize_with_unknown_keys::<BitmaskSlice>(&fields)?;
+            Ok((operation, unknown))
+        }
+        "BitmaskWindows" => variant::<BitmaskWindows>(&fields),
+        "FramesToDirs" => variant::<FramesToDirs>(&fields),
+        "DirsToFrames" => variant::<DirsToFrames>(&fields),
+        "DamageOverlay" => variant::<DamageOverlay>(&fields),
+        "ColorSwap" => variant::<ColorSwap>(&fields),
+        _ => Ok((IconOperation::deserialize(value.clone())?, vec![])),
+    }
+}
+
+fn variant<T: DeserializeOwned + Serialize + Into<IconOperation>>(
+    value: &Value,
+) -> Result<(IconOperation, Vec<UnknownKey>), toml::de::Error> {
+    let (config, unknown) = deserialize_with_unknown_keys::<T>(value)?;
+    Ok((config.into(), unknown))
+}
+
+fn deserialize_with_unknown_keys<T: DeserializeOwned + Serialize>(
+    value: &Value,
+) -> Result<(T, Vec<UnknownKey>), toml::de::Error> {
+    let mut ignored = vec![];
+    let config: T = serde_ignored::deserialize(value.clone(), |path| {
+        ignored.push(segments(&path));
+    })?;
+
+    // serialized as json so unset optional fields still show up, as nulls
+    let known = serde_json::to_value(&config).ok();
+    let unknown = ignored
+        .into_iter()
+        .map(|path| {
+            let (key, parent) = path.split_last().expect("ignored keys have a path");
+            let suggestion = match key {
+                Segment::Key(key) => {
+                    let fields = known
+                        .as_ref()
+                        .and_then(|root| lookup(root, parent)?.as_object());
+                    fields.and_then(|fields| closest_match(key, fields.keys()))
+                }
+                Segment::Index(_) => None,
+            };
+            UnknownKey {
+                path: display_path(&path),
+                suggestion,
+            }
+        })
+        .collect();
+    Ok((config, unknown))
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    Key(String),
+    Index(usize),
+}
+
+fn segments(path: &Path) -> Vec<Segment> {
+    match path {
+        Path::Root => vec![],
+        Path::Seq { parent, index } => {
+            let mut segments = segments(parent);
+            segments.push(Segment::Index(*index));
+            segments
+        }
+        Path::Map { parent, key } => {
+            let mut segments = segments(parent);
+            segments.push(Segment::Key(key.clone()));
+            segments
+        }
+        Path::Some { parent }
+        | Path::NewtypeStruct { parent }
+        | Path::NewtypeVariant { parent } => segments(parent),
+    }
+}
+
+fn display_path(path: &[Segment]) -> String {
+    let mut out = String::new();
+    for segment in path {
+        match segment {
+            Segment::Key(key) if out.is_empty() => out.push_str(key),
+            Segment::Key(key) => {
+                out.push('.');
+                out.push_str(key);
+            }
+            Segment::Index(index) => {
+                out.push('[');
+                out.push_str(&index.to_string());
+                out.push(']');
+            }
+        }
+    }
+    out
+}
+
+fn lookup<'a>(root: &'a serde_json::Value, path: &[Segment]) -> Option<&'a serde_json::Value> {
+    path.iter().try_fold(root, |value, segment| {
+        match segment {
+            Segment::Key(key) => value.get(key),
+            Segment::Index(index) => value.get(index),
+        }
+    })
+}
+
+/// Finds the candidate closest to `key`, if it's close enough to plausibly be
+/// a typo of it
+fn closest_match<'a>(key: &str, candidates: impl Iterator<Item = &'a String>) -> Option<String> {
+    let threshold = (key.chars().count() / 3).max(1);
+    candidates
+        .map(|candidate| (edit_distance(key, candidate), candidate))
+        .filter(|(distance, _)| *distance <= threshold)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate.clone())
+}
+
+/// Levenshtein distance between two strings
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, a_char) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a_char != *b_char);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::config::error::ConfigError;
+    use crate::config::read_config;
+    use crate::config::template_resolver::NullResolver;
+
+    /// Checks a minimal valid config with `top` added to the root table and
+    /// `tables` added after it
+    fn unknown_keys(top: &str, tables: &str) -> Vec<UnknownKey> {
+        let config = format!(
+            r#"
+            mode = "BitmaskSlice"
+            produce_dirs = false
+            smooth_diagonally = false
+            {top}
+            [icon_size]
+            x = 32
+            y = 32
+            [output_icon_pos]
+            x = 0
+            y = 0
+            [output_icon_size]
+            x = 32
+            y = 32
+            [positions]
+            convex = 0
+            concave = 1
+            horizontal = 2
+            vertical = 3
+            [cut_pos]
+            x = 16
+            y = 16
+            {tables}
+            "#
+        );
+        match read_config(&mut Cursor::new(config), NullResolver) {
+            Ok(_) => vec![],
+            Err(ConfigError::UnknownKeys(keys)) => keys,
+            Err(err) => panic!("Unexpected error: {err}"),
+        }
+    }
+
+    #[test]
+    fn typo_suggests_closest_field() {
+        assert_eq!(unknown_keys("", ""), vec![]);
+        assert_eq!(
+            unknown_keys("smooth_diagonaly = true", ""),
+            vec![UnknownKey {
+                path: "smooth_diagonaly".to_string(),
+                suggestion: Some("smooth_diagonally".to_string()),
+            }]
+        );
+        assert_eq!(
+            unknown_keys("completely_made_up = 1", ""),
+            vec![UnknownKey {
+                path: "completely_made_up".to_string(),
+                suggestion: None,
+            }]
+        );
+    }
+
+    #[test]
+    fn nested_keys_are_checked() {
+        let keys = unknown_keys(
+            "",
+            r##"
+            [map_icon]
+            icon_state_name = "map"
+            text_colour = "#ff0000"
+            outer_border = { style = { dashed = { on = 2, off = 1 } }, color = "#000000" }
+            "##,
+        );
+        assert_eq!(keys.len(), 1);
+        assert_eq!(keys[0].path, "map_icon.text_colour");
+        assert_eq!(keys[0].suggestion.as_deref(), Some("text_color"));
+    }
+
+    #[test]
+    fn flattened_keys_are_checked() {
+        let config = r#"
+            mode = "BitmaskDirectionalVis"
+            produce_dirs = true
+            smooth_diagonally = false
+            smooth_diagonaly = true
+            file_prefix = "GENERATED-"
+            icon_size = { x = 32, y = 32 }
+            output_icon_pos = { x = 0, y = 0 }
+            output_icon_size = { x = 32, y = 32 }
+            positions = { convex = 0, concave = 1, horizontal = 2, vertical = 3 }
+            cut_pos = { x = 16, y = 16 }
+            slice_point = {}
+            "#;
+        match read_config(&mut Cursor::new(config), NullResolver) {
+            Err(ConfigError::UnknownKeys(keys)) => {
+                assert_eq!(
+                    keys,
+                    vec![UnknownKey {
+                        path: "smooth_diagonaly".to_string(),
+                        suggestion: Some("smooth_diagonally".to_string()),
+                    }]
+                );
+            }
+            other => panic!("Expected unknown keys, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn edit_distance_counts_changes() {
+        assert_eq!(edit_distance("icon_sixe", "icon_size"), 1);
+        assert_eq!(edit_distance("", "abc"), 3);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+    }
+}
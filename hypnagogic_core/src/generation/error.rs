@@ -1,6 +1,7 @@
 use thiserror::Error;
 
 use crate::generation::text;
+use crate::operations::error::ProcessorError;
 
 #[derive(Debug, Error)]
 pub enum GenerationError {
@@ -10,4 +11,6 @@ pub enum GenerationError {
     TextTooLong(String, u32),
     #[error("Text has too many lines: {0}; max lines for size is {1}")]
     TooManyLines(u32, u32),
+    #[error("Generated image is too large:\n{0}")]
+    ImageTooLarge(Box<ProcessorError>),
 }
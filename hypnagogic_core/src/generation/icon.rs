@@ -1,10 +1,11 @@
-use image::DynamicImage;
+use image::{DynamicImage, GenericImageView};
 
-use crate::config::blocks::generators::{MapIcon, Position};
+use crate::config::blocks::generators::{Background, GradientDirection, MapIcon, Position};
 use crate::generation::error::GenerationError;
-use crate::generation::rect::{draw_border, draw_rect};
-use crate::generation::text::generate_text_block;
-use crate::util::color::fill_image_color;
+use crate::generation::rect::{clamp_radius, draw_border, draw_rect, inside_rounded};
+use crate::generation::text::{generate_text_block, VerticalAlignment};
+use crate::util::color::{fill_image_color, Color};
+use crate::util::icon_ops::safe_new_image;
 
 pub fn generate_map_icon(
     height: u32,
@@ -13,20 +14,36 @@ pub fn generate_map_icon(
 ) -> Result<DynamicImage, GenerationError> {
     let MapIcon {
         base_color,
+        background,
+        corner_radius,
         text,
         text_color,
         text_position,
         text_alignment,
+        text_vertical_alignment,
+        text_style,
         inner_border,
         outer_border,
         ..
     } = args;
-    let mut image = DynamicImage::new_rgba8(width, height);
-    draw_rect(&mut image, 0, 0, width, height, *base_color);
+    let mut image = safe_new_image(width, height)
+        .map_err(|err| GenerationError::ImageTooLarge(Box::new(err)))?;
+    match *background {
+        Background::Solid => {
+            draw_rect(&mut image, 0, 0, width, height, *corner_radius, *base_color);
+        }
+        Background::Gradient {
+            from,
+            to,
+            direction,
+        } => {
+            draw_gradient(&mut image, *corner_radius, from, to, direction);
+        }
+    }
     // draw the text block
 
     if let Some(text) = text {
-        let mut text_image = generate_text_block(text, *text_alignment);
+        let mut text_image = generate_text_block(text, *text_alignment, *text_style)?;
         if text_image.width() > (width - 4) {
             return Err(GenerationError::TextTooLong(text.clone(), (width - 4) / 4));
         }
@@ -36,7 +53,10 @@ pub fn generate_map_icon(
                 (height - 4) / 6,
             ));
         }
-        fill_image_color(&mut text_image, *text_color);
+        fill_image_color(
+            &mut text_image,
+            text_color.resolve(background.average(*base_color)),
+        );
         let text_width = text_image.width();
         let text_height = text_image.height();
         let (text_x, text_y) = match text_position {
@@ -46,19 +66,227 @@ pub fn generate_map_icon(
             Position::BottomRight => (width - text_width - 3, height - text_height - 3),
             Position::Center => ((width - text_width) / 2, (height - text_height) / 2),
         };
-        image::imageops::overlay(&mut image, &text_image, text_x as i64, text_y as i64);
+        let text_y = match text_vertical_alignment {
+            Some(VerticalAlignment::Top) => 3,
+            Some(VerticalAlignment::Middle) => (height - text_height) / 2,
+            Some(VerticalAlignment::Bottom) => height - text_height - 3,
+            None => text_y,
+        };
+        blend_onto(&mut image, &text_image, text_x, text_y);
     }
 
     // outer border
     if let Some(border) = outer_border {
-        draw_border(&mut image, 0, 0, width, height, *border);
+        draw_border(&mut image, 0, 0, width, height, *corner_radius, *border);
     }
     // inner border
     if let Some(border) = inner_border {
-        draw_border(&mut image, 1, 1, width - 2, height - 2, *border);
+        draw_border(
+            &mut image,
+            1,
+            1,
+            width - 2,
+            height - 2,
+            corner_radius.saturating_sub(1),
+            *border,
+        );
     }
     Ok(image)
 }
 
+/// Fills all of `image` with a gradient, rounding its corners to
+/// `corner_radius` the same as `draw_rect`
+fn draw_gradient(
+    image: &mut DynamicImage,
+    corner_radius: u32,
+    from: Color,
+    to: Color,
+    direction: GradientDirection,
+) {
+    let (width, height) = image.dimensions();
+    let radius = clamp_radius(width, height, corner_radius);
+    let length = match direction {
+        GradientDirection::Vertical => height,
+        GradientDirection::Horizontal => width,
+    };
+    let mut buffer = image.to_rgba8();
+    for (x, y, pixel) in buffer.enumerate_pixels_mut() {
+        if !inside_rounded(x.into(), y.into(), width, height, radius) {
+            continue;
+        }
+        let position = match direction {
+            GradientDirection::Vertical => y,
+            GradientDirection::Horizontal => x,
+        };
+        let amount = if length > 1 {
+            position as f32 / (length - 1) as f32
+        } else {
+            0.0
+        };
+        pixel.0 = from.lerp(to, amount).into();
+    }
+    *image = DynamicImage::ImageRgba8(buffer);
+}
+
+/// Draws `top` over `base` at the given offset, blending each pixel so
+/// antialiased edges keep their partial alpha
+fn blend_onto(base: &mut DynamicImage, top: &DynamicImage, x: u32, y: u32) {
+    let mut buffer = base.to_rgba8();
+    for (top_x, top_y, pixel) in top.pixels() {
+        let Some(under) = buffer.get_pixel_mut_checked(x + top_x, y + top_y) else {
+            continue;
+        };
+        under.0 = Color::from(under.0).blend(Color::from(pixel.0)).into();
+    }
+    *base = DynamicImage::ImageRgba8(buffer);
+}
+
 #[cfg(test)]
-mod test {}
+mod test {
+    use super::*;
+    use crate::config::blocks::generators::TextColor;
+
+    /// First row with any text on it, skipping the border
+    fn first_text_row(image: &DynamicImage) -> Option<u32> {
+        (1..image.height() - 1)
+            .find(|y| (1..image.width() - 1).any(|x| image.get_pixel(x, *y).0 == [0, 0, 0, 255]))
+    }
+
+    #[test]
+    fn oversized_map_icon() {
+        let config: MapIcon = toml::from_str(
+            r##"
+            icon_state_name = "map"
+            base_color = "#ff0000"
+            "##,
+        )
+        .unwrap();
+        assert!(matches!(
+            generate_map_icon(32, u32::MAX, &config),
+            Err(GenerationError::ImageTooLarge(_))
+        ));
+    }
+
+    #[test]
+    fn auto_text_color() {
+        let text_colors = |base_color: Color| {
+            let config: MapIcon = toml::from_str(&format!(
+                r#"
+                icon_state_name = "map"
+                base_color = "{}"
+                text = "DEF"
+                text_color = "auto"
+                "#,
+                base_color.to_hex_str()
+            ))
+            .unwrap();
+            assert_eq!(config.text_color, TextColor::Auto);
+            let image = generate_map_icon(32, 32, &config).unwrap();
+            let mut colors: Vec<[u8; 4]> = (2..30)
+                .flat_map(|y| (2..30).map(move |x| (x, y)))
+                .map(|(x, y)| image.get_pixel(x, y).0)
+                .filter(|pixel| *pixel != <[u8; 4]>::from(base_color))
+                .collect();
+            colors.sort_unstable();
+            colors.dedup();
+            colors
+        };
+        assert_eq!(
+            text_colors(Color::new_rgb(20, 20, 60)),
+            vec![[255, 255, 255, 255]]
+        );
+        assert_eq!(
+            text_colors(Color::new_rgb(230, 230, 120)),
+            vec![[0, 0, 0, 255]]
+        );
+    }
+
+    #[test]
+    fn colors_derived_from_input() {
+        let dark = Color::new_rgb(40, 20, 10);
+        let light = Color::new_rgb(220, 200, 150);
+        let input = DynamicImage::ImageRgba8(image::RgbaImage::from_fn(4, 1, |x, _| {
+            match x {
+                0 => image::Rgba([0, 0, 0, 0]),
+                1 | 2 => image::Rgba(dark.into()),
+                _ => image::Rgba(light.into()),
+            }
+        }));
+
+        let configured = MapIcon::default();
+        assert_eq!(configured.for_input(&input), configured);
+
+        let derived = MapIcon {
+            derive_colors_from_input: true,
+            ..Default::default()
+        }
+        .for_input(&input);
+        assert_eq!(derived.base_color, dark);
+        assert_eq!(derived.text_color, TextColor::Color(light));
+        assert_eq!(derived.outer_border.unwrap().color, light);
+    }
+
+    #[test]
+    fn gradient_background() {
+        let from = Color::new_rgb(0, 0, 0);
+        let to = Color::new_rgb(0, 0, 250);
+        let gradient = |direction: GradientDirection| {
+            let config = MapIcon {
+                background: Background::Gradient {
+                    from,
+                    to,
+                    direction,
+                },
+                text: None,
+                outer_border: None,
+                ..Default::default()
+            };
+            generate_map_icon(6, 6, &config).unwrap()
+        };
+
+        let vertical = gradient(GradientDirection::Vertical);
+        let blues: Vec<u8> = (0..6).map(|y| vertical.get_pixel(2, y).0[2]).collect();
+        assert_eq!(blues, vec![0, 50, 100, 150, 200, 250]);
+        assert_eq!(vertical.get_pixel(0, 3), vertical.get_pixel(5, 3));
+
+        let horizontal = gradient(GradientDirection::Horizontal);
+        let blues: Vec<u8> = (0..6).map(|x| horizontal.get_pixel(x, 2).0[2]).collect();
+        assert_eq!(blues, vec![0, 50, 100, 150, 200, 250]);
+
+        let config: MapIcon = toml::from_str(
+            r##"
+            icon_state_name = "map"
+            background = { gradient = { from = "#000000", to = "#0000fa", direction = "vertical" } }
+            "##,
+        )
+        .unwrap();
+        assert_eq!(
+            config.background,
+            Background::Gradient {
+                from,
+                to,
+                direction: GradientDirection::Vertical
+            }
+        );
+    }
+
+    #[test]
+    fn vertical_alignment() {
+        let anchored = generate_map_icon(32, 32, &MapIcon::default()).unwrap();
+        assert_eq!(first_text_row(&anchored), Some(24));
+
+        let top = MapIcon {
+            text_vertical_alignment: Some(VerticalAlignment::Top),
+            ..Default::default()
+        };
+        let top = generate_map_icon(32, 32, &top).unwrap();
+        assert_eq!(first_text_row(&top), Some(3));
+
+        let middle = MapIcon {
+            text_vertical_alignment: Some(VerticalAlignment::Middle),
+            ..Default::default()
+        };
+        let middle = generate_map_icon(32, 32, &middle).unwrap();
+        assert_eq!(first_text_row(&middle), Some(13));
+    }
+}
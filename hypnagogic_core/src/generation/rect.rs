@@ -3,10 +3,24 @@ use serde::{Deserialize, Serialize};
 
 use crate::util::color::Color;
 
-pub fn draw_rect(image: &mut DynamicImage, x: u32, y: u32, width: u32, height: u32, color: Color) {
-    for x in x..x + width {
-        for y in y..y + height {
-            image.put_pixel(x, y, image::Rgba(color.into()));
+/// Fills a rectangle, rounding its corners to `corner_radius`. The radius is
+/// clamped to half of the smaller dimension, and pixels outside of the rounded
+/// corners are left untouched.
+pub fn draw_rect(
+    image: &mut DynamicImage,
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+    corner_radius: u32,
+    color: Color,
+) {
+    let radius = clamp_radius(width, height, corner_radius);
+    for x_offset in 0..width {
+        for y_offset in 0..height {
+            if inside_rounded(x_offset.into(), y_offset.into(), width, height, radius) {
+                image.put_pixel(x + x_offset, y + y_offset, image::Rgba(color.into()));
+            }
         }
     }
 }
@@ -16,6 +30,12 @@ pub fn draw_rect(image: &mut DynamicImage, x: u32, y: u32, width: u32, height: u
 pub enum BorderStyle {
     Solid,
     Dotted,
+    /// Dashes `on` pixels long with `off` pixel gaps, starting from each
+    /// corner. Sides too short for a whole dash and gap are drawn solid.
+    Dashed {
+        on: u32,
+        off: u32,
+    },
 }
 
 #[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize, Deserialize)]
@@ -24,41 +44,247 @@ pub struct Border {
     pub color: Color,
 }
 
+/// Draws a 1 pixel border around the inside edge of a rectangle, rounding its
+/// corners to `corner_radius` the same as `draw_rect`. The style's pattern
+/// runs along the straight part of each side, and rounded corners are always
+/// drawn solid.
 pub fn draw_border(
     image: &mut DynamicImage,
     x: u32,
     y: u32,
     width: u32,
     height: u32,
+    corner_radius: u32,
     border: Border,
 ) {
+    if width == 0 || height == 0 {
+        return;
+    }
+    let color = image::Rgba(border.color.into());
+    let radius = clamp_radius(width, height, corner_radius);
+    // the straight part of each side, between the rounded corners
+    let horizontal = radius..width - radius;
+    let vertical = radius..height - radius;
     match border.style {
         BorderStyle::Solid => {
-            draw_rect(image, x, y, width, 1, border.color);
-            draw_rect(image, x, y + height - 1, width, 1, border.color);
-            draw_rect(image, x, y, 1, height, border.color);
-            draw_rect(image, x + width - 1, y, 1, height, border.color);
+            for x_offset in horizontal {
+                image.put_pixel(x + x_offset, y, color);
+                image.put_pixel(x + x_offset, y + height - 1, color);
+            }
+            for y_offset in vertical {
+                image.put_pixel(x, y + y_offset, color);
+                image.put_pixel(x + width - 1, y + y_offset, color);
+            }
         }
         BorderStyle::Dotted => {
-            for x in x..x + width {
+            for x in horizontal.map(|x_offset| x + x_offset) {
                 if x % 2 == 0 {
-                    image.put_pixel(x, y, image::Rgba(border.color.into()));
+                    image.put_pixel(x, y, color);
                 }
                 if x % 2 == 1 {
-                    image.put_pixel(x, y + height - 1, image::Rgba(border.color.into()));
+                    image.put_pixel(x, y + height - 1, color);
                 }
             }
-            for y in y..y + height {
+            for y in vertical.map(|y_offset| y + y_offset) {
                 if y % 2 == 0 {
-                    image.put_pixel(x, y, image::Rgba(border.color.into()));
+                    image.put_pixel(x, y, color);
                 }
                 if y % 2 == 1 {
-                    image.put_pixel(x + width - 1, y, image::Rgba(border.color.into()));
+                    image.put_pixel(x + width - 1, y, color);
                 }
             }
         }
+        BorderStyle::Dashed { on, off } => {
+            for x_offset in
+                dash_offsets(width, on, off).filter(|offset| horizontal.contains(offset))
+            {
+                image.put_pixel(x + x_offset, y, color);
+                image.put_pixel(x + x_offset, y + height - 1, color);
+            }
+            for y_offset in dash_offsets(height, on, off).filter(|offset| vertical.contains(offset))
+            {
+                image.put_pixel(x, y + y_offset, color);
+                image.put_pixel(x + width - 1, y + y_offset, color);
+            }
+        }
+    }
+
+    if radius == 0 {
+        return;
+    }
+    let inside =
+        |x_offset: i64, y_offset: i64| inside_rounded(x_offset, y_offset, width, height, radius);
+    let near = (0..radius).chain(width - radius..width);
+    for x_offset in near {
+        for y_offset in (0..radius).chain(height - radius..height) {
+            let (px, py) = (i64::from(x_offset), i64::from(y_offset));
+            let on_edge = inside(px, py)
+                && [(px - 1, py), (px + 1, py), (px, py - 1), (px, py + 1)]
+                    .into_iter()
+                    .any(|(nx, ny)| !inside(nx, ny));
+            if on_edge {
+                image.put_pixel(x + x_offset, y + y_offset, color);
+            }
+        }
+    }
+}
+
+/// Limits a corner radius to half of the smaller dimension of a rectangle
+pub(crate) fn clamp_radius(width: u32, height: u32, radius: u32) -> u32 {
+    radius.min(width.min(height) / 2)
+}
+
+/// Whether the pixel at an offset within a rectangle is inside of it once its
+/// corners are rounded to `radius`. Pixels count as inside if their center is
+/// within the corner's circle.
+pub(crate) fn inside_rounded(
+    x_offset: i64,
+    y_offset: i64,
+    width: u32,
+    height: u32,
+    radius: u32,
+) -> bool {
+    let (width, height, radius) = (i64::from(width), i64::from(height), i64::from(radius));
+    if x_offset < 0 || y_offset < 0 || x_offset >= width || y_offset >= height {
+        return false;
     }
+    // distance past the start of the rounded corner along an axis, doubled so
+    // pixel centers stay whole numbers
+    let past_corner = |offset: i64, length: i64| {
+        if offset < radius {
+            2 * (radius - offset) - 1
+        } else if offset >= length - radius {
+            2 * (offset - (length - radius)) + 1
+        } else {
+            0
+        }
+    };
+    let (dx, dy) = (past_corner(x_offset, width), past_corner(y_offset, height));
+    dx == 0 || dy == 0 || dx * dx + dy * dy <= 4 * radius * radius
+}
+
+/// Offsets along a side of `length` pixels that a dashed line covers
+fn dash_offsets(length: u32, on: u32, off: u32) -> impl Iterator<Item = u32> {
+    let period = on.saturating_add(off);
+    (0..length).filter(move |offset| on != 0 && (period > length || offset % period < on))
 }
 
 #[cfg(test)]
-mod test {}
+mod test {
+    use image::GenericImageView;
+
+    use super::*;
+
+    fn rows(image: &DynamicImage) -> Vec<String> {
+        (0..image.height())
+            .map(|y| {
+                (0..image.width())
+                    .map(|x| {
+                        if image.get_pixel(x, y).0[3] == 0 {
+                            '.'
+                        } else {
+                            '#'
+                        }
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn top_row(image: &DynamicImage) -> String {
+        rows(image).swap_remove(0)
+    }
+
+    fn dashed(size: u32, on: u32, off: u32) -> DynamicImage {
+        let mut image = DynamicImage::new_rgba8(size, size);
+        let border = Border {
+            style: BorderStyle::Dashed { on, off },
+            color: Color::new_rgb(255, 0, 0),
+        };
+        draw_border(&mut image, 0, 0, size, size, 0, border);
+        image
+    }
+
+    #[test]
+    fn square_corners_unchanged() {
+        let color = Color::new_rgb(0, 255, 0);
+        let bordered = |style: BorderStyle| {
+            let mut image = DynamicImage::new_rgba8(8, 5);
+            draw_border(&mut image, 0, 0, 8, 5, 0, Border { style, color });
+            image
+        };
+
+        let mut lines = DynamicImage::new_rgba8(8, 5);
+        draw_rect(&mut lines, 0, 0, 8, 1, 0, color);
+        draw_rect(&mut lines, 0, 4, 8, 1, 0, color);
+        draw_rect(&mut lines, 0, 0, 1, 5, 0, color);
+        draw_rect(&mut lines, 7, 0, 1, 5, 0, color);
+        assert_eq!(bordered(BorderStyle::Solid).as_bytes(), lines.as_bytes());
+        assert_eq!(
+            rows(&bordered(BorderStyle::Dotted)),
+            vec!["#.#.#.#.", ".......#", "#.......", ".......#", "##.#.#.#"]
+        );
+        assert_eq!(
+            rows(&bordered(BorderStyle::Dashed { on: 3, off: 1 })),
+            vec!["###.####", "#......#", "#......#", "........", "###.####"]
+        );
+    }
+
+    #[test]
+    fn rounded_corners() {
+        let color = Color::new_rgb(0, 0, 255);
+        let mut filled = DynamicImage::new_rgba8(8, 8);
+        draw_rect(&mut filled, 0, 0, 8, 8, 3, color);
+        assert_eq!(rows(&filled)[0], ".######.");
+        assert_eq!(rows(&filled)[1], "########");
+        assert_eq!(rows(&filled)[7], ".######.");
+
+        let mut clamped = DynamicImage::new_rgba8(8, 8);
+        draw_rect(&mut clamped, 0, 0, 8, 8, 100, color);
+        let mut half = DynamicImage::new_rgba8(8, 8);
+        draw_rect(&mut half, 0, 0, 8, 8, 4, color);
+        assert_eq!(clamped.as_bytes(), half.as_bytes());
+        assert_eq!(rows(&half)[0], "..####..");
+
+        let mut border = DynamicImage::new_rgba8(8, 8);
+        let style = Border {
+            style: BorderStyle::Solid,
+            color,
+        };
+        draw_border(&mut border, 0, 0, 8, 8, 4, style);
+        assert_eq!(
+            rows(&border),
+            vec![
+                "..####..", ".#....#.", "#......#", "#......#", "#......#", "#......#", ".#....#.",
+                "..####..",
+            ]
+        );
+    }
+
+    #[test]
+    fn dashed_border() {
+        assert_eq!(top_row(&dashed(12, 3, 2)), "###..###..##");
+        assert_eq!(top_row(&dashed(6, 1, 0)), "######");
+        assert_eq!(top_row(&dashed(6, 0, 2)), "......");
+
+        let image = dashed(12, 3, 2);
+        let left_column: Vec<bool> = (0..12).map(|y| image.get_pixel(0, y).0[3] != 0).collect();
+        assert_eq!(left_column, (0..12).map(|y| y % 5 < 3).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn dashed_border_on_small_tiles() {
+        // a dash and gap that don't fit are drawn solid, rather than as one
+        // dash that could be mistaken for a solid border anyway
+        assert_eq!(top_row(&dashed(4, 4, 4)), "####");
+        assert_eq!(top_row(&dashed(1, 2, 1)), "#");
+    }
+
+    #[test]
+    fn dashed_style_from_toml() {
+        let border: Border =
+            toml::from_str("style = { dashed = { on = 2, off = 1 } }\ncolor = \"#000000\"")
+                .unwrap();
+        assert_eq!(border.style, BorderStyle::Dashed { on: 2, off: 1 });
+    }
+}
@@ -3,6 +3,9 @@ use once_cell::sync::Lazy;
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
+use crate::operations::error::ProcessorError;
+use crate::util::icon_ops::safe_new_image;
+
 // all printable ascii characters
 const VALID_CHARS: [char; 95] = [
     ' ', '!', '"', '#', '$', '%', '&', '\'', '(', ')', '*', '+', ',', '-', '.', '/', '0', '1', '2',
@@ -29,30 +32,95 @@ const fn is_char_narrow(char: char) -> Option<u32> {
     }
 }
 
-#[must_use]
-pub fn generate_text_line(text_to_gen: &str) -> DynamicImage {
+fn one() -> u32 {
+    1
+}
+
+/// Spacing used when laying out text, in pixels
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct TextStyle {
+    /// Gap between characters on a line
+    #[serde(default = "one")]
+    pub char_spacing: u32,
+    /// Gap between lines in a block
+    #[serde(default = "one")]
+    pub line_spacing: u32,
+    /// Width to wrap blocks at, packing as many words on to each line as fit.
+    /// If unset, every word goes on its own line
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub max_width: Option<u32>,
+}
+
+impl Default for TextStyle {
+    fn default() -> Self {
+        Self {
+            char_spacing: 1,
+            line_spacing: 1,
+            max_width: None,
+        }
+    }
+}
+
+/// Errors if the text is longer than `MAX_LENGTH`, or if any characters in
+/// it can't be drawn, listing each once
+fn check_text(text: &str) -> Result<(), TextError> {
+    let length = text.chars().count();
+    if length > MAX_LENGTH {
+        return Err(TextError::TooLong(
+            u32::try_from(length).unwrap_or(u32::MAX),
+        ));
+    }
+    let mut invalid: Vec<char> = vec![];
+    for char in text.chars() {
+        if char != ' ' && lookup_coords(char).is_none() && !invalid.contains(&char) {
+            invalid.push(char);
+        }
+    }
+    if invalid.is_empty() {
+        Ok(())
+    } else {
+        Err(TextError::InvalidCharacters(invalid))
+    }
+}
+
+/// Generates a single line of text
+/// # Errors
+/// Errors if the text is over `MAX_LENGTH` characters, contains characters
+/// that aren't printable ascii, or is spaced out too far to draw
+pub fn generate_text_line(text_to_gen: &str, style: TextStyle) -> Result<DynamicImage, TextError> {
+    check_text(text_to_gen)?;
     let num_chars = text_to_gen.chars().count() as u32;
     // -1 because we don't want to count the last space
-    let num_spaces = num_chars - 1;
-    let width = CHARACTER_WIDTH * num_chars + num_spaces;
+    let num_spaces = num_chars.saturating_sub(1);
+    // spacing comes straight from the config, so can be anything
+    let width = num_spaces
+        .checked_mul(style.char_spacing)
+        .and_then(|spacing| spacing.checked_add(CHARACTER_WIDTH * num_chars))
+        .ok_or(TextError::SpacingOverflow)?;
     let height = CHARACTER_HEIGHT;
-    let mut image = DynamicImage::new_rgba8(width, height);
+    let mut image =
+        safe_new_image(width, height).map_err(|err| TextError::TooLarge(Box::new(err)))?;
     let mut pos = 0;
-    for char in text_to_gen.chars() {
+    for (i, char) in text_to_gen.chars().enumerate() {
+        // spacing only goes between characters, so pos never passes `width`
+        if i > 0 {
+            pos += style.char_spacing;
+        }
         if char == ' ' {
-            pos += 2;
+            pos += 1;
             continue;
         }
         let x = pos;
-        pos += is_char_narrow(char).unwrap_or(CHARACTER_WIDTH) + 1;
+        pos += is_char_narrow(char).unwrap_or(CHARACTER_WIDTH);
         let y = 0;
-        let char_image = get_char_crop(char).expect("Invalid character");
+        let char_image = get_char_crop(char).expect("Characters were checked above");
         image
             .copy_from(&char_image, x, y)
             .expect("Failed to copy (bad image?)");
     }
-    // crop off the last space
-    image.crop_imm(0, 0, pos - 1, CHARACTER_HEIGHT)
+    // crop off what narrow characters and spaces didn't use
+    Ok(image.crop_imm(0, 0, pos, CHARACTER_HEIGHT))
 }
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
@@ -63,28 +131,83 @@ pub enum Alignment {
     Right,
 }
 
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VerticalAlignment {
+    Top,
+    Middle,
+    Bottom,
+}
+
+/// Splits text in to lines. Without a `max_width` every word gets its own
+/// line, otherwise words are greedily packed on to lines no wider than it. A
+/// word wider than `max_width` on its own still gets a line to itself.
+fn wrap_lines(text: &str, style: TextStyle) -> Result<Vec<String>, TextError> {
+    let Some(max_width) = style.max_width else {
+        return Ok(text.split(' ').map(str::to_string).collect());
+    };
+    let mut lines = vec![];
+    let mut current: Option<String> = None;
+    for word in text.split(' ') {
+        let Some(line) = current.take() else {
+            current = Some(word.to_string());
+            continue;
+        };
+        let candidate = format!("{line} {word}");
+        if generate_text_line(&candidate, style)?.width() <= max_width {
+            current = Some(candidate);
+        } else {
+            lines.push(line);
+            current = Some(word.to_string());
+        }
+    }
+    lines.extend(current);
+    Ok(lines)
+}
+
 /// generates a block of text
-/// splits the text into lines by spaces and generates each line
-/// then combines the lines into a single image
-#[must_use]
-pub fn generate_text_block(text_to_gen: &str, alignment: Alignment) -> DynamicImage {
-    let split: Vec<&str> = text_to_gen.split(' ').collect();
-    let images: Vec<DynamicImage> = split.iter().map(|&s| generate_text_line(s)).collect();
+/// splits the text into lines by spaces (or by `max_width`, see `TextStyle`)
+/// and generates each line, then combines the lines into a single image
+/// # Errors
+/// Errors if the text is over `MAX_LENGTH` characters, contains characters
+/// that aren't printable ascii, or is spaced out too far to draw
+pub fn generate_text_block(
+    text_to_gen: &str,
+    alignment: Alignment,
+    style: TextStyle,
+) -> Result<DynamicImage, TextError> {
+    // checked up front so every invalid character is reported, not just the first
+    // line's
+    check_text(text_to_gen)?;
+    let split = wrap_lines(text_to_gen, style)?;
+    let images = split
+        .iter()
+        .map(|s| generate_text_line(s, style))
+        .collect::<Result<Vec<DynamicImage>, TextError>>()?;
     let longest_line = images.iter().max_by_key(|i| i.width()).unwrap().width();
-    let height = split.len() * CHARACTER_HEIGHT as usize + (split.len() - 1);
-    let mut image = DynamicImage::new_rgba8(longest_line, height as u32);
+    // lines are limited by MAX_LENGTH, so only the spacing can overflow
+    let num_lines = split.len() as u32;
+    let height = (num_lines - 1)
+        .checked_mul(style.line_spacing)
+        .and_then(|spacing| spacing.checked_add(num_lines * CHARACTER_HEIGHT))
+        .ok_or(TextError::SpacingOverflow)?;
+    let mut image =
+        safe_new_image(longest_line, height).map_err(|err| TextError::TooLarge(Box::new(err)))?;
+    let mut y = 0;
     for (i, line) in images.iter().enumerate() {
+        if i > 0 {
+            y += CHARACTER_HEIGHT + style.line_spacing;
+        }
         let x = match alignment {
             Alignment::Left => 0,
             Alignment::Center => (longest_line - line.width()) / 2,
             Alignment::Right => longest_line - line.width(),
         };
-        let y = i * (CHARACTER_HEIGHT as usize + 1);
         image
-            .copy_from(line, x, y as u32)
+            .copy_from(line, x, y)
             .expect("Failed to copy (bad image?)");
     }
-    image
+    Ok(image)
 }
 
 #[must_use]
@@ -131,6 +254,10 @@ pub enum TextError {
     TooLong(u32),
     #[error("Text contains invalid characters: {0:?}")]
     InvalidCharacters(Vec<char>),
+    #[error("Text spacing is too large to lay out")]
+    SpacingOverflow,
+    #[error("Text is too large to draw:\n{0}")]
+    TooLarge(Box<ProcessorError>),
 }
 
 #[cfg(test)]
@@ -161,4 +288,86 @@ mod test {
         let image = get_char_crop(char).unwrap();
         assert_eq!(image.dimensions(), (CHARACTER_WIDTH, CHARACTER_HEIGHT));
     }
+
+    #[test]
+    fn text_spacing() {
+        let default = generate_text_block("ab cd", Alignment::Left, TextStyle::default()).unwrap();
+        assert_eq!(default.dimensions(), (7, 11));
+
+        let style = TextStyle {
+            char_spacing: 0,
+            line_spacing: 3,
+            ..Default::default()
+        };
+        let dense = generate_text_block("ab cd", Alignment::Left, style).unwrap();
+        assert_eq!(dense.dimensions(), (6, 13));
+    }
+
+    #[test]
+    fn huge_spacing() {
+        let spaced = |char_spacing, line_spacing| {
+            TextStyle {
+                char_spacing,
+                line_spacing,
+                ..Default::default()
+            }
+        };
+        assert!(matches!(
+            generate_text_line("ab", spaced(50_000_000, 1)),
+            Err(TextError::TooLarge(_))
+        ));
+        assert!(matches!(
+            generate_text_line("abc", spaced(u32::MAX, 1)),
+            Err(TextError::SpacingOverflow)
+        ));
+        assert!(matches!(
+            generate_text_block("a b c", Alignment::Left, spaced(1, u32::MAX)),
+            Err(TextError::SpacingOverflow)
+        ));
+        // spacing only goes between characters and lines, so a lone one is fine
+        let single = generate_text_block("a", Alignment::Left, spaced(u32::MAX, u32::MAX));
+        assert_eq!(
+            single.unwrap().dimensions(),
+            (CHARACTER_WIDTH, CHARACTER_HEIGHT)
+        );
+    }
+
+    #[test]
+    fn wraps_to_max_width() {
+        let style = TextStyle {
+            max_width: Some(25),
+            ..Default::default()
+        };
+        let lines = wrap_lines("EMERGENCY EXIT ONLY", TextStyle::default()).unwrap();
+        assert_eq!(lines, vec!["EMERGENCY", "EXIT", "ONLY"]);
+        let lines = wrap_lines("NO EXIT HERE EVER", style).unwrap();
+        assert_eq!(lines, vec!["NO EXIT", "HERE", "EVER"]);
+        // too wide on its own, but still gets a line
+        let lines = wrap_lines("A EMERGENCY B", style).unwrap();
+        assert_eq!(lines, vec!["A", "EMERGENCY", "B"]);
+    }
+
+    #[test]
+    fn too_long() {
+        let max = "a".repeat(MAX_LENGTH);
+        assert!(generate_text_line(&max, TextStyle::default()).is_ok());
+
+        let over = "a ".repeat(MAX_LENGTH);
+        assert!(matches!(
+            generate_text_block(&over, Alignment::Left, TextStyle::default()),
+            Err(TextError::TooLong(length)) if length as usize == MAX_LENGTH * 2
+        ));
+    }
+
+    #[test]
+    fn invalid_characters() {
+        let style = TextStyle::default();
+        assert!(generate_text_block("fine text", Alignment::Left, style).is_ok());
+
+        let result = generate_text_block("caf\u{e9}\tcaf\u{e9}", Alignment::Left, style);
+        assert!(matches!(
+            result,
+            Err(TextError::InvalidCharacters(chars)) if chars == vec!['\u{e9}', '\t']
+        ));
+    }
 }
@@ -1,9 +1,10 @@
 use dmi::icon::{Icon, IconState};
 use enum_iterator::all;
-use image::{imageops, DynamicImage, GenericImageView};
+use image::{imageops, DynamicImage};
 use serde::{Deserialize, Serialize};
 
 use crate::config::blocks::cutters::SlicePoint;
+use crate::config::blocks::filters::apply_filters;
 use crate::generation::icon::generate_map_icon;
 use crate::operations::cutters::bitmask_slice::{
     BitmaskSlice,
@@ -11,6 +12,7 @@ use crate::operations::cutters::bitmask_slice::{
     SIZE_OF_CARDINALS,
     SIZE_OF_DIAGONALS,
 };
+use crate::operations::cutters::frame_count;
 use crate::operations::error::{ProcessorError, ProcessorResult};
 use crate::operations::{
     IconOperationConfig,
@@ -20,8 +22,9 @@ use crate::operations::{
     ProcessorPayload,
 };
 use crate::util::adjacency::Adjacency;
+use crate::util::color::{fill_image_color, Color};
 use crate::util::corners::{Corner, Side};
-use crate::util::icon_ops::dedupe_frames;
+use crate::util::icon_ops::{dedupe_frames, safe_new_image};
 use crate::util::repeat_for;
 
 #[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
@@ -29,6 +32,8 @@ pub struct BitmaskDirectionalVis {
     #[serde(flatten)]
     pub bitmask_slice_config: BitmaskSlice,
     pub slice_point: SlicePoint,
+    /// Hex color that every visible pixel of the generated states is filled
+    /// with, for producing flat occlusion masks
     #[serde(skip_serializing_if = "Option::is_none")]
     #[serde(default)]
     pub mask_color: Option<String>,
@@ -45,23 +50,35 @@ impl IconOperationConfig for BitmaskDirectionalVis {
                 "This operation only accepts raw images".to_string(),
             ));
         };
+        let mask_color = self.mask_color()?;
+        let num_frames = frame_count(img, self.bitmask_slice_config.icon_size)?;
         let (corners, prefabs) = self.bitmask_slice_config.generate_corners(img)?;
 
-        let (_in_x, in_y) = img.dimensions();
-        let num_frames = in_y / self.bitmask_slice_config.icon_size.y;
-
         let possible_states = if self.bitmask_slice_config.smooth_diagonally {
             SIZE_OF_DIAGONALS
         } else {
             SIZE_OF_CARDINALS
         };
 
-        let assembled = self.bitmask_slice_config.generate_icons(
+        let mut assembled = self.bitmask_slice_config.generate_icons(
             &corners,
             &prefabs,
             num_frames,
             possible_states,
-        );
+        )?;
+        self.bitmask_slice_config
+            .apply_prefab_overlays(img, &mut assembled)?;
+        // slice points are in icon_size coordinates, so scaling has to wait until
+        // after the cuts
+        let finish_frame = |mut cut_img: DynamicImage| {
+            if let Some(filter) = self.bitmask_slice_config.scale_filter {
+                cut_img = self.bitmask_slice_config.scale_frame(&cut_img, filter);
+            }
+            if let Some(color) = mask_color {
+                fill_image_color(&mut cut_img, color);
+            }
+            cut_img
+        };
 
         let delay = self
             .bitmask_slice_config
@@ -79,32 +96,34 @@ impl IconOperationConfig for BitmaskDirectionalVis {
                 let mut icon_state_frames = vec![];
                 let slice_info = self.get_side_cuts(side);
 
+                let step = slice_info.checked_step(side)?;
+
                 let (x, y, width, height) = if side.is_vertical() {
                     (
                         0,
                         slice_info.start,
                         self.bitmask_slice_config.icon_size.x,
-                        slice_info.step(),
+                        step,
                     )
                 } else {
                     (
                         slice_info.start,
                         0,
-                        slice_info.step(),
+                        step,
                         self.bitmask_slice_config.icon_size.y,
                     )
                 };
 
                 for image in images {
-                    let mut cut_img = DynamicImage::new_rgba8(
+                    let mut cut_img = safe_new_image(
                         self.bitmask_slice_config.icon_size.x,
                         self.bitmask_slice_config.icon_size.y,
-                    );
+                    )?;
 
                     let crop = image.crop_imm(x, y, width, height);
 
                     imageops::overlay(&mut cut_img, &crop, x as i64, y as i64);
-                    icon_state_frames.push(cut_img);
+                    icon_state_frames.push(finish_frame(cut_img));
                 }
                 icon_states.push(dedupe_frames(IconState {
                     name: format!("{}-{}", adjacency.bits(), side.byond_dir()),
@@ -126,27 +145,22 @@ impl IconOperationConfig for BitmaskDirectionalVis {
 
             let horizontal_side_info = self.bitmask_slice_config.get_side_info(horizontal);
             let x = horizontal_side_info.start;
-            let width = horizontal_side_info.step();
+            let width = horizontal_side_info.checked_step(horizontal)?;
 
-            // todo: This is awful, maybe a better way to do this?
-            let (y, height) = if vertical == Side::North {
-                (0, self.slice_point.get(vertical).unwrap())
-            } else {
-                let slice_point = self.slice_point.get(vertical).unwrap();
-                let end = self.bitmask_slice_config.icon_size.y;
-                (slice_point, end - slice_point)
-            };
+            let vertical_cuts = self.get_side_cuts(vertical);
+            let y = vertical_cuts.start;
+            let height = vertical_cuts.checked_step(vertical)?;
 
             for image in convex_images {
-                let mut cut_img = DynamicImage::new_rgba8(
+                let mut cut_img = safe_new_image(
                     self.bitmask_slice_config.icon_size.x,
                     self.bitmask_slice_config.icon_size.y,
-                );
+                )?;
 
                 let crop_img = image.crop_imm(x, y, width, height);
 
                 imageops::overlay(&mut cut_img, &crop_img, x as i64, y as i64);
-                icon_state_frames.push(cut_img);
+                icon_state_frames.push(finish_frame(cut_img));
             }
 
             icon_states.push(dedupe_frames(IconState {
@@ -160,11 +174,15 @@ impl IconOperationConfig for BitmaskDirectionalVis {
             }));
         }
 
+        if let Some(post_process) = &self.bitmask_slice_config.post_process {
+            apply_filters(post_process, &mut icon_states);
+        }
+
         if let Some(map_icon) = &self.bitmask_slice_config.map_icon {
             let icon = generate_map_icon(
                 self.bitmask_slice_config.output_icon_size.x,
                 self.bitmask_slice_config.output_icon_size.y,
-                map_icon,
+                &map_icon.for_input(img),
             )?;
             icon_states.push(IconState {
                 name: map_icon.icon_state_name.clone(),
@@ -176,14 +194,14 @@ impl IconOperationConfig for BitmaskDirectionalVis {
         }
 
         let out_icon = Icon {
-            version: dmi::icon::DmiVersion::default(),
+            version: self.bitmask_slice_config.output_dmi_version()?,
             width: self.bitmask_slice_config.output_icon_size.x,
             height: self.bitmask_slice_config.output_icon_size.y,
             states: icon_states,
         };
 
         if mode == OperationMode::Debug {
-            let mut out = self.bitmask_slice_config.generate_debug_icons(&corners);
+            let mut out = self.bitmask_slice_config.generate_debug_icons(&corners)?;
 
             out.push(NamedIcon::from_icon(out_icon));
             Ok(ProcessorPayload::MultipleNamed(out))
@@ -193,12 +211,60 @@ impl IconOperationConfig for BitmaskDirectionalVis {
     }
 
     fn verify_config(&self) -> ProcessorResult<()> {
-        // TODO: actually verify config
+        self.bitmask_slice_config.verify_config()?;
+        self.mask_color()?;
+        let icon_size = self.bitmask_slice_config.icon_size;
+        let point = |side: Side| {
+            self.slice_point.get(side).ok_or_else(|| {
+                ProcessorError::InvalidConfig(format!("slice_point is missing an entry for {side}"))
+            })
+        };
+        let (north, south, east, west) = (
+            point(Side::North)?,
+            point(Side::South)?,
+            point(Side::East)?,
+            point(Side::West)?,
+        );
+        for (side, value, limit) in [
+            (Side::North, north, icon_size.y),
+            (Side::South, south, icon_size.y),
+            (Side::East, east, icon_size.x),
+            (Side::West, west, icon_size.x),
+        ] {
+            if value > limit {
+                return Err(ProcessorError::InvalidConfig(format!(
+                    "slice_point.{side} ({value}) is outside of the icon ({limit})"
+                )));
+            }
+        }
+        if north > south {
+            return Err(ProcessorError::InvalidConfig(format!(
+                "slice_point.north ({north}) must not be below slice_point.south ({south})"
+            )));
+        }
+        if west > east {
+            return Err(ProcessorError::InvalidConfig(format!(
+                "slice_point.west ({west}) must not be right of slice_point.east ({east})"
+            )));
+        }
         Ok(())
     }
 }
 
 impl BitmaskDirectionalVis {
+    /// Parses `mask_color`
+    /// # Errors
+    /// Errors if `mask_color` isn't a valid hex color
+    pub fn mask_color(&self) -> ProcessorResult<Option<Color>> {
+        self.mask_color
+            .as_deref()
+            .map(|hex| {
+                Color::from_hex_str(hex)
+                    .map_err(|err| ProcessorError::InvalidConfig(format!("mask_color: {err}")))
+            })
+            .transpose()
+    }
+
     /// Gets the side cutter info for a given side based on the slice point
     /// # Panics
     /// Can panic if the `slice_point` map is unpopulated, which shouldn't
@@ -234,3 +300,115 @@ impl BitmaskDirectionalVis {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use fixed_map::Map;
+    use image::{DynamicImage, GenericImageView, Rgba, RgbaImage};
+
+    use super::*;
+    use crate::config::blocks::cutters::{CutPosition, IconSize, OutputIconSize};
+    use crate::config::blocks::filters::ScaleFilter;
+    use crate::operations::OutputImage;
+
+    fn with_slice_points(north: u32, south: u32, east: u32, west: u32) -> BitmaskDirectionalVis {
+        let mut points = Map::new();
+        points.insert(Side::North, north);
+        points.insert(Side::South, south);
+        points.insert(Side::East, east);
+        points.insert(Side::West, west);
+        BitmaskDirectionalVis {
+            bitmask_slice_config: BitmaskSlice::default(),
+            slice_point: SlicePoint(points),
+            mask_color: None,
+        }
+    }
+
+    #[test]
+    fn mask_color() {
+        let mut config = with_slice_points(16, 16, 28, 4);
+        config.mask_color = Some("#ff00ff".to_string());
+        let sheet =
+            DynamicImage::ImageRgba8(RgbaImage::from_pixel(4 * 32, 32, Rgba([255, 0, 0, 255])));
+
+        let ProcessorPayload::Single(output) = config
+            .do_operation(&InputIcon::DynamicImage(sheet), OperationMode::Standard)
+            .unwrap()
+        else {
+            panic!("Expected a single output");
+        };
+        let OutputImage::Dmi(icon) = *output else {
+            panic!("Expected a dmi output");
+        };
+        let visible: Vec<[u8; 4]> = icon
+            .states
+            .iter()
+            .flat_map(|state| &state.images)
+            .flat_map(|image| image.pixels().map(|(_, _, pixel)| pixel.0))
+            .filter(|pixel| pixel[3] != 0)
+            .collect();
+        assert!(!visible.is_empty());
+        assert!(visible.iter().all(|pixel| *pixel == [255, 0, 255, 255]));
+
+        config.mask_color = Some("ff00ff".to_string());
+        assert!(matches!(
+            config.verify_config(),
+            Err(ProcessorError::InvalidConfig(message)) if message.starts_with("mask_color")
+        ));
+    }
+
+    #[test]
+    fn scale_filter() {
+        let mut config = with_slice_points(8, 8, 12, 4);
+        config.bitmask_slice_config = BitmaskSlice {
+            icon_size: IconSize { x: 16, y: 16 },
+            cut_pos: CutPosition { x: 8, y: 8 },
+            output_icon_size: OutputIconSize { x: 32, y: 32 },
+            scale_filter: Some(ScaleFilter::Nearest),
+            ..Default::default()
+        };
+        let sheet =
+            DynamicImage::ImageRgba8(RgbaImage::from_pixel(4 * 16, 16, Rgba([255, 0, 0, 255])));
+
+        let ProcessorPayload::Single(output) = config
+            .do_operation(&InputIcon::DynamicImage(sheet), OperationMode::Standard)
+            .unwrap()
+        else {
+            panic!("Expected a single output");
+        };
+        let OutputImage::Dmi(icon) = *output else {
+            panic!("Expected a dmi output");
+        };
+        let state = |name: &str| &icon.states.iter().find(|s| s.name == name).unwrap().images[0];
+
+        // the north cut is the top half of the 16px icon, so the top half once scaled
+        let north = state(&format!("0-{}", Side::North.byond_dir()));
+        assert_eq!(north.dimensions(), (32, 32));
+        assert_eq!(north.get_pixel(31, 15), Rgba([255, 0, 0, 255]));
+        assert_eq!(north.get_pixel(0, 16)[3], 0);
+
+        // and the east cut is the last 4px, so the last 8px once scaled
+        let east = state(&format!("0-{}", Side::East.byond_dir()));
+        assert_eq!(east.get_pixel(24, 31), Rgba([255, 0, 0, 255]));
+        assert_eq!(east.get_pixel(23, 0)[3], 0);
+    }
+
+    #[test]
+    fn slice_point_verification() {
+        assert!(with_slice_points(16, 16, 28, 4).verify_config().is_ok());
+
+        let is_invalid = |config: BitmaskDirectionalVis, expected: &str| {
+            assert!(matches!(
+                config.verify_config(),
+                Err(ProcessorError::InvalidConfig(message)) if message.contains(expected)
+            ));
+        };
+        is_invalid(with_slice_points(16, 40, 28, 4), "slice_point.south");
+        is_invalid(with_slice_points(20, 10, 28, 4), "slice_point.north");
+        is_invalid(with_slice_points(16, 16, 4, 28), "slice_point.west");
+
+        let mut missing = with_slice_points(16, 16, 28, 4);
+        missing.slice_point.0.remove(Side::East);
+        is_invalid(missing, "missing an entry for east");
+    }
+}
@@ -1,11 +1,13 @@
 use std::collections::{BTreeMap, HashMap};
 
-use dmi::icon::{Icon, IconState};
+use dmi::icon::{DmiVersion, Icon, IconState};
 use enum_iterator::all;
 use fixed_map::Map;
+use image::imageops::FilterType;
 use image::{imageops, DynamicImage, GenericImageView};
+use rayon::prelude::*;
 use serde::{Deserialize, Serialize};
-use tracing::{debug, trace};
+use tracing::{debug, trace, warn};
 
 use crate::config::blocks::cutters::{
     Animation,
@@ -16,9 +18,12 @@ use crate::config::blocks::cutters::{
     Positions,
     PrefabOverlays,
     Prefabs,
+    StateDocs,
 };
+use crate::config::blocks::filters::{apply_filters, FilterStep, ScaleFilter};
 use crate::config::blocks::generators::MapIcon;
 use crate::generation::icon::generate_map_icon;
+use crate::operations::cutters::frame_count;
 use crate::operations::error::{ProcessorError, ProcessorResult};
 use crate::operations::{
     IconOperationConfig,
@@ -30,7 +35,7 @@ use crate::operations::{
 };
 use crate::util::adjacency::Adjacency;
 use crate::util::corners::{Corner, CornerType, Side};
-use crate::util::icon_ops::dedupe_frames;
+use crate::util::icon_ops::{dedupe_frames, dmi_version, grid_size, is_blank, safe_new_image};
 use crate::util::repeat_for;
 
 #[derive(Copy, Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
@@ -40,21 +45,48 @@ pub struct SideSpacing {
 }
 
 impl SideSpacing {
+    /// Length of the side, `None` if it ends before it starts
     #[must_use]
-    pub fn step(self) -> u32 {
-        self.end - self.start
+    pub fn step(self) -> Option<u32> {
+        self.end.checked_sub(self.start)
+    }
+
+    /// Length of the given side
+    /// # Errors
+    /// Errors if the side ends before it starts, which means the cut positions
+    /// it came from are out of order
+    pub fn checked_step(self, side: Side) -> ProcessorResult<u32> {
+        self.step().ok_or_else(|| {
+            ProcessorError::InvalidConfig(format!(
+                "{side} side ends ({}) before it starts ({}), check the cut positions",
+                self.end, self.start
+            ))
+        })
     }
 }
 
-#[derive(Clone, PartialEq, Debug, Default, Serialize, Deserialize)]
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[allow(clippy::struct_excessive_bools)] // these are all independent config toggles
 pub struct BitmaskSlice {
     #[serde(skip_serializing_if = "Option::is_none")]
     #[serde(default)]
     pub output_name: Option<String>,
     pub produce_dirs: bool,
+    /// With `produce_dirs`, outputs each direction as its own single dir
+    /// state named `{signature}-{dir}`, instead of one four dir state.
+    /// A dmi state shares one set of delays between all of its dirs, so this
+    /// is required for `per_direction_delays` that differ from `delays`
+    #[serde(default)]
+    pub split_dirs: bool,
     pub smooth_diagonally: bool,
     pub icon_size: IconSize,
     pub output_icon_pos: OutputIconPosition,
+    /// Where prefabs and prefab overlays are placed in the output icon, for
+    /// when they're authored with a different anchor to the corners. Defaults
+    /// to `output_icon_pos`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub prefab_pos: Option<OutputIconPosition>,
     pub output_icon_size: OutputIconSize,
     pub positions: Positions,
     pub cut_pos: CutPosition,
@@ -70,6 +102,85 @@ pub struct BitmaskSlice {
     #[serde(skip_serializing_if = "Option::is_none")]
     #[serde(default)]
     pub map_icon: Option<MapIcon>,
+    /// Adjacency signatures that should not be generated, for when they're
+    /// already provided elsewhere
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub exclude_signatures: Option<Vec<u8>>,
+    /// Filters applied in order to every frame after assembly
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub post_process: Option<Vec<FilterStep>>,
+    /// Descriptions of signatures, written out to a text file next to the dmi
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub state_docs: Option<StateDocs>,
+    /// Version string written in to the output dmi's metadata, defaults to
+    /// 4.0
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub dmi_version: Option<String>,
+    /// Drops trailing frames that are fully transparent in every generated
+    /// state
+    #[serde(default)]
+    pub trim_empty_frames: bool,
+    /// Merges identical consecutive frames of each state in to one longer
+    /// frame. On unless set to false in the config.
+    #[serde(default = "default_dedupe_animation")]
+    pub dedupe_animation: bool,
+    /// If set, icons are assembled at `icon_size` and then resized to
+    /// `output_icon_size` with this filter, instead of being placed at
+    /// `output_icon_pos` unscaled
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub scale_filter: Option<ScaleFilter>,
+    /// Template for state names, with `{signature}`, `{prefix}` (the
+    /// `output_name`, or empty) and `{dir}` (the byond dir of states split per
+    /// direction, or empty) placeholders. Defaults to `{prefix}-{signature}`,
+    /// or just `{signature}` without an `output_name`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub state_name_format: Option<String>,
+    /// Adds a `preview` state tiling a small patch of walls, showing how the
+    /// generated states join up. Shrunk down to fit in a single icon.
+    #[serde(default)]
+    pub sample_state: bool,
+}
+
+fn default_dedupe_animation() -> bool {
+    true
+}
+
+// kept in line with the serde defaults, so a struct built from `default()`
+// behaves the same as a config that leaves the keys out
+impl Default for BitmaskSlice {
+    fn default() -> Self {
+        Self {
+            output_name: None,
+            produce_dirs: false,
+            split_dirs: false,
+            smooth_diagonally: false,
+            icon_size: IconSize::default(),
+            output_icon_pos: OutputIconPosition::default(),
+            prefab_pos: None,
+            output_icon_size: OutputIconSize::default(),
+            positions: Positions::default(),
+            cut_pos: CutPosition::default(),
+            animation: None,
+            prefabs: None,
+            prefab_overlays: None,
+            map_icon: None,
+            exclude_signatures: None,
+            post_process: None,
+            state_docs: None,
+            dmi_version: None,
+            trim_empty_frames: false,
+            dedupe_animation: default_dedupe_animation(),
+            scale_filter: None,
+            state_name_format: None,
+            sample_state: false,
+        }
+    }
 }
 
 impl IconOperationConfig for BitmaskSlice {
@@ -80,15 +191,23 @@ impl IconOperationConfig for BitmaskSlice {
         mode: OperationMode,
     ) -> ProcessorResult<ProcessorPayload> {
         debug!("Starting bitmask slice icon op");
-        let InputIcon::DynamicImage(img) = input else {
-            return Err(ProcessorError::FormatError(
-                "This operation only accepts raw images".to_string(),
-            ));
+        let sheet;
+        let img = match input {
+            InputIcon::DynamicImage(img) => img,
+            InputIcon::Dmi(icon) => {
+                sheet = match self.corner_states_to_sheet(icon) {
+                    Some(sheet) => sheet?,
+                    None => dmi_to_sheet(icon)?,
+                };
+                &sheet
+            }
         };
+        let mut num_frames = frame_count(img, self.icon_size)?;
         let (corners, prefabs) = self.generate_corners(img)?;
 
-        let (_in_x, in_y) = img.dimensions();
-        let num_frames = in_y / self.icon_size.y;
+        if mode == OperationMode::Preview {
+            return self.generate_preview(img, &corners, &prefabs, num_frames);
+        }
 
         let possible_states = if self.smooth_diagonally {
             SIZE_OF_DIAGONALS
@@ -97,56 +216,96 @@ impl IconOperationConfig for BitmaskSlice {
         };
 
         let icon_directions = if self.produce_dirs {
-            Adjacency::dmi_cardinals().to_vec()
+            Side::dmi_cardinals().to_vec()
         } else {
-            vec![Adjacency::S]
+            vec![Side::South]
         };
 
         // First phase: generate icons
-        let assembled = self.generate_icons(&corners, &prefabs, num_frames, possible_states);
+        let mut assembled = self.generate_icons(&corners, &prefabs, num_frames, possible_states)?;
+        self.apply_prefab_overlays(img, &mut assembled)?;
+        self.scale_assembled(&mut assembled);
+        if self.trim_empty_frames {
+            num_frames = trim_trailing_blank_frames(&mut assembled, num_frames);
+        }
 
         // Second phase: map to byond icon states and produce dirs if need
         // Even though this is the same loop as what happens in generate_icons,
         // all states need to be generated first for the
         // Rotation to work correctly, so it must be done as a second loop.
-        let mut icon_states = vec![];
-
-        let delay = self
-            .animation
-            .clone()
-            .map(|x| repeat_for(&x.delays, num_frames as usize));
+        let direction_delays: Vec<Option<Vec<f32>>> = icon_directions
+            .iter()
+            .map(|side| self.direction_delay(*side, num_frames))
+            .collect();
+        // verify_config makes sure directions with their own delays are split out,
+        // as a dmi icon state only has one set of delays shared by all of its dirs
+        let split_dirs = self.split_dirs && self.produce_dirs;
 
-        let states_to_gen = (0..possible_states)
+        let states_to_gen: Vec<Adjacency> = (0..possible_states)
             .map(|x| Adjacency::from_bits(x as u8).unwrap())
-            .filter(Adjacency::ref_has_no_orphaned_corner);
-        for adjacency in states_to_gen {
-            let mut icon_state_frames = vec![];
-
-            for icon_state_dir in &icon_directions {
-                let rotated_sig = adjacency.rotate_to(*icon_state_dir);
-                trace!(sig = ?icon_state_dir, rotated_sig = ?rotated_sig, "Rotated");
-                icon_state_frames.extend(assembled[&rotated_sig].clone());
-            }
+            .filter(Adjacency::ref_has_no_orphaned_corner)
+            .filter(|adjacency| !self.is_excluded(*adjacency))
+            .collect();
+        // Runs on whatever rayon pool we're called from, so this shares threads with
+        // any file level parallelism instead of spawning more. Collecting keeps the
+        // original state order.
+        let mut icon_states: Vec<IconState> = states_to_gen
+            .par_iter()
+            .flat_map_iter(|adjacency| {
+                let name = self.state_name(*adjacency);
 
-            let signature = adjacency.bits();
-            let name = if let Some(prefix_name) = &self.output_name {
-                format!("{prefix_name}-{signature}")
-            } else {
-                format!("{signature}")
-            };
-            icon_states.push(dedupe_frames(IconState {
-                name,
-                dirs: icon_directions.len() as u8,
-                frames: num_frames,
-                images: icon_state_frames,
-                delay: delay.clone(),
-                ..Default::default()
-            }));
+                if split_dirs {
+                    return icon_directions
+                        .iter()
+                        .zip(&direction_delays)
+                        .map(|(icon_state_dir, delay)| {
+                            let rotated_sig = adjacency.rotate_to(Adjacency::from(*icon_state_dir));
+                            trace!(sig = ?icon_state_dir, rotated_sig = ?rotated_sig, "Rotated");
+                            self.finish_state(IconState {
+                                name: self.dir_state_name(*adjacency, *icon_state_dir),
+                                dirs: 1,
+                                frames: num_frames,
+                                images: assembled[&rotated_sig].clone(),
+                                delay: delay.clone(),
+                                ..Default::default()
+                            })
+                        })
+                        .collect();
+                }
+
+                let mut icon_state_frames = vec![];
+
+                for icon_state_dir in &icon_directions {
+                    let rotated_sig = adjacency.rotate_to(Adjacency::from(*icon_state_dir));
+                    trace!(sig = ?icon_state_dir, rotated_sig = ?rotated_sig, "Rotated");
+                    icon_state_frames.extend(assembled[&rotated_sig].clone());
+                }
+
+                vec![self.finish_state(IconState {
+                    name,
+                    dirs: icon_directions.len() as u8,
+                    frames: num_frames,
+                    images: icon_state_frames,
+                    delay: direction_delays[0].clone(),
+                    ..Default::default()
+                })]
+            })
+            .collect();
+
+        if self.sample_state {
+            icon_states.push(self.generate_sample_state(&assembled)?);
+        }
+
+        if let Some(post_process) = &self.post_process {
+            apply_filters(post_process, &mut icon_states);
         }
 
         if let Some(map_icon) = &self.map_icon {
-            let icon =
-                generate_map_icon(self.output_icon_size.x, self.output_icon_size.y, map_icon)?;
+            let icon = generate_map_icon(
+                self.output_icon_size.x,
+                self.output_icon_size.y,
+                &map_icon.for_input(img),
+            )?;
             icon_states.push(IconState {
                 name: map_icon.icon_state_name.clone(),
                 dirs: 1,
@@ -157,25 +316,91 @@ impl IconOperationConfig for BitmaskSlice {
         }
 
         let output_icon = Icon {
-            version: dmi::icon::DmiVersion::default(),
+            version: self.output_dmi_version()?,
             width: self.output_icon_size.x,
             height: self.output_icon_size.y,
             states: icon_states,
         };
 
+        let docs = self
+            .state_docs
+            .as_ref()
+            .map(|state_docs| self.generate_docs(state_docs, &states_to_gen));
+
         if mode == OperationMode::Debug {
             debug!("Starting debug output");
-            let mut out = self.generate_debug_icons(&corners);
+            let mut out = self.generate_debug_icons(&corners)?;
 
             out.push(NamedIcon::from_icon(output_icon));
+            out.extend(docs);
             Ok(ProcessorPayload::MultipleNamed(out))
+        } else if let Some(docs) = docs {
+            Ok(ProcessorPayload::MultipleNamed(vec![
+                NamedIcon::from_icon(output_icon),
+                docs,
+            ]))
         } else {
             Ok(ProcessorPayload::from_icon(output_icon))
         }
     }
 
     fn verify_config(&self) -> ProcessorResult<()> {
-        // TODO: Actual verification
+        let invalid = |message: String| Err(ProcessorError::InvalidConfig(message));
+        if self.icon_size.x == 0 || self.icon_size.y == 0 {
+            return invalid(format!(
+                "icon_size must be non-zero, found {}x{}",
+                self.icon_size.x, self.icon_size.y
+            ));
+        }
+        if self.output_icon_size.x == 0 || self.output_icon_size.y == 0 {
+            return invalid(format!(
+                "output_icon_size must be non-zero, found {}x{}",
+                self.output_icon_size.x, self.output_icon_size.y
+            ));
+        }
+        if self.cut_pos.x >= self.icon_size.x {
+            return invalid(format!(
+                "cut_pos.x ({}) must be less than icon_size.x ({})",
+                self.cut_pos.x, self.icon_size.x
+            ));
+        }
+        if self.cut_pos.y >= self.icon_size.y {
+            return invalid(format!(
+                "cut_pos.y ({}) must be less than icon_size.y ({})",
+                self.cut_pos.y, self.icon_size.y
+            ));
+        }
+        for corner_type in self.corner_types() {
+            if self.positions.get(corner_type).is_none() {
+                return invalid(format!("positions is missing an entry for {corner_type}"));
+            }
+        }
+        if self.produce_dirs && !self.split_dirs {
+            if let Some(animation) = &self.animation {
+                let differing = Side::dmi_cardinals().into_iter().find(|side| {
+                    animation
+                        .per_direction_delays
+                        .as_ref()
+                        .and_then(|per_direction| per_direction.get(*side))
+                        .is_some_and(|delays| *delays != animation.delays)
+                });
+                if let Some(side) = differing {
+                    return invalid(format!(
+                        "per_direction_delays for {side} differ from delays, which needs \
+                         split_dirs = true as a state's dirs share their delays. Note that split \
+                         dirs are output as separate states named {{signature}}-{{dir}}"
+                    ));
+                }
+            }
+        }
+        if let Some(format) = &self.state_name_format {
+            if !format.contains("{signature}") {
+                return invalid(format!(
+                    "state_name_format \"{format}\" must contain {{signature}}, or every state \
+                     gets the same name"
+                ));
+            }
+        }
         Ok(())
     }
 }
@@ -188,14 +413,133 @@ type PrefabPayload = HashMap<Adjacency, Vec<DynamicImage>>;
 pub const SIZE_OF_CARDINALS: usize = usize::pow(2, 4);
 pub const SIZE_OF_DIAGONALS: usize = usize::pow(2, 8);
 
+/// Width and height, in cells, of the patch drawn by `sample_state`
+const SAMPLE_SIZE: u32 = 3;
+/// Patch drawn by `sample_state`. A T junction running down in to a corner,
+/// with ends on the open sides, so most kinds of join show up.
+const SAMPLE_LAYOUT: [[bool; SAMPLE_SIZE as usize]; SAMPLE_SIZE as usize] = [
+    [true, true, true],
+    [false, true, false],
+    [false, true, true],
+];
+
 impl BitmaskSlice {
+    /// Gets the delays to use for a given direction, preferring the direction
+    /// specific delays if they are set
+    #[must_use]
+    pub fn direction_delay(&self, side: Side, num_frames: u32) -> Option<Vec<f32>> {
+        let animation = self.animation.as_ref()?;
+        let delays = animation
+            .per_direction_delays
+            .as_ref()
+            .and_then(|per_direction| per_direction.get(side))
+            .unwrap_or(&animation.delays);
+        Some(repeat_for(delays, num_frames as usize))
+    }
+
+    /// Dedupes the frames of a generated state, if `dedupe_animation` is set
+    #[must_use]
+    pub fn finish_state(&self, icon_state: IconState) -> IconState {
+        if self.dedupe_animation {
+            dedupe_frames(icon_state)
+        } else {
+            icon_state
+        }
+    }
+
+    /// The version to write to output dmis, from `dmi_version` if it's set
+    /// # Errors
+    /// Errors if the configured version isn't valid
+    pub fn output_dmi_version(&self) -> ProcessorResult<DmiVersion> {
+        self.dmi_version
+            .as_deref()
+            .map_or_else(|| Ok(DmiVersion::default()), dmi_version)
+    }
+
+    /// The corner types that need to be cut for this config
+    #[must_use]
+    pub fn corner_types(&self) -> Vec<CornerType> {
+        if self.smooth_diagonally {
+            CornerType::diagonal()
+        } else {
+            CornerType::cardinal()
+        }
+    }
+
+    /// The name of the icon state generated for a signature
+    #[must_use]
+    pub fn state_name(&self, adjacency: Adjacency) -> String {
+        self.format_state_name(adjacency, None)
+    }
+
+    /// The name of a single direction of a signature, for when directions are
+    /// split in to their own states
+    #[must_use]
+    pub fn dir_state_name(&self, adjacency: Adjacency, side: Side) -> String {
+        self.format_state_name(adjacency, Some(side))
+    }
+
+    fn format_state_name(&self, adjacency: Adjacency, side: Option<Side>) -> String {
+        let signature = adjacency.bits().to_string();
+        let dir = side.map(|side| side.byond_dir().to_string());
+        let Some(format) = &self.state_name_format else {
+            let name = if let Some(prefix_name) = &self.output_name {
+                format!("{prefix_name}-{signature}")
+            } else {
+                signature
+            };
+            return match dir {
+                Some(dir) => format!("{name}-{dir}"),
+                None => name,
+            };
+        };
+        let name = format
+            .replace("{signature}", &signature)
+            .replace("{prefix}", self.output_name.as_deref().unwrap_or_default())
+            .replace("{dir}", dir.as_deref().unwrap_or_default());
+        // split dirs would collide if the format has nowhere to put them
+        match dir {
+            Some(dir) if !format.contains("{dir}") => format!("{name}-{dir}"),
+            _ => name,
+        }
+    }
+
+    /// Builds a text file listing the generated states that have docs, and
+    /// what they represent
+    #[must_use]
+    pub fn generate_docs(&self, state_docs: &StateDocs, generated: &[Adjacency]) -> NamedIcon {
+        let text: String = generated
+            .iter()
+            .filter_map(|adjacency| {
+                let doc = state_docs.0.get(&adjacency.bits())?;
+                Some(format!("{}: {doc}\n", self.state_name(*adjacency)))
+            })
+            .collect();
+        NamedIcon {
+            path_hint: None,
+            name_hint: Some("docs".to_string()),
+            image: OutputImage::Text(text),
+        }
+    }
+
+    /// Whether the signature has been excluded from output by the config
+    #[must_use]
+    pub fn is_excluded(&self, adjacency: Adjacency) -> bool {
+        self.exclude_signatures
+            .as_ref()
+            .is_some_and(|excluded| excluded.contains(&adjacency.bits()))
+    }
+
+    /// Cuts every corner out of the column at `position`
+    /// # Errors
+    /// Errors if `cut_pos` puts a side's end before its start
     #[tracing::instrument(skip(img))]
     pub fn build_corner(
         &self,
         img: &DynamicImage,
         position: u32,
         num_frames: u32,
-    ) -> Map<Corner, Vec<DynamicImage>> {
+    ) -> ProcessorResult<Map<Corner, Vec<DynamicImage>>> {
         let mut out = Map::new();
 
         for corner in all::<Corner>() {
@@ -213,8 +557,8 @@ impl BitmaskSlice {
                 let x = (position * self.icon_size.x) + x_offset;
                 let y = (frame_num * self.icon_size.y) + y_offset;
 
-                let width = x_spacing.step();
-                let height = y_spacing.step();
+                let width = x_spacing.checked_step(x_side)?;
+                let height = y_spacing.checked_step(y_side)?;
                 trace!(
                     corner = ?corner,
                     x = ?x,
@@ -227,7 +571,7 @@ impl BitmaskSlice {
                 frame_vec.push(corner_img);
             }
         }
-        out
+        Ok(out)
     }
 
     /// Generates corners
@@ -240,30 +584,32 @@ impl BitmaskSlice {
         &self,
         img: &DynamicImage,
     ) -> ProcessorResult<(CornerPayload, PrefabPayload)> {
-        let (_width, height) = img.dimensions();
+        let (width, height) = img.dimensions();
 
         let num_frames = height / self.icon_size.y;
-
-        let corner_types = if self.smooth_diagonally {
-            CornerType::diagonal()
-        } else {
-            CornerType::cardinal()
-        };
+        let check_column =
+            |field: String, position: u32| self.check_column(width, &field, position);
 
         let mut corner_map: CornerPayload = Map::new();
 
-        for corner_type in &corner_types[..] {
-            let position = self.positions.get(*corner_type).unwrap();
+        for corner_type in self.corner_types() {
+            let Some(position) = self.positions.get(corner_type) else {
+                return Err(ProcessorError::InvalidConfig(format!(
+                    "positions is missing an entry for {corner_type}"
+                )));
+            };
+            check_column(format!("positions.{corner_type}"), position)?;
 
-            let corners = self.build_corner(img, position, num_frames);
+            let corners = self.build_corner(img, position, num_frames)?;
 
-            corner_map.insert(*corner_type, corners);
+            corner_map.insert(corner_type, corners);
         }
 
         let mut prefabs: PrefabPayload = HashMap::new();
 
         if let Some(prefabs_config) = &self.prefabs {
             for (adjacency_bits, position) in &prefabs_config.0 {
+                check_column(format!("prefabs.{adjacency_bits}"), *position)?;
                 let mut frame_vector = vec![];
                 for frame in 0..num_frames {
                     let x = position * self.icon_size.x;
@@ -272,85 +618,401 @@ impl BitmaskSlice {
 
                     frame_vector.push(img);
                 }
-                prefabs.insert(Adjacency::from_bits(*adjacency_bits).unwrap(), frame_vector);
+                let adjacency = signature_key("prefabs", *adjacency_bits);
+                prefabs.insert(adjacency, frame_vector);
             }
         }
 
         Ok((corner_map, prefabs))
     }
 
-    /// Blah
-    /// # Panics
-    /// Whatever
+    /// For dmis that have a state per corner type, named after the corner type
+    /// (`convex`, `concave`, etc), lays each state out at its column in
+    /// `positions` to build a sheet that can be cut like a png.
+    ///
+    /// Returns `None` if the dmi doesn't have a state for every corner type
+    /// needed.
     #[must_use]
+    pub fn corner_states_to_sheet(&self, icon: &Icon) -> Option<ProcessorResult<DynamicImage>> {
+        let corner_states: Vec<(u32, &IconState)> = self
+            .corner_types()
+            .into_iter()
+            .map(|corner_type| {
+                let position = self.positions.get(corner_type)?;
+                let name = corner_type.to_string();
+                let state = icon.states.iter().find(|state| state.name == name)?;
+                Some((position, state))
+            })
+            .collect::<Option<_>>()?;
+
+        Some(self.layout_corner_states(icon, &corner_states))
+    }
+
+    fn layout_corner_states(
+        &self,
+        icon: &Icon,
+        corner_states: &[(u32, &IconState)],
+    ) -> ProcessorResult<DynamicImage> {
+        if (icon.width, icon.height) != (self.icon_size.x, self.icon_size.y) {
+            return Err(ProcessorError::InvalidConfig(format!(
+                "Corner states are {}x{}, but icon_size is {}x{}",
+                icon.width, icon.height, self.icon_size.x, self.icon_size.y
+            )));
+        }
+        let columns = corner_states
+            .iter()
+            .map(|(position, _)| position + 1)
+            .max()
+            .unwrap_or(0);
+        let frames = corner_states
+            .iter()
+            .map(|(_, state)| state.frames)
+            .max()
+            .unwrap_or(1);
+        let (width, height) = grid_size(
+            u64::from(columns),
+            u64::from(frames),
+            icon.width,
+            icon.height,
+        )?;
+        let mut sheet = safe_new_image(width, height)?;
+        for (position, state) in corner_states {
+            let first_dir = state.images.iter().step_by(usize::from(state.dirs.max(1)));
+            for (frame, image) in (0..state.frames).zip(first_dir) {
+                imageops::replace(
+                    &mut sheet,
+                    image,
+                    i64::from(position * icon.width),
+                    i64::from(frame * icon.height),
+                );
+            }
+        }
+        Ok(sheet)
+    }
+
+    /// Errors if `position` is past the last column of a sheet `width` pixels
+    /// wide. `field` is the config field the position came from.
+    fn check_column(&self, width: u32, field: &str, position: u32) -> ProcessorResult<()> {
+        let columns = width / self.icon_size.x;
+        if position >= columns {
+            return Err(ProcessorError::InvalidConfig(format!(
+                "{field} points at column {position}, but the input only has {columns} columns"
+            )));
+        }
+        Ok(())
+    }
+
+    /// Signatures generated in preview mode: isolated, a corner, straight
+    /// lines, a T junction and fully surrounded
+    #[must_use]
+    pub fn preview_signatures(&self) -> Vec<Adjacency> {
+        let mut signatures = vec![
+            Adjacency::empty(),
+            Adjacency::S | Adjacency::E,
+            Adjacency::N_S,
+            Adjacency::E_W,
+            Adjacency::N_S | Adjacency::E,
+            Adjacency::CARDINALS,
+        ];
+        if self.smooth_diagonally {
+            signatures.push(Adjacency::all());
+        }
+        signatures
+    }
+
+    /// Tiles the first frame of each cell of `SAMPLE_LAYOUT`, using the
+    /// signature its neighbours in the layout give it, then shrinks the patch
+    /// down to a single icon
+    fn generate_sample_state(
+        &self,
+        assembled: &BTreeMap<Adjacency, Vec<DynamicImage>>,
+    ) -> ProcessorResult<IconState> {
+        let (width, height) = (self.output_icon_size.x, self.output_icon_size.y);
+        let mut patch = safe_new_image(width * SAMPLE_SIZE, height * SAMPLE_SIZE)?;
+        for (y, row) in (0..).zip(SAMPLE_LAYOUT) {
+            for (x, filled) in (0..).zip(row) {
+                if !filled {
+                    continue;
+                }
+                let frame = &assembled[&self.sample_signature(x, y)][0];
+                imageops::replace(
+                    &mut patch,
+                    frame,
+                    i64::from(x * width),
+                    i64::from(y * height),
+                );
+            }
+        }
+        Ok(IconState {
+            name: "preview".to_string(),
+            dirs: 1,
+            frames: 1,
+            images: vec![patch.resize_exact(width, height, FilterType::Nearest)],
+            ..Default::default()
+        })
+    }
+
+    /// The signature of a cell of `SAMPLE_LAYOUT`, from which of its neighbours
+    /// are filled
+    fn sample_signature(&self, x: u32, y: u32) -> Adjacency {
+        let filled = |dx: i32, dy: i32| {
+            let (Some(x), Some(y)) = (x.checked_add_signed(dx), y.checked_add_signed(dy)) else {
+                return false;
+            };
+            SAMPLE_LAYOUT
+                .get(y as usize)
+                .and_then(|row| row.get(x as usize))
+                .copied()
+                .unwrap_or(false)
+        };
+        let neighbours = [
+            (Adjacency::N, 0, -1),
+            (Adjacency::S, 0, 1),
+            (Adjacency::E, 1, 0),
+            (Adjacency::W, -1, 0),
+            (Adjacency::NE, 1, -1),
+            (Adjacency::SE, 1, 1),
+            (Adjacency::SW, -1, 1),
+            (Adjacency::NW, -1, -1),
+        ];
+        let mut signature = Adjacency::empty();
+        for (direction, dx, dy) in neighbours {
+            signature.set(direction, filled(dx, dy));
+        }
+        // corners only count when both of their sides are filled too
+        for corner in Adjacency::diagonals() {
+            if !self.smooth_diagonally || !signature.adjacent_corners_filled(corner) {
+                signature.remove(corner);
+            }
+        }
+        signature
+    }
+
+    /// Builds a single dir icon with only `preview_signatures`, skipping docs,
+    /// map icons and debug output
+    fn generate_preview(
+        &self,
+        img: &DynamicImage,
+        corners: &CornerPayload,
+        prefabs: &PrefabPayload,
+        num_frames: u32,
+    ) -> ProcessorResult<ProcessorPayload> {
+        let signatures = self.preview_signatures();
+        let mut assembled = self.generate_signatures(corners, prefabs, num_frames, &signatures)?;
+        self.apply_prefab_overlays(img, &mut assembled)?;
+        self.scale_assembled(&mut assembled);
+
+        let delay = self.direction_delay(Side::South, num_frames);
+        let mut icon_states: Vec<IconState> = assembled
+            .into_iter()
+            .map(|(adjacency, images)| {
+                self.finish_state(IconState {
+                    name: self.state_name(adjacency),
+                    dirs: 1,
+                    frames: num_frames,
+                    images,
+                    delay: delay.clone(),
+                    ..Default::default()
+                })
+            })
+            .collect();
+        if let Some(post_process) = &self.post_process {
+            apply_filters(post_process, &mut icon_states);
+        }
+
+        Ok(ProcessorPayload::from_icon(Icon {
+            version: self.output_dmi_version()?,
+            width: self.output_icon_size.x,
+            height: self.output_icon_size.y,
+            states: icon_states,
+        }))
+    }
+
+    /// Size of the canvas icons are assembled on, before any scaling
+    #[must_use]
+    pub fn assembly_size(&self) -> (u32, u32) {
+        if self.scale_filter.is_some() {
+            (self.icon_size.x, self.icon_size.y)
+        } else {
+            (self.output_icon_size.x, self.output_icon_size.y)
+        }
+    }
+
+    /// Where prefabs and overlays are placed on the assembly canvas, from
+    /// `prefab_pos` if it's set
+    #[must_use]
+    pub fn assembly_pos(&self) -> (u32, u32) {
+        let pos = self.prefab_pos.unwrap_or(self.output_icon_pos);
+        if self.scale_filter.is_some() {
+            (0, 0)
+        } else {
+            (pos.x, pos.y)
+        }
+    }
+
+    /// Resizes every assembled frame to `output_icon_size` if `scale_filter`
+    /// is set, otherwise does nothing
+    pub fn scale_assembled(&self, assembled: &mut BTreeMap<Adjacency, Vec<DynamicImage>>) {
+        let Some(filter) = self.scale_filter else {
+            return;
+        };
+        for frame in assembled.values_mut().flatten() {
+            *frame = self.scale_frame(frame, filter);
+        }
+    }
+
+    /// Resizes a frame assembled at `icon_size` to `output_icon_size`
+    #[must_use]
+    pub fn scale_frame(&self, frame: &DynamicImage, filter: ScaleFilter) -> DynamicImage {
+        frame.resize_exact(
+            self.output_icon_size.x,
+            self.output_icon_size.y,
+            filter.into(),
+        )
+    }
+
+    /// Draws the columns listed in `prefab_overlays` over the assembled frames
+    /// of their signatures, in order.
+    /// # Errors
+    /// Errors if an overlay column is off the sheet, or the signature isn't one
+    /// that gets generated
+    pub fn apply_prefab_overlays(
+        &self,
+        img: &DynamicImage,
+        assembled: &mut BTreeMap<Adjacency, Vec<DynamicImage>>,
+    ) -> ProcessorResult<()> {
+        let Some(prefab_overlays) = &self.prefab_overlays else {
+            return Ok(());
+        };
+        let (pos_x, pos_y) = self.assembly_pos();
+        for (adjacency_bits, positions) in &prefab_overlays.0 {
+            let adjacency = signature_key("prefab_overlays", *adjacency_bits);
+            if !self.smooth_diagonally && !Adjacency::CARDINALS.contains(adjacency) {
+                return Err(ProcessorError::InvalidConfig(format!(
+                    "prefab_overlays.{adjacency_bits} isn't a signature that gets generated"
+                )));
+            }
+            // not every signature is assembled in preview mode
+            let Some(frames) = assembled.get_mut(&adjacency) else {
+                continue;
+            };
+            for position in positions {
+                self.check_column(
+                    img.width(),
+                    &format!("prefab_overlays.{adjacency_bits}"),
+                    *position,
+                )?;
+                for (frame_num, frame) in (0..).zip(frames.iter_mut()) {
+                    let overlay = img.crop_imm(
+                        position * self.icon_size.x,
+                        frame_num * self.icon_size.y,
+                        self.icon_size.x,
+                        self.icon_size.y,
+                    );
+                    imageops::overlay(frame, &overlay, i64::from(pos_x), i64::from(pos_y));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Assembles the frames of every signature from the cut corners (or
+    /// prefabs), keyed by signature.
+    ///
+    /// Signatures are independent of each other, so they're built in parallel
+    /// on the current rayon pool.
+    /// # Errors
+    /// Errors if the output icon size is too large to allocate
+    /// # Panics
+    /// Panics if the corners or prefabs are missing frames
     pub fn generate_icons(
         &self,
         corners: &CornerPayload,
         prefabs: &PrefabPayload,
         num_frames: u32,
         possible_states: usize,
-    ) -> BTreeMap<Adjacency, Vec<DynamicImage>> {
-        let mut assembled: BTreeMap<Adjacency, Vec<DynamicImage>> = BTreeMap::new();
-        for signature in 0..possible_states {
-            let adjacency = Adjacency::from_bits(signature as u8).unwrap();
-            let mut icon_state_images = vec![];
-            for frame in 0..num_frames {
-                if prefabs.contains_key(&adjacency) {
-                    let mut frame_image =
-                        DynamicImage::new_rgba8(self.output_icon_size.x, self.output_icon_size.y);
-                    imageops::replace(
-                        &mut frame_image,
-                        prefabs
-                            .get(&adjacency)
-                            .unwrap()
-                            .get(frame as usize)
-                            .unwrap(),
-                        self.output_icon_pos.x as i64,
-                        self.output_icon_pos.y as i64,
-                    );
+    ) -> ProcessorResult<BTreeMap<Adjacency, Vec<DynamicImage>>> {
+        let signatures: Vec<Adjacency> = (0..possible_states)
+            .map(|signature| Adjacency::from_bits(signature as u8).unwrap())
+            .collect();
+        self.generate_signatures(corners, prefabs, num_frames, &signatures)
+    }
 
-                    icon_state_images.push(frame_image);
-                } else {
-                    let mut frame_image =
-                        DynamicImage::new_rgba8(self.output_icon_size.x, self.output_icon_size.y);
-
-                    for corner in all::<Corner>() {
-                        let corner_type = adjacency.get_corner_type(corner);
-                        let corner_img = &corners
-                            .get(corner_type)
-                            .unwrap()
-                            .get(corner)
-                            .unwrap()
-                            .get(frame as usize)
-                            .unwrap();
-
-                        let (horizontal, vertical) = corner.sides_of_corner();
-                        let horizontal = self.get_side_info(horizontal);
-                        let vertical = self.get_side_info(vertical);
-
-                        imageops::overlay(
+    /// Same as `generate_icons`, but only assembles the given signatures
+    /// # Errors
+    /// Errors if the output icon size is too large to allocate
+    /// # Panics
+    /// Panics if the corners or prefabs are missing frames
+    pub fn generate_signatures(
+        &self,
+        corners: &CornerPayload,
+        prefabs: &PrefabPayload,
+        num_frames: u32,
+        signatures: &[Adjacency],
+    ) -> ProcessorResult<BTreeMap<Adjacency, Vec<DynamicImage>>> {
+        let (canvas_x, canvas_y) = self.assembly_size();
+        let (pos_x, pos_y) = self.assembly_pos();
+        signatures
+            .par_iter()
+            .map(|&adjacency| {
+                let mut icon_state_images = vec![];
+                for frame in 0..num_frames {
+                    if prefabs.contains_key(&adjacency) {
+                        let mut frame_image = safe_new_image(canvas_x, canvas_y)?;
+                        imageops::replace(
                             &mut frame_image,
-                            *corner_img,
-                            horizontal.start as i64,
-                            vertical.start as i64,
+                            prefabs
+                                .get(&adjacency)
+                                .unwrap()
+                                .get(frame as usize)
+                                .unwrap(),
+                            i64::from(pos_x),
+                            i64::from(pos_y),
                         );
+
+                        icon_state_images.push(frame_image);
+                    } else {
+                        let mut frame_image = safe_new_image(canvas_x, canvas_y)?;
+
+                        for corner in all::<Corner>() {
+                            let corner_type = adjacency.get_corner_type(corner);
+                            let corner_img = &corners
+                                .get(corner_type)
+                                .unwrap()
+                                .get(corner)
+                                .unwrap()
+                                .get(frame as usize)
+                                .unwrap();
+
+                            let (horizontal, vertical) = corner.sides_of_corner();
+                            let horizontal = self.get_side_info(horizontal);
+                            let vertical = self.get_side_info(vertical);
+
+                            imageops::overlay(
+                                &mut frame_image,
+                                *corner_img,
+                                horizontal.start as i64,
+                                vertical.start as i64,
+                            );
+                        }
+                        icon_state_images.push(frame_image);
                     }
-                    icon_state_images.push(frame_image);
                 }
-            }
-            assembled.insert(adjacency, icon_state_images);
-        }
-        assembled
+                Ok((adjacency, icon_state_images))
+            })
+            .collect()
     }
 
     /// Generates debug outputs for bitmask slice
+    /// # Errors
+    /// Errors if the assembled corners image is too large to allocate
     /// # Panics
     /// Shouldn't panic, unless the passed in corners are malformed
-    #[must_use]
-    pub fn generate_debug_icons(&self, corners: &CornerPayload) -> Vec<NamedIcon> {
+    pub fn generate_debug_icons(&self, corners: &CornerPayload) -> ProcessorResult<Vec<NamedIcon>> {
         let mut out = vec![];
-        let mut corners_image =
-            DynamicImage::new_rgba8(corners.len() as u32 * self.icon_size.x, self.icon_size.y);
+        let (corners_width, corners_height) =
+            grid_size(corners.len() as u64, 1, self.icon_size.x, self.icon_size.y)?;
+        let mut corners_image = safe_new_image(corners_width, corners_height)?;
 
         for (corner_type, map) in corners.iter() {
             let position = self.positions.get(corner_type).unwrap();
@@ -379,7 +1041,68 @@ impl BitmaskSlice {
             "ASSEMBLED-CORNERS",
             OutputImage::Png(corners_image),
         ));
-        out
+        out.extend(self.generate_corner_type_sheets(corners)?);
+
+        let blank = blank_corners(corners);
+        if !blank.is_empty() {
+            let mut report = vec!["These corners are fully transparent on their first frame, \
+                                   check positions:"
+                .to_string()];
+            for (corner_type, corner) in &blank {
+                warn!(?corner_type, ?corner, "Corner is fully transparent");
+                report.push(format!("{corner_type} {corner:?}"));
+            }
+            out.push(NamedIcon::new(
+                "DEBUGOUT",
+                "BLANK-CORNERS",
+                OutputImage::Text(report.join("\n")),
+            ));
+        }
+        Ok(out)
+    }
+
+    /// Lays out every corner of each corner type on its own sheet, for
+    /// reviewing all the corners of a type together.
+    ///
+    /// Each corner gets an icon sized cell (at its position within the icon),
+    /// one column per corner and one row per frame.
+    /// # Errors
+    /// Errors if the sheet would be too large to allocate
+    pub fn generate_corner_type_sheets(
+        &self,
+        corners: &CornerPayload,
+    ) -> ProcessorResult<Vec<NamedIcon>> {
+        let mut out = vec![];
+        for (corner_type, map) in corners.iter() {
+            let num_frames = map.values().map(Vec::len).max().unwrap_or(0);
+            let (width, height) = grid_size(
+                map.len() as u64,
+                num_frames as u64,
+                self.icon_size.x,
+                self.icon_size.y,
+            )?;
+            let mut sheet = safe_new_image(width, height)?;
+
+            for (column, (corner, frames)) in map.iter().enumerate() {
+                let (horizontal, vertical) = corner.sides_of_corner();
+                let horizontal = self.get_side_info(horizontal);
+                let vertical = self.get_side_info(vertical);
+                for (row, frame) in frames.iter().enumerate() {
+                    imageops::replace(
+                        &mut sheet,
+                        frame,
+                        (column as u32 * self.icon_size.x + horizontal.start) as i64,
+                        (row as u32 * self.icon_size.y + vertical.start) as i64,
+                    );
+                }
+            }
+            out.push(NamedIcon::new(
+                "DEBUGOUT/CORNER-TYPES",
+                &format!("{corner_type:?}"),
+                OutputImage::Png(sheet),
+            ));
+        }
+        Ok(out)
     }
 
     #[must_use]
@@ -412,3 +1135,772 @@ impl BitmaskSlice {
         }
     }
 }
+
+/// Every corner whose first frame is fully transparent, which almost always
+/// means its `positions` column is wrong
+#[must_use]
+pub fn blank_corners(corners: &CornerPayload) -> Vec<(CornerType, Corner)> {
+    corners
+        .iter()
+        .flat_map(|(corner_type, map)| {
+            map.iter()
+                .filter(|(_, frames)| frames.first().is_some_and(is_blank))
+                .map(move |(corner, _)| (corner_type, corner))
+        })
+        .collect()
+}
+
+/// The adjacency for a signature key under `field`. Signatures with a corner
+/// missing one of its sides are never generated, so anything keyed by one goes
+/// unused, but as such keys have always been accepted they only warn
+fn signature_key(field: &str, bits: u8) -> Adjacency {
+    Adjacency::try_from_bits(bits).unwrap_or_else(|err| {
+        warn!("{field}.{bits}: {err}");
+        Adjacency::from_bits_truncate(bits)
+    })
+}
+
+/// Turns the first state of a dmi back in to a sheet that can be cut, with
+/// each frame below the last. Only the first direction is used.
+/// # Errors
+/// Errors if the dmi has no states, or the sheet is too large to allocate
+pub fn dmi_to_sheet(icon: &Icon) -> ProcessorResult<DynamicImage> {
+    let Some(state) = icon.states.first() else {
+        return Err(ProcessorError::FormatError(
+            "Can't cut a dmi with no icon states".to_string(),
+        ));
+    };
+    let frames = state.frames.max(1);
+    let (width, height) = grid_size(1, u64::from(frames), icon.width, icon.height)?;
+    let mut sheet = safe_new_image(width, height)?;
+    // images are stored frame major, so every `dirs`th image is a new frame
+    let first_dir = state.images.iter().step_by(usize::from(state.dirs.max(1)));
+    for (frame, image) in (0..frames).zip(first_dir) {
+        imageops::replace(&mut sheet, image, 0, i64::from(icon.height * frame));
+    }
+    Ok(sheet)
+}
+
+/// Removes trailing frames that are blank in every assembled icon, returning
+/// the new frame count. Always keeps at least one frame.
+pub fn trim_trailing_blank_frames(
+    assembled: &mut BTreeMap<Adjacency, Vec<DynamicImage>>,
+    num_frames: u32,
+) -> u32 {
+    let mut trimmed_frames = num_frames;
+    while trimmed_frames > 1
+        && assembled
+            .values()
+            .all(|frames| frames.get(trimmed_frames as usize - 1).is_none_or(is_blank))
+    {
+        trimmed_frames -= 1;
+    }
+    for frames in assembled.values_mut() {
+        frames.truncate(trimmed_frames as usize);
+    }
+    trimmed_frames
+}
+
+#[cfg(test)]
+mod tests {
+    use image::Rgba;
+
+    use super::*;
+    use crate::config::blocks::cutters::DirectionalDelays;
+    use crate::generation::rect::draw_rect;
+    use crate::util::color::Color;
+    use crate::util::icon_ops::blank_states;
+
+    /// Builds a sheet with `columns` blocks, where every frame is filled with
+    /// a different color
+    fn test_sheet(columns: u32, frames: u32) -> DynamicImage {
+        let mut sheet = DynamicImage::new_rgba8(columns * 32, frames * 32);
+        for frame in 0..frames {
+            let color = Color::new(255, (frame * 40) as u8, 0, 255);
+            draw_rect(&mut sheet, 0, frame * 32, columns * 32, 32, 0, color);
+        }
+        sheet
+    }
+
+    fn output_icon(payload: ProcessorPayload) -> Icon {
+        let ProcessorPayload::Single(output) = payload else {
+            panic!("Expected a single output");
+        };
+        let OutputImage::Dmi(icon) = *output else {
+            panic!("Expected a dmi output");
+        };
+        icon
+    }
+
+    #[test]
+    fn per_direction_delays() {
+        let mut per_direction = Map::new();
+        per_direction.insert(Side::North, vec![2.0, 3.0]);
+        let mut config = BitmaskSlice {
+            produce_dirs: true,
+            animation: Some(Animation {
+                delays: vec![1.0],
+                per_direction_delays: Some(DirectionalDelays(per_direction)),
+            }),
+            ..Default::default()
+        };
+
+        let input = InputIcon::DynamicImage(test_sheet(4, 2));
+        // differing delays would silently rename every state without split_dirs
+        assert!(matches!(
+            config.do_operation(&input, OperationMode::Standard),
+            Err(ProcessorError::InvalidConfig(message)) if message.contains("split_dirs")
+        ));
+
+        config.split_dirs = true;
+        let icon = output_icon(
+            config
+                .do_operation(&input, OperationMode::Standard)
+                .unwrap(),
+        );
+
+        let state = |name: &str| icon.states.iter().find(|s| s.name == name).unwrap();
+
+        let north = Side::North.byond_dir();
+        let south = Side::South.byond_dir();
+        assert_eq!(state(&format!("0-{north}")).delay, Some(vec![2.0, 3.0]));
+        assert_eq!(state(&format!("0-{south}")).delay, Some(vec![1.0, 1.0]));
+        assert_eq!(state(&format!("0-{south}")).dirs, 1);
+    }
+
+    #[test]
+    fn trim_empty_frames() {
+        // third and fourth frames are left transparent
+        let mut sheet = DynamicImage::new_rgba8(4 * 32, 4 * 32);
+        imageops::replace(&mut sheet, &test_sheet(4, 2), 0, 0);
+        let config = BitmaskSlice {
+            trim_empty_frames: true,
+            animation: Some(Animation {
+                delays: vec![1.0, 2.0, 3.0, 4.0],
+                per_direction_delays: None,
+            }),
+            ..Default::default()
+        };
+
+        let input = InputIcon::DynamicImage(sheet);
+        let icon = output_icon(
+            config
+                .do_operation(&input, OperationMode::Standard)
+                .unwrap(),
+        );
+        for state in &icon.states {
+            assert_eq!(state.frames, 2);
+            assert_eq!(state.images.len(), 2);
+            assert_eq!(state.delay, Some(vec![1.0, 2.0]));
+        }
+    }
+
+    #[test]
+    fn dedupe_animation() {
+        // every frame is the same color, so all frames are identical
+        let mut sheet = DynamicImage::new_rgba8(4 * 32, 3 * 32);
+        draw_rect(
+            &mut sheet,
+            0,
+            0,
+            4 * 32,
+            3 * 32,
+            0,
+            Color::new(255, 0, 0, 255),
+        );
+        let animated = |dedupe_animation| {
+            BitmaskSlice {
+                dedupe_animation,
+                animation: Some(Animation {
+                    delays: vec![1.0, 2.0, 3.0],
+                    per_direction_delays: None,
+                }),
+                ..Default::default()
+            }
+        };
+        let run = |config: BitmaskSlice, sheet: DynamicImage| {
+            output_icon(
+                config
+                    .do_operation(&InputIcon::DynamicImage(sheet), OperationMode::Standard)
+                    .unwrap(),
+            )
+        };
+
+        let deduped = run(animated(true), sheet.clone());
+        for state in &deduped.states {
+            assert_eq!(state.frames, 1);
+            assert_eq!(state.delay, Some(vec![6.0]));
+        }
+
+        let untouched = run(animated(false), sheet);
+        for state in &untouched.states {
+            assert_eq!(state.frames, 3);
+            assert_eq!(state.delay, Some(vec![1.0, 2.0, 3.0]));
+        }
+
+        let single_frame = run(
+            BitmaskSlice {
+                dedupe_animation: true,
+                ..Default::default()
+            },
+            test_sheet(4, 1),
+        );
+        for state in &single_frame.states {
+            assert_eq!(state.frames, 1);
+            assert_eq!(state.delay, None);
+        }
+    }
+
+    #[test]
+    fn cuts_dmi_input() {
+        let sheet = test_sheet(4, 2);
+        let frames = (0..2)
+            .map(|frame| sheet.crop_imm(0, frame * 32, 4 * 32, 32))
+            .collect();
+        let icon = Icon {
+            width: 4 * 32,
+            height: 32,
+            states: vec![IconState {
+                frames: 2,
+                images: frames,
+                delay: Some(vec![1.0, 1.0]),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        assert_eq!(dmi_to_sheet(&icon).unwrap(), sheet);
+
+        let config = BitmaskSlice::default();
+        let from_dmi = config
+            .do_operation(&InputIcon::Dmi(icon), OperationMode::Standard)
+            .unwrap();
+        let from_png = config
+            .do_operation(&InputIcon::DynamicImage(sheet), OperationMode::Standard)
+            .unwrap();
+        assert_eq!(output_icon(from_dmi).states, output_icon(from_png).states);
+    }
+
+    #[test]
+    fn cuts_dmi_corner_states() {
+        let sheet = test_sheet(4, 2);
+        let config = BitmaskSlice::default();
+        let states = CornerType::cardinal()
+            .into_iter()
+            .map(|corner_type| {
+                let column = config.positions.get(corner_type).unwrap();
+                IconState {
+                    name: corner_type.to_string(),
+                    frames: 2,
+                    images: (0..2)
+                        .map(|frame| sheet.crop_imm(column * 32, frame * 32, 32, 32))
+                        .collect(),
+                    delay: Some(vec![1.0, 1.0]),
+                    ..Default::default()
+                }
+            })
+            .rev()
+            .collect();
+        let icon = Icon {
+            width: 32,
+            height: 32,
+            states,
+            ..Default::default()
+        };
+
+        let from_dmi = config
+            .do_operation(&InputIcon::Dmi(icon), OperationMode::Standard)
+            .unwrap();
+        let from_png = config
+            .do_operation(&InputIcon::DynamicImage(sheet), OperationMode::Standard)
+            .unwrap();
+        assert_eq!(output_icon(from_dmi).states, output_icon(from_png).states);
+    }
+
+    #[test]
+    fn reports_blank_corners() {
+        let mut positions = Positions::default();
+        // column 4 is left empty
+        positions.0.insert(CornerType::Vertical, 4);
+        let config = BitmaskSlice {
+            positions,
+            ..Default::default()
+        };
+        let mut sheet = DynamicImage::new_rgba8(5 * 32, 32);
+        imageops::replace(&mut sheet, &test_sheet(4, 1), 0, 0);
+
+        let (corners, _prefabs) = config.generate_corners(&sheet).unwrap();
+        let blank = blank_corners(&corners);
+        assert_eq!(blank.len(), all::<Corner>().count());
+        assert!(blank
+            .iter()
+            .all(|(corner_type, _)| *corner_type == CornerType::Vertical));
+
+        let debug = config.generate_debug_icons(&corners).unwrap();
+        assert!(debug
+            .iter()
+            .any(|icon| icon.name_hint.as_deref() == Some("BLANK-CORNERS")));
+    }
+
+    #[test]
+    fn side_spacing_step() {
+        let spacing = SideSpacing { start: 8, end: 32 };
+        assert_eq!(spacing.step(), Some(24));
+
+        let backwards = SideSpacing { start: 40, end: 32 };
+        assert_eq!(backwards.step(), None);
+        assert!(matches!(
+            backwards.checked_step(Side::East),
+            Err(ProcessorError::InvalidConfig(message)) if message.starts_with("east side")
+        ));
+    }
+
+    #[test]
+    fn split_dirs() {
+        let config = BitmaskSlice {
+            produce_dirs: true,
+            split_dirs: true,
+            animation: Some(Animation {
+                delays: vec![1.0, 2.0],
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+
+        let icon = output_icon(
+            config
+                .do_operation(
+                    &InputIcon::DynamicImage(test_sheet(4, 2)),
+                    OperationMode::Standard,
+                )
+                .unwrap(),
+        );
+        assert_eq!(icon.states.len(), SIZE_OF_CARDINALS * 4);
+        for side in Side::dmi_cardinals() {
+            let name = format!("5-{}", side.byond_dir());
+            let state = icon.states.iter().find(|state| state.name == name).unwrap();
+            assert_eq!(state.dirs, 1);
+            assert_eq!(state.delay, Some(vec![1.0, 2.0]));
+        }
+    }
+
+    #[test]
+    fn sample_state() {
+        let config = BitmaskSlice {
+            sample_state: true,
+            ..Default::default()
+        };
+        let icon = output_icon(
+            config
+                .do_operation(
+                    &InputIcon::DynamicImage(test_sheet(4, 1)),
+                    OperationMode::Standard,
+                )
+                .unwrap(),
+        );
+        let preview = icon.states.last().unwrap();
+        assert_eq!(preview.name, "preview");
+        let image = &preview.images[0];
+        assert_eq!(image.dimensions(), (32, 32));
+        // a solid input means every filled cell of the patch is solid
+        for (y, row) in (0..).zip(SAMPLE_LAYOUT) {
+            for (x, filled) in (0..).zip(row) {
+                let center = image.get_pixel(x * 10 + 5, y * 10 + 5);
+                assert_eq!(center.0[3] == 255, filled, "cell {x},{y}");
+            }
+        }
+
+        assert_eq!(config.sample_signature(1, 0), Adjacency::E_W | Adjacency::S);
+        assert_eq!(config.sample_signature(1, 2), Adjacency::N | Adjacency::E);
+        assert_eq!(config.sample_signature(2, 2), Adjacency::W);
+    }
+
+    #[test]
+    fn preview_mode() {
+        let config = BitmaskSlice {
+            produce_dirs: true,
+            ..Default::default()
+        };
+        let input = InputIcon::DynamicImage(test_sheet(4, 1));
+        let preview = output_icon(config.do_operation(&input, OperationMode::Preview).unwrap());
+        let standard = output_icon(
+            config
+                .do_operation(&input, OperationMode::Standard)
+                .unwrap(),
+        );
+
+        assert_eq!(preview.states.len(), config.preview_signatures().len());
+        for state in &preview.states {
+            assert_eq!(state.dirs, 1);
+            let full = standard
+                .states
+                .iter()
+                .find(|s| s.name == state.name)
+                .unwrap();
+            // the south facing dir comes first
+            assert_eq!(state.images[0], full.images[0]);
+        }
+    }
+
+    #[test]
+    fn state_name_format() {
+        let config = BitmaskSlice {
+            output_name: Some("wall".to_string()),
+            state_name_format: Some("{prefix}_{signature}".to_string()),
+            ..Default::default()
+        };
+        let east = Adjacency::E;
+        assert_eq!(config.state_name(east), "wall_4");
+        assert_eq!(config.dir_state_name(east, Side::North), "wall_4-1");
+
+        let with_dir = BitmaskSlice {
+            state_name_format: Some("smooth-{signature}-{dir}".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(with_dir.dir_state_name(east, Side::West), "smooth-4-8");
+
+        let unset = BitmaskSlice {
+            output_name: Some("wall".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(unset.state_name(east), "wall-4");
+        assert_eq!(unset.dir_state_name(east, Side::South), "wall-4-2");
+
+        let missing_signature = BitmaskSlice {
+            state_name_format: Some("wall".to_string()),
+            ..Default::default()
+        };
+        assert!(matches!(
+            missing_signature.verify_config(),
+            Err(ProcessorError::InvalidConfig(_))
+        ));
+    }
+
+    #[test]
+    fn corner_type_sheets() {
+        let config = BitmaskSlice::default();
+        let (corners, _prefabs) = config.generate_corners(&test_sheet(4, 2)).unwrap();
+
+        let sheets = config.generate_corner_type_sheets(&corners).unwrap();
+
+        assert_eq!(sheets.len(), corners.len());
+        for ((corner_type, _), sheet) in corners.iter().zip(&sheets) {
+            assert_eq!(sheet.name_hint, Some(format!("{corner_type:?}")));
+            let OutputImage::Png(image) = &sheet.image else {
+                panic!("Expected a png output");
+            };
+            assert_eq!(
+                image.dimensions(),
+                (all::<Corner>().count() as u32 * 32, 2 * 32)
+            );
+        }
+    }
+
+    #[test]
+    fn parallel_matches_serial() {
+        let mut positions = Positions::default();
+        positions.0.insert(CornerType::Flat, 4);
+        let config = BitmaskSlice {
+            produce_dirs: true,
+            smooth_diagonally: true,
+            positions,
+            animation: Some(Animation {
+                delays: vec![1.0, 2.0],
+                per_direction_delays: None,
+            }),
+            ..Default::default()
+        };
+        let input = InputIcon::DynamicImage(test_sheet(5, 2));
+
+        let run_with_threads = |threads| {
+            let pool = rayon::ThreadPoolBuilder::new()
+                .num_threads(threads)
+                .build()
+                .unwrap();
+            pool.install(|| {
+                output_icon(
+                    config
+                        .do_operation(&input, OperationMode::Standard)
+                        .unwrap(),
+                )
+            })
+        };
+
+        let serial = run_with_threads(1);
+        let parallel = run_with_threads(4);
+        assert_eq!(serial.states, parallel.states);
+    }
+
+    #[test]
+    fn prefab_overlays() {
+        // column 4 is the overlay, a single blue pixel in the corner
+        let mut sheet = DynamicImage::new_rgba8(5 * 32, 32);
+        imageops::replace(&mut sheet, &test_sheet(4, 1), 0, 0);
+        draw_rect(&mut sheet, 4 * 32, 0, 1, 1, 0, Color::new(0, 0, 255, 255));
+        let mut overlays = BTreeMap::new();
+        overlays.insert(0, vec![4]);
+        let config = BitmaskSlice {
+            prefab_overlays: Some(PrefabOverlays(overlays)),
+            ..Default::default()
+        };
+
+        let icon = output_icon(
+            config
+                .do_operation(&InputIcon::DynamicImage(sheet), OperationMode::Standard)
+                .unwrap(),
+        );
+        let corner_pixel = |name: &str| {
+            icon.states.iter().find(|s| s.name == name).unwrap().images[0].get_pixel(0, 0)
+        };
+        assert_eq!(corner_pixel("0"), Rgba([0, 0, 255, 255]));
+        assert_eq!(corner_pixel("1"), Rgba([255, 0, 0, 255]));
+
+        let mut overlays = BTreeMap::new();
+        overlays.insert(0, vec![5]);
+        let out_of_range = BitmaskSlice {
+            prefab_overlays: Some(PrefabOverlays(overlays)),
+            ..Default::default()
+        };
+        assert!(matches!(
+            out_of_range.do_operation(&InputIcon::DynamicImage(test_sheet(5, 1)), OperationMode::Standard),
+            Err(ProcessorError::InvalidConfig(message)) if message.contains("prefab_overlays.0")
+        ));
+    }
+
+    #[test]
+    fn scale_filter() {
+        let mut sheet = DynamicImage::new_rgba8(4 * 16, 16);
+        draw_rect(&mut sheet, 0, 0, 4 * 16, 16, 0, Color::new(255, 0, 0, 255));
+        let config = BitmaskSlice {
+            icon_size: IconSize { x: 16, y: 16 },
+            cut_pos: CutPosition { x: 8, y: 8 },
+            output_icon_size: OutputIconSize { x: 32, y: 32 },
+            scale_filter: Some(ScaleFilter::Nearest),
+            ..Default::default()
+        };
+
+        let icon = output_icon(
+            config
+                .do_operation(&InputIcon::DynamicImage(sheet), OperationMode::Standard)
+                .unwrap(),
+        );
+        assert_eq!((icon.width, icon.height), (32, 32));
+        for state in &icon.states {
+            assert_eq!(state.images[0].dimensions(), (32, 32));
+            assert!(state.images[0]
+                .pixels()
+                .all(|(_, _, pixel)| pixel == Rgba([255, 0, 0, 255])));
+        }
+    }
+
+    #[test]
+    fn empty_columns_are_blank() {
+        let mut positions = Map::new();
+        positions.insert(CornerType::Convex, 4);
+        positions.insert(CornerType::Concave, 5);
+        positions.insert(CornerType::Horizontal, 2);
+        positions.insert(CornerType::Vertical, 3);
+        let config = BitmaskSlice {
+            positions: Positions(positions),
+            ..Default::default()
+        };
+
+        // only the first four columns have anything drawn in them
+        let mut sheet = DynamicImage::new_rgba8(6 * 32, 32);
+        imageops::replace(&mut sheet, &test_sheet(4, 1), 0, 0);
+        let input = InputIcon::DynamicImage(sheet);
+        let icon = output_icon(
+            config
+                .do_operation(&input, OperationMode::Standard)
+                .unwrap(),
+        );
+
+        // the lone state is all convex corners, which are cut from an empty column,
+        // while north + south is all vertical corners, which aren't
+        let blank = blank_states(&icon);
+        assert!(blank.contains(&"0".to_string()));
+        assert!(!blank.contains(&"3".to_string()));
+    }
+
+    #[test]
+    fn default_matches_serde() {
+        // only the keys without a serde default
+        let config: BitmaskSlice = toml::from_str(
+            r"
+            produce_dirs = false
+            smooth_diagonally = false
+            icon_size = { x = 32, y = 32 }
+            output_icon_pos = { x = 0, y = 0 }
+            output_icon_size = { x = 32, y = 32 }
+            positions = { convex = 0, concave = 1, horizontal = 2, vertical = 3 }
+            cut_pos = { x = 16, y = 16 }
+            ",
+        )
+        .unwrap();
+        let expected = BitmaskSlice {
+            icon_size: config.icon_size,
+            output_icon_pos: config.output_icon_pos,
+            output_icon_size: config.output_icon_size,
+            positions: config.positions.clone(),
+            cut_pos: config.cut_pos,
+            ..Default::default()
+        };
+        assert_eq!(config, expected);
+        assert!(config.dedupe_animation);
+    }
+
+    #[test]
+    fn state_docs() {
+        let config: BitmaskSlice = toml::from_str(
+            r#"
+            produce_dirs = false
+            smooth_diagonally = false
+            icon_size = { x = 32, y = 32 }
+            output_icon_pos = { x = 0, y = 0 }
+            output_icon_size = { x = 32, y = 32 }
+            positions = { convex = 0, concave = 1, horizontal = 2, vertical = 3 }
+            cut_pos = { x = 16, y = 16 }
+            exclude_signatures = [15]
+
+            [state_docs]
+            0 = "Alone, no neighbours"
+            3 = "North and south, a vertical run"
+            15 = "Fully surrounded"
+            "#,
+        )
+        .unwrap();
+
+        let input = InputIcon::DynamicImage(test_sheet(4, 1));
+        let payload = config
+            .do_operation(&input, OperationMode::Standard)
+            .unwrap();
+        let ProcessorPayload::MultipleNamed(outputs) = payload else {
+            panic!("Expected the dmi and docs outputs");
+        };
+        let OutputImage::Text(docs) = &outputs[1].image else {
+            panic!("Expected a text output");
+        };
+
+        // excluded states aren't generated, so aren't documented either
+        assert_eq!(
+            docs,
+            "0: Alone, no neighbours\n3: North and south, a vertical run\n"
+        );
+    }
+
+    #[test]
+    fn invalid_configs() {
+        let is_invalid = |config: BitmaskSlice, field: &str| {
+            let input = InputIcon::DynamicImage(test_sheet(4, 1));
+            match config.do_operation(&input, OperationMode::Standard) {
+                Err(ProcessorError::InvalidConfig(message)) => {
+                    assert!(message.contains(field), "{message} should mention {field}");
+                }
+                _ => panic!("Expected {field} to be invalid"),
+            }
+        };
+
+        is_invalid(
+            BitmaskSlice {
+                cut_pos: CutPosition { x: 32, y: 16 },
+                ..Default::default()
+            },
+            "cut_pos.x",
+        );
+        is_invalid(
+            BitmaskSlice {
+                output_icon_size: OutputIconSize { x: 0, y: 32 },
+                ..Default::default()
+            },
+            "output_icon_size",
+        );
+        is_invalid(
+            BitmaskSlice {
+                smooth_diagonally: true,
+                ..Default::default()
+            },
+            "flat",
+        );
+        let mut positions = Positions::default();
+        positions.0.insert(CornerType::Vertical, 7);
+        is_invalid(
+            BitmaskSlice {
+                positions,
+                ..Default::default()
+            },
+            "positions.vertical",
+        );
+        let mut prefabs = BTreeMap::new();
+        prefabs.insert(15, 4);
+        is_invalid(
+            BitmaskSlice {
+                prefabs: Some(Prefabs(prefabs)),
+                ..Default::default()
+            },
+            "prefabs.15",
+        );
+
+        // signatures that are never generated have always been allowed, and only warn
+        let mut prefabs = BTreeMap::new();
+        prefabs.insert(Adjacency::NE.bits(), 0);
+        let config = BitmaskSlice {
+            prefabs: Some(Prefabs(prefabs)),
+            ..Default::default()
+        };
+        assert!(config
+            .do_operation(
+                &InputIcon::DynamicImage(test_sheet(4, 1)),
+                OperationMode::Standard
+            )
+            .is_ok());
+    }
+
+    #[test]
+    fn prefab_pos() {
+        let mut prefabs = BTreeMap::new();
+        prefabs.insert(15, 4);
+        let mut config = BitmaskSlice {
+            output_icon_size: OutputIconSize { x: 48, y: 48 },
+            output_icon_pos: OutputIconPosition { x: 8, y: 8 },
+            prefabs: Some(Prefabs(prefabs)),
+            ..Default::default()
+        };
+        let input = InputIcon::DynamicImage(test_sheet(5, 1));
+        let prefab_frame = |config: &BitmaskSlice| {
+            let icon = output_icon(
+                config
+                    .do_operation(&input, OperationMode::Standard)
+                    .unwrap(),
+            );
+            let state = icon.states.iter().find(|state| state.name == "15").unwrap();
+            state.images[0].clone()
+        };
+
+        let anchored = prefab_frame(&config);
+        assert_eq!(anchored.get_pixel(0, 0).0[3], 0);
+        assert_eq!(anchored.get_pixel(39, 39).0[3], 255);
+
+        config.prefab_pos = Some(OutputIconPosition { x: 0, y: 0 });
+        let moved = prefab_frame(&config);
+        assert_eq!(moved.get_pixel(0, 0).0[3], 255);
+        assert_eq!(moved.get_pixel(39, 39).0[3], 0);
+    }
+
+    #[test]
+    fn excluded_signatures() {
+        let config = BitmaskSlice {
+            exclude_signatures: Some(vec![0]),
+            ..Default::default()
+        };
+
+        let input = InputIcon::DynamicImage(test_sheet(4, 1));
+        let icon = output_icon(
+            config
+                .do_operation(&input, OperationMode::Standard)
+                .unwrap(),
+        );
+
+        assert!(!icon.states.iter().any(|state| state.name == "0"));
+        assert!(icon.states.iter().any(|state| state.name == "1"));
+        assert_eq!(icon.states.len(), SIZE_OF_CARDINALS - 1);
+    }
+}
@@ -2,7 +2,7 @@ use std::collections::BTreeMap;
 
 use dmi::icon::{Icon, IconState};
 use fixed_map::Map;
-use image::{DynamicImage, GenericImageView};
+use image::DynamicImage;
 use serde::{Deserialize, Serialize};
 
 use crate::config::blocks::cutters::{
@@ -13,7 +13,9 @@ use crate::config::blocks::cutters::{
     OutputIconSize,
     Positions,
 };
+use crate::config::blocks::filters::{apply_filters, FilterStep};
 use crate::operations::cutters::bitmask_slice::{BitmaskSlice, SIZE_OF_DIAGONALS};
+use crate::operations::cutters::frame_count;
 use crate::operations::error::{ProcessorError, ProcessorResult};
 use crate::operations::{IconOperationConfig, InputIcon, OperationMode, ProcessorPayload};
 use crate::util::adjacency::Adjacency;
@@ -29,6 +31,10 @@ pub struct BitmaskWindows {
     #[serde(skip_serializing_if = "Option::is_none")]
     #[serde(default)]
     pub animation: Option<Animation>,
+    /// Filters applied in order to every frame after assembly
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub post_process: Option<Vec<FilterStep>>,
 }
 
 impl IconOperationConfig for BitmaskWindows {
@@ -44,8 +50,7 @@ impl IconOperationConfig for BitmaskWindows {
             ));
         };
 
-        let (_in_x, in_y) = img.dimensions();
-        let num_frames = in_y / self.icon_size.y;
+        let num_frames = frame_count(img, self.icon_size)?;
 
         let mut positions = Positions::default();
         positions.0.insert(CornerType::Flat, 4);
@@ -54,6 +59,7 @@ impl IconOperationConfig for BitmaskWindows {
             output_name: None,
             icon_size: self.icon_size,
             output_icon_pos: self.output_icon_pos,
+            prefab_pos: None,
             output_icon_size: OutputIconSize {
                 x: self.icon_size.x,
                 y: self.icon_size.y,
@@ -65,15 +71,25 @@ impl IconOperationConfig for BitmaskWindows {
             },
             animation: self.animation.clone(),
             produce_dirs: false,
+            split_dirs: false,
             prefabs: None,
             prefab_overlays: None,
             smooth_diagonally: true,
             map_icon: None,
+            exclude_signatures: None,
+            post_process: None,
+            state_docs: None,
+            dmi_version: None,
+            trim_empty_frames: false,
+            dedupe_animation: true,
+            scale_filter: None,
+            state_name_format: None,
+            sample_state: false,
         };
 
         let (corners, prefabs) = bitmask_config.generate_corners(img)?;
         let assembled =
-            bitmask_config.generate_icons(&corners, &prefabs, num_frames, SIZE_OF_DIAGONALS);
+            bitmask_config.generate_icons(&corners, &prefabs, num_frames, SIZE_OF_DIAGONALS)?;
 
         let mut alt_config = bitmask_config;
 
@@ -88,7 +104,7 @@ impl IconOperationConfig for BitmaskWindows {
 
         let (corners_alt, prefabs_alt) = alt_config.generate_corners(img)?;
         let assembled_alt =
-            alt_config.generate_icons(&corners_alt, &prefabs_alt, num_frames, SIZE_OF_DIAGONALS);
+            alt_config.generate_icons(&corners_alt, &prefabs_alt, num_frames, SIZE_OF_DIAGONALS)?;
 
         let delay = self
             .animation
@@ -149,6 +165,10 @@ impl IconOperationConfig for BitmaskWindows {
             states_from_assembled("alt-", &assembled_alt);
         }
 
+        if let Some(post_process) = &self.post_process {
+            apply_filters(post_process, &mut states);
+        }
+
         let icon = Icon {
             width: self.output_icon_size.x,
             height: self.output_icon_size.y,
@@ -1,3 +1,49 @@
+use image::{DynamicImage, GenericImageView};
+
+use crate::config::blocks::cutters::IconSize;
+use crate::operations::error::{ProcessorError, ProcessorResult};
+
 pub mod bitmask_dir_visibility;
 pub mod bitmask_slice;
 pub mod bitmask_windows;
+
+/// Number of frames in a cutter input, where each frame is a row of
+/// `icon_size` icons.
+/// # Errors
+/// Errors if the input isn't an exact multiple of `icon_size`, rather than
+/// silently dropping the leftover strip
+pub fn frame_count(img: &DynamicImage, icon_size: IconSize) -> ProcessorResult<u32> {
+    let (width, height) = img.dimensions();
+    if width.checked_rem(icon_size.x) != Some(0) || height.checked_rem(icon_size.y) != Some(0) {
+        return Err(ProcessorError::ImageDimensionMismatch {
+            width,
+            height,
+            icon_width: icon_size.x,
+            icon_height: icon_size.y,
+        });
+    }
+    Ok(height / icon_size.y)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frame_count_needs_whole_icons() {
+        let icon_size = IconSize { x: 32, y: 32 };
+        assert_eq!(
+            frame_count(&DynamicImage::new_rgba8(160, 96), icon_size).unwrap(),
+            3
+        );
+        for (width, height) in [(160, 97), (161, 96), (160, 31)] {
+            assert!(matches!(
+                frame_count(&DynamicImage::new_rgba8(width, height), icon_size),
+                Err(ProcessorError::ImageDimensionMismatch { width: w, height: h, .. })
+                    if (w, h) == (width, height)
+            ));
+        }
+        let zero = IconSize { x: 0, y: 32 };
+        assert!(frame_count(&DynamicImage::new_rgba8(160, 96), zero).is_err());
+    }
+}
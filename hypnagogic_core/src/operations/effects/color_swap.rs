@@ -0,0 +1,292 @@
+use std::collections::BTreeMap;
+
+use dmi::icon::{Icon, IconState};
+use image::DynamicImage;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use tracing::debug;
+
+use crate::config::blocks::filters::FilterStep;
+use crate::operations::error::ProcessorResult;
+use crate::operations::{
+    IconOperationConfig,
+    InputIcon,
+    NamedIcon,
+    OperationMode,
+    OutputImage,
+    ProcessorPayload,
+};
+use crate::util::color::Color;
+use crate::util::icon_ops::{audit_palette_coverage, swap_colors, PaletteCoverage};
+
+/// Replaces colors across a whole image or dmi, for things like team color
+/// variants of a sheet before it gets cut.
+///
+/// Raw images are written out next to the input with a `-swapped` suffix, so
+/// the source image is never overwritten.
+#[derive(Clone, PartialEq, Debug, Default, Serialize, Deserialize)]
+pub struct ColorSwap {
+    /// Map of source color to the color it's replaced with
+    pub colors: ColorMap,
+    /// Filters applied in order after swapping, eg `QuantizeToPalette` to limit
+    /// the output to a fixed palette
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub post_process: Option<Vec<FilterStep>>,
+    /// Errors if the input has visible colors that none of `colors` swap.
+    /// Source colors that match no pixels are warned about either way.
+    #[serde(default)]
+    pub strict: bool,
+}
+
+/// Color to color mapping, keyed by hex strings or color names in configs
+#[derive(Clone, Eq, PartialEq, Debug, Default)]
+pub struct ColorMap(pub Vec<(Color, Color)>);
+
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(transparent)]
+struct ColorMapHelper {
+    map: BTreeMap<String, Color>,
+}
+
+impl Serialize for ColorMap {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut map = BTreeMap::new();
+
+        for (k, v) in &self.0 {
+            map.insert(k.to_hex_str(), *v);
+        }
+
+        ColorMapHelper { map }.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for ColorMap {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let ColorMapHelper { map } = Deserialize::deserialize(deserializer)?;
+        map.into_iter()
+            .map(|(k, v)| {
+                k.parse::<Color>()
+                    .map(|from| (from, v))
+                    .map_err(serde::de::Error::custom)
+            })
+            .collect::<Result<_, _>>()
+            .map(ColorMap)
+    }
+}
+
+impl ColorSwap {
+    /// Checks the source colors against every image of the input at once, so a
+    /// color only has to show up in one frame to count as used
+    fn audit<'a>(&self, images: impl IntoIterator<Item = &'a DynamicImage>) -> PaletteCoverage {
+        let sources: Vec<Color> = self.colors.0.iter().map(|(from, _)| *from).collect();
+        let mut coverage = PaletteCoverage {
+            unused: sources.clone(),
+            uncovered: vec![],
+        };
+        for image in images {
+            let audit = audit_palette_coverage(image, &sources);
+            coverage.unused.retain(|color| audit.unused.contains(color));
+            for color in audit.uncovered {
+                if !coverage.uncovered.contains(&color) {
+                    coverage.uncovered.push(color);
+                }
+            }
+        }
+        coverage
+    }
+
+    fn apply(&self, image: &mut DynamicImage) {
+        swap_colors(image, &self.colors.0);
+        for step in self.post_process.iter().flatten() {
+            step.apply(image);
+        }
+    }
+}
+
+impl IconOperationConfig for ColorSwap {
+    #[tracing::instrument(skip(input))]
+    fn perform_operation(
+        &self,
+        input: &InputIcon,
+        mode: OperationMode,
+    ) -> ProcessorResult<ProcessorPayload> {
+        debug!("Starting color swap icon op");
+        match input {
+            InputIcon::DynamicImage(img) => {
+                self.audit([img]).verify(self.strict)?;
+                let mut img = img.clone();
+                self.apply(&mut img);
+                Ok(ProcessorPayload::SingleNamed(Box::new(NamedIcon {
+                    path_hint: None,
+                    name_hint: Some("swapped".to_string()),
+                    image: OutputImage::Png(img),
+                })))
+            }
+            InputIcon::Dmi(icon) => {
+                self.audit(icon.states.iter().flat_map(|state| &state.images))
+                    .verify(self.strict)?;
+                let states = icon
+                    .states
+                    .iter()
+                    .map(|state| {
+                        let mut images = state.images.clone();
+                        for image in &mut images {
+                            self.apply(image);
+                        }
+                        IconState {
+                            images,
+                            ..state.clone()
+                        }
+                    })
+                    .collect();
+                Ok(ProcessorPayload::from_icon(Icon {
+                    states,
+                    ..icon.clone()
+                }))
+            }
+        }
+    }
+
+    fn verify_config(&self) -> ProcessorResult<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use image::{GenericImageView, Rgba, RgbaImage};
+
+    use super::*;
+    use crate::config::read_config;
+    use crate::config::template_resolver::NullResolver;
+    use crate::operations::error::ProcessorError;
+    use crate::operations::IconOperation;
+
+    #[test]
+    fn swaps_colors_at_once() {
+        let config = read_config(
+            &mut Cursor::new(
+                r##"
+            mode = "ColorSwap"
+            [colors]
+            "#FF0000" = "#0000FF"
+            "#0000FF" = "#FF0000"
+            "##,
+            ),
+            NullResolver,
+        )
+        .unwrap();
+        let IconOperation::ColorSwap(config) = config else {
+            panic!("Expected a color swap config");
+        };
+
+        let mut img = RgbaImage::from_pixel(2, 1, Rgba([255, 0, 0, 255]));
+        img.put_pixel(1, 0, Rgba([0, 0, 255, 255]));
+        let input = InputIcon::DynamicImage(DynamicImage::ImageRgba8(img));
+
+        let ProcessorPayload::SingleNamed(named) = config
+            .do_operation(&input, OperationMode::Standard)
+            .unwrap()
+        else {
+            panic!("Expected a single named output");
+        };
+        let OutputImage::Png(output) = named.image else {
+            panic!("Expected a png output");
+        };
+        assert_eq!(output.get_pixel(0, 0), Rgba([0, 0, 255, 255]));
+        assert_eq!(output.get_pixel(1, 0), Rgba([255, 0, 0, 255]));
+    }
+
+    #[test]
+    fn named_color_keys() {
+        let config: ColorSwap = toml::from_str(
+            r##"
+            [colors]
+            red = "#0000FF"
+            transparent = "white"
+            "#0f0" = "red"
+            "##,
+        )
+        .unwrap();
+        let mut colors = config.colors.0;
+        colors.sort_by_key(|(from, _)| from.to_hex_str());
+        assert_eq!(
+            colors,
+            vec![
+                (Color::new(0, 0, 0, 0), Color::new_rgb(255, 255, 255)),
+                (Color::new_rgb(0, 255, 0), Color::new_rgb(255, 0, 0)),
+                (Color::new_rgb(255, 0, 0), Color::new_rgb(0, 0, 255)),
+            ]
+        );
+        assert!(toml::from_str::<ColorSwap>("[colors]\nreddish = \"red\"").is_err());
+    }
+
+    #[test]
+    fn post_process_after_swap() {
+        let config = ColorSwap {
+            colors: ColorMap(vec![(
+                Color::new_rgb(255, 0, 0),
+                Color::new_rgb(200, 200, 200),
+            )]),
+            post_process: Some(vec![FilterStep::QuantizeToPalette {
+                palette: vec![Color::new_rgb(0, 0, 0), Color::new_rgb(255, 255, 255)],
+            }]),
+            ..Default::default()
+        };
+        let mut img = DynamicImage::ImageRgba8(RgbaImage::from_pixel(2, 1, Rgba([255, 0, 0, 255])));
+        img.as_mut_rgba8()
+            .unwrap()
+            .put_pixel(1, 0, Rgba([255, 0, 0, 100]));
+        config.apply(&mut img);
+        assert_eq!(img.get_pixel(0, 0), Rgba([255, 255, 255, 255]));
+        // swap colors only match exactly, so the translucent pixel keeps its
+        // red, and quantizes to black with its alpha intact
+        assert_eq!(img.get_pixel(1, 0), Rgba([0, 0, 0, 100]));
+    }
+
+    #[test]
+    fn strict_rejects_uncovered_colors() {
+        let red = Color::new_rgb(255, 0, 0);
+        let blue = Color::new_rgb(0, 0, 255);
+        let mut config = ColorSwap {
+            colors: ColorMap(vec![(red, blue), (Color::new_rgb(0, 255, 0), red)]),
+            ..Default::default()
+        };
+
+        // the second frame has a color no swap covers, the green swap is unused
+        let frame = |color: Color| {
+            DynamicImage::ImageRgba8(RgbaImage::from_pixel(1, 1, Rgba(color.into())))
+        };
+        let icon = Icon {
+            width: 1,
+            height: 1,
+            states: vec![IconState {
+                name: "state".to_string(),
+                frames: 2,
+                images: vec![frame(red), frame(Color::new_rgb(1, 2, 3))],
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let input = InputIcon::Dmi(icon);
+        assert!(config.do_operation(&input, OperationMode::Standard).is_ok());
+
+        config.strict = true;
+        assert!(matches!(
+            config.do_operation(&input, OperationMode::Standard),
+            Err(ProcessorError::UncoveredColors(colors)) if colors == vec!["#010203FF".to_string()]
+        ));
+        assert_eq!(
+            config.audit(&[frame(red), frame(blue)]).unused,
+            vec![Color::new_rgb(0, 255, 0)]
+        );
+    }
+}
@@ -0,0 +1,242 @@
+use dmi::icon::{Icon, IconState};
+use image::{DynamicImage, GenericImage, Rgba};
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+
+use crate::operations::error::{ProcessorError, ProcessorResult};
+use crate::operations::{IconOperationConfig, InputIcon, OperationMode, ProcessorPayload};
+use crate::util::color::Color;
+use crate::util::icon_ops::{overlay_opaque, safe_new_image};
+use crate::util::random::SeededRng;
+
+/// Takes an already cut dmi and adds procedurally cracked copies of every
+/// state, for damage states.
+///
+/// Every state gets the same crack pattern for a given damage level, so cracks
+/// stay consistent across a smoothing set.
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct DamageOverlay {
+    /// Seed for the crack pattern, the same seed always produces the same
+    /// cracks
+    #[serde(default)]
+    pub seed: u64,
+    /// How damaged the most damaged variant is, from 0.0 to 1.0
+    #[serde(default = "default_intensity")]
+    pub intensity: f32,
+    /// Number of damaged variants to produce, each more damaged than the last
+    #[serde(default = "default_levels")]
+    pub levels: u32,
+    /// Color of the cracks, alpha is respected
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub crack_color: Option<Color>,
+}
+
+/// The eight neighbours of a pixel, in order going around so turning is just
+/// +-1
+const STEPS: [(i64, i64); 8] = [
+    (0, -1),
+    (1, -1),
+    (1, 0),
+    (1, 1),
+    (0, 1),
+    (-1, 1),
+    (-1, 0),
+    (-1, -1),
+];
+
+fn default_intensity() -> f32 {
+    0.5
+}
+
+fn default_levels() -> u32 {
+    1
+}
+
+impl Default for DamageOverlay {
+    fn default() -> Self {
+        Self {
+            seed: 0,
+            intensity: default_intensity(),
+            levels: default_levels(),
+            crack_color: None,
+        }
+    }
+}
+
+impl IconOperationConfig for DamageOverlay {
+    #[tracing::instrument(skip(input))]
+    fn perform_operation(
+        &self,
+        input: &InputIcon,
+        mode: OperationMode,
+    ) -> ProcessorResult<ProcessorPayload> {
+        debug!("Starting damage overlay icon op");
+        let InputIcon::Dmi(icon) = input else {
+            return Err(ProcessorError::FormatError(
+                "This operation only accepts dmi images".to_string(),
+            ));
+        };
+
+        let mut states = icon.states.clone();
+        for level in 1..=self.levels {
+            let intensity = self.intensity * level as f32 / self.levels as f32;
+            let cracks = self.generate_cracks(icon.width, icon.height, level, intensity)?;
+            states.extend(icon.states.iter().map(|state| {
+                let images = state
+                    .images
+                    .iter()
+                    .map(|image| {
+                        let mut image = image.clone();
+                        overlay_opaque(&mut image, &cracks);
+                        image
+                    })
+                    .collect();
+                IconState {
+                    name: format!("{}-damaged-{level}", state.name),
+                    images,
+                    ..state.clone()
+                }
+            }));
+        }
+
+        Ok(ProcessorPayload::from_icon(Icon {
+            states,
+            ..icon.clone()
+        }))
+    }
+
+    fn verify_config(&self) -> ProcessorResult<()> {
+        if !(0.0..=1.0).contains(&self.intensity) {
+            return Err(ProcessorError::InvalidConfig(format!(
+                "intensity must be between 0.0 and 1.0, found {}",
+                self.intensity
+            )));
+        }
+        if self.levels == 0 {
+            return Err(ProcessorError::InvalidConfig(
+                "levels must be at least 1".to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl DamageOverlay {
+    /// Generates a transparent image with cracks drawn on it, as random walks
+    /// that wander across the icon. Higher intensity means more and longer
+    /// cracks.
+    /// # Errors
+    /// Errors if the overlay is too large to allocate
+    pub fn generate_cracks(
+        &self,
+        width: u32,
+        height: u32,
+        level: u32,
+        intensity: f32,
+    ) -> ProcessorResult<DynamicImage> {
+        let mut overlay = safe_new_image(width, height)?;
+        if width == 0 || height == 0 || intensity <= 0.0 {
+            return Ok(overlay);
+        }
+        let color = Rgba(self.crack_color.unwrap_or(Color::new(0, 0, 0, 160)).into());
+        let mut rng = SeededRng::new(self.seed.wrapping_add(u64::from(level)));
+
+        let num_cracks = (intensity * 6.0).ceil() as u32;
+        let crack_length = (intensity * (width + height) as f32 / 2.0).ceil() as u32;
+        for _ in 0..num_cracks {
+            let mut x = i64::from(rng.next_below(width));
+            let mut y = i64::from(rng.next_below(height));
+            let mut direction = rng.next_below(8) as usize;
+            for _ in 0..crack_length {
+                overlay.put_pixel(x as u32, y as u32, color);
+                if rng.chance(0.3) {
+                    direction = if rng.chance(0.5) {
+                        (direction + 1) % 8
+                    } else {
+                        (direction + 7) % 8
+                    };
+                }
+                let (step_x, step_y) = STEPS[direction];
+                x += step_x;
+                y += step_y;
+                if x < 0 || y < 0 || x >= i64::from(width) || y >= i64::from(height) {
+                    break;
+                }
+            }
+        }
+        Ok(overlay)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use image::RgbaImage;
+
+    use super::*;
+    use crate::operations::OutputImage;
+
+    fn test_icon() -> Icon {
+        let wall =
+            DynamicImage::ImageRgba8(RgbaImage::from_pixel(32, 32, Rgba([200, 200, 200, 255])));
+        Icon {
+            width: 32,
+            height: 32,
+            states: vec![IconState {
+                name: "wall".to_string(),
+                images: vec![wall],
+                ..Default::default()
+            }],
+            ..Default::default()
+        }
+    }
+
+    fn run(config: &DamageOverlay) -> Icon {
+        let input = InputIcon::Dmi(test_icon());
+        let ProcessorPayload::Single(output) = config
+            .do_operation(&input, OperationMode::Standard)
+            .unwrap()
+        else {
+            panic!("Expected a single output");
+        };
+        let OutputImage::Dmi(icon) = *output else {
+            panic!("Expected a dmi output");
+        };
+        icon
+    }
+
+    #[test]
+    fn generates_damaged_states() {
+        let config = DamageOverlay {
+            seed: 1234,
+            levels: 2,
+            ..Default::default()
+        };
+        let icon = run(&config);
+
+        let names: Vec<&str> = icon
+            .states
+            .iter()
+            .map(|state| state.name.as_str())
+            .collect();
+        assert_eq!(names, vec!["wall", "wall-damaged-1", "wall-damaged-2"]);
+        assert_eq!(icon.states[0], test_icon().states[0]);
+        assert_ne!(icon.states[1].images, icon.states[0].images);
+        assert_ne!(icon.states[2].images, icon.states[1].images);
+
+        // same seed, same cracks
+        assert_eq!(run(&config).states, icon.states);
+    }
+
+    #[test]
+    fn rejects_invalid_intensity() {
+        let config = DamageOverlay {
+            intensity: 2.0,
+            ..Default::default()
+        };
+        let input = InputIcon::Dmi(test_icon());
+        assert!(config
+            .do_operation(&input, OperationMode::Standard)
+            .is_err());
+    }
+}
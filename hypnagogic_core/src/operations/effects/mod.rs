@@ -0,0 +1,2 @@
+pub mod color_swap;
+pub mod damage_overlay;
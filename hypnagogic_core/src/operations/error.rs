@@ -8,8 +8,44 @@ pub enum ProcessorError {
     ImageError(#[from] image::error::ImageError),
     #[error("Error generating icon for processor:\n{0}")]
     GenerationError(#[from] crate::generation::error::GenerationError),
+    #[deprecated(note = "use `ProcessorError::InvalidConfig`, which says what was wrong")]
     #[error("Error within image config:")]
     ConfigError,
+    #[error("Invalid config:\n{0}")]
+    InvalidConfig(String),
+    #[error("Can't merge a {found_width}x{found_height} dmi in to a {width}x{height} dmi")]
+    MismatchedIconSize {
+        width: u32,
+        height: u32,
+        found_width: u32,
+        found_height: u32,
+    },
+    #[error(
+        "Input of size {width}x{height} isn't a whole number of {icon_width}x{icon_height} icons, \
+         check that icon_size matches the input"
+    )]
+    ImageDimensionMismatch {
+        width: u32,
+        height: u32,
+        icon_width: u32,
+        icon_height: u32,
+    },
+    #[error("Error encoding png:\n{0}")]
+    PngEncodingError(#[from] png::EncodingError),
+    #[error("Error building dmi:\n{0}")]
+    DmiError(#[from] dmi::error::DmiError),
+    #[error("Image of size {width}x{height} exceeds the maximum dimension of {max}")]
+    ImageTooLarge { width: u32, height: u32, max: u32 },
+    /// The width and height that were attempted, which don't fit in a `u32` or
+    /// are too large to back with a buffer
+    #[error("Image dimensions overflowed while allocating a {0}x{1} image")]
+    DimensionOverflow(u64, u64),
+    #[error("More than one output has an icon state named \"{0}\", so they can't be merged")]
+    DuplicateState(String),
+    #[error("Icon state \"{state}\" can't be converted:\n{reason}")]
+    InvalidState { state: String, reason: String },
+    #[error("Colors in the image aren't covered by the palette: {0:?}")]
+    UncoveredColors(Vec<String>),
 }
 
 pub type ProcessorResult<T> = Result<T, ProcessorError>;
@@ -0,0 +1,320 @@
+use dmi::icon::{Icon, IconState};
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+
+use crate::operations::error::{ProcessorError, ProcessorResult};
+use crate::operations::{IconOperationConfig, InputIcon, OperationMode, ProcessorPayload};
+
+/// Direction counts that byond actually supports
+const VALID_DIR_COUNTS: [u32; 3] = [1, 4, 8];
+
+/// Takes single dir states where each frame is actually a direction, and turns
+/// the frames in to directions.
+#[derive(Clone, PartialEq, Debug, Default, Serialize, Deserialize)]
+pub struct FramesToDirs {
+    /// Names of the states to convert. If not set, every state is converted.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub states: Option<Vec<String>>,
+}
+
+impl IconOperationConfig for FramesToDirs {
+    #[tracing::instrument(skip(input))]
+    fn perform_operation(
+        &self,
+        input: &InputIcon,
+        mode: OperationMode,
+    ) -> ProcessorResult<ProcessorPayload> {
+        debug!("Starting frames to dirs icon op");
+        let icon = dmi_input(input)?;
+        let states = icon
+            .states
+            .iter()
+            .map(|state| {
+                if !is_selected(self.states.as_ref(), state) {
+                    return Ok(state.clone());
+                }
+                if state.dirs != 1 {
+                    return Err(invalid_state(
+                        state,
+                        format!("Expected a single dir state, found {} dirs", state.dirs),
+                    ));
+                }
+                if !VALID_DIR_COUNTS.contains(&state.frames) {
+                    return Err(invalid_state(
+                        state,
+                        format!(
+                            "{} frames can't be turned in to directions, must be one of {:?}",
+                            state.frames, VALID_DIR_COUNTS
+                        ),
+                    ));
+                }
+                // images are stored frame major, so with a single dir the frame order is
+                // already the dir order
+                Ok(IconState {
+                    dirs: state.frames as u8,
+                    frames: 1,
+                    delay: None,
+                    ..state.clone()
+                })
+            })
+            .collect::<ProcessorResult<Vec<_>>>()?;
+
+        Ok(ProcessorPayload::from_icon(Icon {
+            states,
+            ..icon.clone()
+        }))
+    }
+
+    fn verify_config(&self) -> ProcessorResult<()> {
+        Ok(())
+    }
+}
+
+/// The inverse of [`FramesToDirs`]; takes single frame states and turns each
+/// direction in to a frame of a single dir state.
+#[derive(Clone, PartialEq, Debug, Default, Serialize, Deserialize)]
+pub struct DirsToFrames {
+    /// Names of the states to convert. If not set, every state is converted.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub states: Option<Vec<String>>,
+    /// Delay to give every resulting frame. Defaults to 1 tick.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub delay: Option<f32>,
+}
+
+impl IconOperationConfig for DirsToFrames {
+    #[tracing::instrument(skip(input))]
+    fn perform_operation(
+        &self,
+        input: &InputIcon,
+        mode: OperationMode,
+    ) -> ProcessorResult<ProcessorPayload> {
+        debug!("Starting dirs to frames icon op");
+        let icon = dmi_input(input)?;
+        let delay = self.delay.unwrap_or(1.0);
+        let states = icon
+            .states
+            .iter()
+            .map(|state| {
+                if !is_selected(self.states.as_ref(), state) {
+                    return Ok(state.clone());
+                }
+                if state.frames != 1 {
+                    return Err(invalid_state(
+                        state,
+                        format!(
+                            "Expected a single frame state, found {} frames",
+                            state.frames
+                        ),
+                    ));
+                }
+                let frames = u32::from(state.dirs);
+                Ok(IconState {
+                    dirs: 1,
+                    frames,
+                    delay: (frames > 1).then(|| vec![delay; frames as usize]),
+                    ..state.clone()
+                })
+            })
+            .collect::<ProcessorResult<Vec<_>>>()?;
+
+        Ok(ProcessorPayload::from_icon(Icon {
+            states,
+            ..icon.clone()
+        }))
+    }
+
+    fn verify_config(&self) -> ProcessorResult<()> {
+        Ok(())
+    }
+}
+
+fn dmi_input(input: &InputIcon) -> ProcessorResult<&Icon> {
+    let InputIcon::Dmi(icon) = input else {
+        return Err(ProcessorError::FormatError(
+            "This operation only accepts dmi images".to_string(),
+        ));
+    };
+    Ok(icon)
+}
+
+fn is_selected(selected: Option<&Vec<String>>, state: &IconState) -> bool {
+    selected.is_none_or(|names| names.contains(&state.name))
+}
+
+fn invalid_state(state: &IconState, reason: String) -> ProcessorError {
+    ProcessorError::InvalidState {
+        state: state.name.clone(),
+        reason,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{DynamicImage, GenericImageView, Rgba};
+
+    use super::*;
+    use crate::operations::OutputImage;
+
+    fn solid(color: u8) -> DynamicImage {
+        let mut img = DynamicImage::new_rgba8(32, 32);
+        img.as_mut_rgba8()
+            .unwrap()
+            .pixels_mut()
+            .for_each(|pixel| *pixel = Rgba([color, 0, 0, 255]));
+        img
+    }
+
+    fn test_icon(dirs: u8, frames: u32) -> Icon {
+        let count = u32::from(dirs) * frames;
+        Icon {
+            width: 32,
+            height: 32,
+            states: vec![
+                IconState {
+                    name: "convert".to_string(),
+                    dirs,
+                    frames,
+                    images: (0..count).map(|i| solid(i as u8 * 10)).collect(),
+                    delay: (frames > 1).then(|| vec![1.0; frames as usize]),
+                    ..Default::default()
+                },
+                IconState {
+                    name: "untouched".to_string(),
+                    images: vec![solid(255)],
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        }
+    }
+
+    fn output_icon(payload: ProcessorPayload) -> Icon {
+        let ProcessorPayload::Single(output) = payload else {
+            panic!("Expected a single output");
+        };
+        let OutputImage::Dmi(icon) = *output else {
+            panic!("Expected a dmi output");
+        };
+        icon
+    }
+
+    fn first_pixel(state: &IconState) -> Vec<u8> {
+        state
+            .images
+            .iter()
+            .map(|img| img.get_pixel(0, 0).0[0])
+            .collect()
+    }
+
+    #[test]
+    fn frames_to_dirs() {
+        let config = FramesToDirs {
+            states: Some(vec!["convert".to_string()]),
+        };
+        let input = InputIcon::Dmi(test_icon(1, 4));
+        let icon = output_icon(
+            config
+                .do_operation(&input, OperationMode::Standard)
+                .unwrap(),
+        );
+
+        let converted = &icon.states[0];
+        assert_eq!(converted.dirs, 4);
+        assert_eq!(converted.frames, 1);
+        assert_eq!(converted.delay, None);
+        assert_eq!(first_pixel(converted), vec![0, 10, 20, 30]);
+        assert_eq!(icon.states[1], test_icon(1, 4).states[1]);
+    }
+
+    #[test]
+    fn frames_to_dirs_rejects_invalid_frame_count() {
+        let config = FramesToDirs::default();
+        let input = InputIcon::Dmi(test_icon(1, 3));
+        let result = config.do_operation(&input, OperationMode::Standard);
+        assert!(matches!(
+            result,
+            Err(ProcessorError::InvalidState { state, .. }) if state == "convert"
+        ));
+    }
+
+    #[test]
+    fn dirs_to_frames() {
+        let config = DirsToFrames {
+            states: Some(vec!["convert".to_string()]),
+            delay: Some(2.0),
+        };
+        let input = InputIcon::Dmi(test_icon(4, 1));
+        let icon = output_icon(
+            config
+                .do_operation(&input, OperationMode::Standard)
+                .unwrap(),
+        );
+
+        let converted = &icon.states[0];
+        assert_eq!(converted.dirs, 1);
+        assert_eq!(converted.frames, 4);
+        assert_eq!(converted.delay, Some(vec![2.0; 4]));
+        assert_eq!(first_pixel(converted), vec![0, 10, 20, 30]);
+        assert_eq!(icon.states[1], test_icon(4, 1).states[1]);
+    }
+
+    #[test]
+    fn hotspots_survive() {
+        use dmi::icon::Hotspot;
+
+        use crate::operations::NamedIcon;
+
+        let hotspot = Some(Hotspot { x: 3, y: 7 });
+        let mut icon = test_icon(1, 4);
+        icon.states[0].hotspot = hotspot;
+        // read back from an actual dmi, so the hotspot comes from the file's metadata
+        let mut bytes = vec![];
+        icon.save(&mut bytes).unwrap();
+        let input = InputIcon::Dmi(Icon::load(bytes.as_slice()).unwrap());
+        let InputIcon::Dmi(loaded) = &input else {
+            unreachable!();
+        };
+        assert_eq!(loaded.states[0].hotspot, hotspot);
+
+        let dirs = output_icon(
+            FramesToDirs::default()
+                .do_operation(&input, OperationMode::Standard)
+                .unwrap(),
+        );
+        let mut frames = output_icon(
+            DirsToFrames::default()
+                .do_operation(&InputIcon::Dmi(dirs.clone()), OperationMode::Standard)
+                .unwrap(),
+        );
+        // merging needs every state name to be unique
+        for state in &mut frames.states {
+            state.name.push_str("-back");
+        }
+        let merged = ProcessorPayload::MultipleNamed(vec![
+            NamedIcon::from_icon(dirs),
+            NamedIcon::from_icon(frames),
+        ])
+        .merge_into_single_dmi()
+        .unwrap()
+        .unwrap();
+
+        let mut bytes = vec![];
+        merged.save(&mut bytes).unwrap();
+        let output = Icon::load(bytes.as_slice()).unwrap();
+        let converted: Vec<&IconState> = output
+            .states
+            .iter()
+            .filter(|state| state.name.starts_with("convert"))
+            .collect();
+        assert_eq!(converted.len(), 2);
+        assert_eq!(converted[0].dirs, 4);
+        assert_eq!(converted[1].frames, 4);
+        for state in converted {
+            assert_eq!(state.hotspot, hotspot);
+        }
+    }
+}
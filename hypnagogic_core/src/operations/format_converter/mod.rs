@@ -1 +1,2 @@
 pub mod bitmask_to_precut;
+pub mod frames_to_dirs;
@@ -6,16 +6,21 @@ use cutters::bitmask_dir_visibility::BitmaskDirectionalVis;
 use cutters::bitmask_slice::BitmaskSlice;
 use cutters::bitmask_windows::BitmaskWindows;
 use dmi::error::DmiError;
-use dmi::icon::Icon;
+use dmi::icon::{Icon, IconState};
+use effects::color_swap::ColorSwap;
+use effects::damage_overlay::DamageOverlay;
 use enum_dispatch::enum_dispatch;
-use image::{DynamicImage, ImageError, ImageFormat};
+use format_converter::frames_to_dirs::{DirsToFrames, FramesToDirs};
+use image::{imageops, DynamicImage, ImageError, ImageFormat};
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
-use tracing::debug;
+use tracing::{debug, warn};
 
-use crate::operations::error::ProcessorResult;
+use crate::operations::error::{ProcessorError, ProcessorResult};
+use crate::util::icon_ops::safe_new_image;
 
 pub mod cutters;
+pub mod effects;
 pub mod error;
 pub mod format_converter;
 
@@ -43,6 +48,7 @@ impl InputIcon {
         match extension {
             "png" => Ok(Self::DynamicImage(image::load(reader, ImageFormat::Png)?)),
             "dmi" => Ok(Self::Dmi(Icon::load(reader)?)),
+            "webp" => Ok(Self::DynamicImage(image::load(reader, ImageFormat::WebP)?)),
             _ => Err(InputError::UnsupportedFormat(extension.to_string())),
         }
     }
@@ -101,6 +107,19 @@ impl NamedIcon {
         }
     }
 
+    /// The path and name hints joined with `/`, for naming the output after
+    /// where it would have been written
+    #[must_use]
+    pub fn state_name(&self) -> String {
+        [self.path_hint.as_deref(), self.name_hint.as_deref()]
+            .into_iter()
+            .flatten()
+            .map(|hint| hint.trim_matches('/'))
+            .filter(|hint| !hint.is_empty())
+            .collect::<Vec<_>>()
+            .join("/")
+    }
+
     /// Assemble what the final relative path of the image should be
     #[must_use]
     #[tracing::instrument]
@@ -135,6 +154,14 @@ impl NamedIcon {
 pub enum OutputImage {
     Png(DynamicImage),
     Dmi(Icon),
+    /// An animation, for previewing outside of byond. Delays are in ticks,
+    /// same as dmi delays
+    Gif {
+        frames: Vec<DynamicImage>,
+        delays: Vec<f32>,
+    },
+    /// Not actually an image, plain text that accompanies the other outputs
+    Text(String),
 }
 
 impl OutputImage {
@@ -143,8 +170,32 @@ impl OutputImage {
         match self {
             OutputImage::Png(_) => "png",
             OutputImage::Dmi(_) => "dmi",
+            OutputImage::Gif { .. } => "gif",
+            OutputImage::Text(_) => "txt",
         }
     }
+
+    /// Animation of the first direction of the dmi state named `state`, as a
+    /// gif for previewing its timing outside of byond. `None` if this isn't a
+    /// dmi, or it has no state by that name
+    #[must_use]
+    pub fn state_animation(&self, state: &str) -> Option<OutputImage> {
+        let OutputImage::Dmi(icon) = self else {
+            return None;
+        };
+        let state = icon.states.iter().find(|found| found.name == state)?;
+        // images are stored frame by frame, with every direction of a frame together
+        let frames = state
+            .images
+            .iter()
+            .step_by(usize::from(state.dirs.max(1)))
+            .cloned()
+            .collect();
+        Some(OutputImage::Gif {
+            frames,
+            delays: state.delay.clone().unwrap_or_default(),
+        })
+    }
 }
 
 /// Represents the result of an icon operation
@@ -166,6 +217,105 @@ impl ProcessorPayload {
     pub fn from_icon(icon: Icon) -> Self {
         Self::Single(Box::new(OutputImage::Dmi(icon)))
     }
+
+    /// Concatenates the states of every dmi in the payload in to one dmi.
+    /// Pngs that fit within the dmi's icon size become single frame states,
+    /// named after their path and name hints (`DEBUGOUT/CORNERS/CORNER-...`).
+    /// Anything else is left out with a warning. Returns `None` if there are
+    /// no dmis at all.
+    /// # Errors
+    /// Returns `ProcessorError::MismatchedIconSize` if the dmis aren't all the
+    /// same size, or `ProcessorError::DuplicateState` if two outputs have a
+    /// state with the same name
+    pub fn merge_into_single_dmi(self) -> ProcessorResult<Option<Icon>> {
+        let outputs = match self {
+            ProcessorPayload::Single(image) => {
+                vec![NamedIcon {
+                    path_hint: None,
+                    name_hint: None,
+                    image: *image,
+                }]
+            }
+            ProcessorPayload::SingleNamed(named) => vec![*named],
+            ProcessorPayload::MultipleNamed(named) => named,
+        };
+
+        let mut merged: Option<Icon> = None;
+        let mut others = vec![];
+        for output in outputs {
+            let OutputImage::Dmi(icon) = output.image else {
+                others.push(output);
+                continue;
+            };
+            let Some(merged) = &mut merged else {
+                merged = Some(icon);
+                continue;
+            };
+            if (merged.width, merged.height) != (icon.width, icon.height) {
+                return Err(ProcessorError::MismatchedIconSize {
+                    width: merged.width,
+                    height: merged.height,
+                    found_width: icon.width,
+                    found_height: icon.height,
+                });
+            }
+            for state in icon.states {
+                push_unique_state(merged, state)?;
+            }
+        }
+
+        let Some(mut merged) = merged else {
+            return Ok(None);
+        };
+        for output in others {
+            let name = output.state_name();
+            match output.image {
+                OutputImage::Png(image)
+                    if image.width() <= merged.width && image.height() <= merged.height =>
+                {
+                    let mut frame = safe_new_image(merged.width, merged.height)?;
+                    imageops::replace(&mut frame, &image, 0, 0);
+                    let state = IconState {
+                        name,
+                        dirs: 1,
+                        frames: 1,
+                        images: vec![frame],
+                        ..Default::default()
+                    };
+                    push_unique_state(&mut merged, state)?;
+                }
+                _ => {
+                    warn!(
+                        output = name,
+                        "Output can't be a {}x{} icon state, leaving it out of the merged dmi",
+                        merged.width,
+                        merged.height
+                    );
+                }
+            }
+        }
+        Ok(Some(merged))
+    }
+
+    /// Creates a gif payload from animation frames and their delays (in
+    /// ticks). Missing delays default to 1 tick.
+    #[must_use]
+    pub fn from_animation(frames: Vec<DynamicImage>, delays: Vec<f32>) -> Self {
+        Self::Single(Box::new(OutputImage::Gif { frames, delays }))
+    }
+}
+
+/// Adds `state` to `icon`, unless it already has a state with the same name
+fn push_unique_state(icon: &mut Icon, state: IconState) -> ProcessorResult<()> {
+    if icon
+        .states
+        .iter()
+        .any(|existing| existing.name == state.name)
+    {
+        return Err(ProcessorError::DuplicateState(state.name));
+    }
+    icon.states.push(state);
+    Ok(())
 }
 
 /// Possible generic modes of operation for an icon operation
@@ -174,12 +324,20 @@ impl ProcessorPayload {
 pub enum OperationMode {
     Standard,
     Debug,
+    /// Only produce enough output to preview the result, for thumbnails and
+    /// editors. Operations without a cheaper path treat this like `Standard`.
+    Preview,
 }
 
 /// Implement this trait to create a new type of icon operation
 ///
 /// Once implemented, it can be used in a processor by adding it to the
 /// `IconOperation` enum.
+///
+/// Operations that re-emit states from an `InputIcon::Dmi` should build them
+/// from the input state (`IconState { images, ..state.clone() }`) so per-state
+/// metadata such as hotspots, movement and loop flags survives. Operations that
+/// synthesize brand new states, like the cutters, drop any such metadata.
 #[enum_dispatch]
 pub trait IconOperationConfig {
     /// Represents performing an icon operation as defined by the implementor
@@ -229,4 +387,195 @@ pub enum IconOperation {
     BitmaskSlice,
     BitmaskDirectionalVis,
     BitmaskWindows,
+    FramesToDirs,
+    DirsToFrames,
+    DamageOverlay,
+    ColorSwap,
+}
+
+impl IconOperation {
+    /// The name of the operation, matching the `mode` used in configs
+    #[must_use]
+    pub const fn operation_name(&self) -> &'static str {
+        match self {
+            IconOperation::BitmaskSlice(_) => "BitmaskSlice",
+            IconOperation::BitmaskDirectionalVis(_) => "BitmaskDirectionalVis",
+            IconOperation::BitmaskWindows(_) => "BitmaskWindows",
+            IconOperation::FramesToDirs(_) => "FramesToDirs",
+            IconOperation::DirsToFrames(_) => "DirsToFrames",
+            IconOperation::DamageOverlay(_) => "DamageOverlay",
+            IconOperation::ColorSwap(_) => "ColorSwap",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::File;
+    use std::io::{BufReader, Cursor};
+
+    use dmi::icon::IconState;
+    use image::GenericImageView;
+
+    use super::*;
+
+    fn icon(size: u32, names: &[&str]) -> Icon {
+        Icon {
+            width: size,
+            height: size,
+            states: names
+                .iter()
+                .map(|name| {
+                    IconState {
+                        name: (*name).to_string(),
+                        images: vec![DynamicImage::new_rgba8(size, size)],
+                        ..Default::default()
+                    }
+                })
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn state_animation() {
+        let frame = |shade: u8| {
+            DynamicImage::ImageRgba8(image::RgbaImage::from_pixel(
+                4,
+                4,
+                image::Rgba([shade, 0, 0, 255]),
+            ))
+        };
+        let mut dmi = icon(4, &["still"]);
+        dmi.states.push(IconState {
+            name: "moving".to_string(),
+            dirs: 2,
+            frames: 2,
+            // frame 1 north, frame 1 south, frame 2 north, frame 2 south
+            images: vec![frame(1), frame(2), frame(3), frame(4)],
+            delay: Some(vec![1.0, 2.5]),
+            ..Default::default()
+        });
+        let dmi = OutputImage::Dmi(dmi);
+
+        let Some(OutputImage::Gif { frames, delays }) = dmi.state_animation("moving") else {
+            panic!("Expected a gif");
+        };
+        let shades: Vec<u8> = frames
+            .iter()
+            .map(|frame| frame.to_rgba8()[(0, 0)][0])
+            .collect();
+        assert_eq!(shades, vec![1, 3]);
+        assert_eq!(delays, vec![1.0, 2.5]);
+
+        assert!(dmi.state_animation("missing").is_none());
+        assert!(OutputImage::Text(String::new())
+            .state_animation("moving")
+            .is_none());
+    }
+
+    #[test]
+    fn merge_into_single_dmi() {
+        let payload = ProcessorPayload::MultipleNamed(vec![
+            NamedIcon::from_icon(icon(32, &["a", "b"])),
+            NamedIcon::new(
+                "DEBUGOUT",
+                "corner",
+                OutputImage::Png(DynamicImage::new_rgba8(16, 16)),
+            ),
+            NamedIcon::from_icon(icon(32, &["c"])),
+        ]);
+        let merged = payload.merge_into_single_dmi().unwrap().unwrap();
+        let names: Vec<&str> = merged
+            .states
+            .iter()
+            .map(|state| state.name.as_str())
+            .collect();
+        assert_eq!(names, vec!["a", "b", "c", "DEBUGOUT/corner"]);
+        assert_eq!(merged.states[3].images[0].dimensions(), (32, 32));
+
+        let oversized = ProcessorPayload::MultipleNamed(vec![
+            NamedIcon::from_icon(icon(32, &["a"])),
+            NamedIcon::new(
+                "DEBUGOUT",
+                "assembled",
+                OutputImage::Png(DynamicImage::new_rgba8(128, 32)),
+            ),
+        ]);
+        assert_eq!(
+            oversized
+                .merge_into_single_dmi()
+                .unwrap()
+                .unwrap()
+                .states
+                .len(),
+            1
+        );
+
+        let colliding = ProcessorPayload::MultipleNamed(vec![
+            NamedIcon::from_icon(icon(32, &["a", "b"])),
+            NamedIcon::from_icon(icon(32, &["b"])),
+        ]);
+        assert!(matches!(
+            colliding.merge_into_single_dmi(),
+            Err(ProcessorError::DuplicateState(name)) if name == "b"
+        ));
+
+        let mismatched = ProcessorPayload::MultipleNamed(vec![
+            NamedIcon::from_icon(icon(32, &["a"])),
+            NamedIcon::from_icon(icon(64, &["b"])),
+        ]);
+        assert!(matches!(
+            mismatched.merge_into_single_dmi(),
+            Err(ProcessorError::MismatchedIconSize {
+                found_width: 64,
+                ..
+            })
+        ));
+
+        let no_dmis = ProcessorPayload::Single(Box::new(OutputImage::Text(String::new())));
+        assert!(no_dmis.merge_into_single_dmi().unwrap().is_none());
+    }
+
+    #[test]
+    fn unknown_extension_is_unsupported() {
+        let result = InputIcon::from_reader(&mut Cursor::new(vec![]), "bmp");
+        assert!(matches!(result, Err(InputError::UnsupportedFormat(ext)) if ext == "bmp"));
+    }
+
+    #[test]
+    fn decodes_webp() {
+        let fixture = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/res/3x2.webp");
+        let mut reader = BufReader::new(File::open(fixture).unwrap());
+        let Ok(InputIcon::DynamicImage(image)) = InputIcon::from_reader(&mut reader, "webp") else {
+            panic!("Expected a decoded image");
+        };
+        assert_eq!(image.dimensions(), (3, 2));
+        // lossless, alternating opaque red and translucent blue
+        let red = [255, 0, 0, 255];
+        let blue = [0, 0, 255, 128];
+        let pixels: Vec<[u8; 4]> = image.to_rgba8().pixels().map(|pixel| pixel.0).collect();
+        assert_eq!(pixels, vec![red, blue, red, blue, red, blue]);
+    }
+
+    #[test]
+    fn webp_is_decoded_as_webp() {
+        // not a valid webp, but it should get as far as the webp decoder
+        let result = InputIcon::from_reader(&mut Cursor::new(b"RIFF".to_vec()), "webp");
+        assert!(matches!(result, Err(InputError::DynamicRead(_))));
+    }
+
+    #[test]
+    fn operation_name_matches_mode() {
+        for operation in [
+            IconOperation::FramesToDirs(FramesToDirs::default()),
+            IconOperation::DirsToFrames(DirsToFrames::default()),
+        ] {
+            let serialized = toml::Value::try_from(&operation).unwrap();
+            assert_eq!(
+                serialized.get("mode").and_then(toml::Value::as_str),
+                Some(operation.operation_name())
+            );
+        }
+    }
 }
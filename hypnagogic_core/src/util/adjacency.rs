@@ -1,5 +1,6 @@
 use bitflags::bitflags;
 use serde::{Deserialize, Serialize};
+use thiserror::Error;
 
 use crate::util::corners::{Corner, CornerType, Side};
 
@@ -21,6 +22,12 @@ bitflags! {
     }
 }
 
+#[derive(Debug, Error, Eq, PartialEq)]
+pub enum InvalidAdjacency {
+    #[error("{0} sets a corner without both of its sides, so it never gets generated")]
+    OrphanedCorner(u8),
+}
+
 impl From<Corner> for Adjacency {
     fn from(corner: Corner) -> Self {
         Adjacency::from_corner(corner)
@@ -95,6 +102,20 @@ impl Adjacency {
         self.has_no_orphaned_corner()
     }
 
+    /// Checked version of `from_bits` for user supplied signatures.
+    /// # Errors
+    /// Errors if the bits set a corner without both of its sides, as those
+    /// signatures are never generated
+    pub fn try_from_bits(bits: u8) -> Result<Self, InvalidAdjacency> {
+        // all eight bits are flags, so every u8 is a set of them
+        let adjacency = Self::from_bits_truncate(bits);
+        if adjacency.has_no_orphaned_corner() {
+            Ok(adjacency)
+        } else {
+            Err(InvalidAdjacency::OrphanedCorner(bits))
+        }
+    }
+
     // implemented as const for usage in get corner type
     const fn from_corner(corner: Corner) -> Self {
         match corner {
@@ -143,6 +164,11 @@ impl Adjacency {
         }
     }
 
+    /// Rotates a single flag so that art authored facing south ends up facing
+    /// `direction`, ie `S` always maps to `direction`. This reads backwards for
+    /// the cardinals: `E` rotates counter-clockwise and `W` rotates clockwise.
+    /// Diagonal directions rotate to the cardinal counter-clockwise of them,
+    /// then a further 45 degrees clockwise.
     #[must_use]
     pub fn rotate_dir(self, direction: Self) -> Self {
         match direction {
@@ -190,14 +216,37 @@ impl Adjacency {
                     _ => unimplemented!("Only single allowed"),
                 }
             }
-            _ => {
-                unimplemented!(
-                    "Rotating to diagonals doesn't make sense. This is a programming error."
-                )
-            }
+            Adjacency::NE => self.rotate_dir(Adjacency::N).rotate_45(),
+            Adjacency::SE => self.rotate_dir(Adjacency::E).rotate_45(),
+            Adjacency::SW => self.rotate_45(),
+            Adjacency::NW => self.rotate_dir(Adjacency::W).rotate_45(),
+            _ => unimplemented!("Can only rotate to a single direction"),
         }
     }
 
+    /// Rotates every set flag 45 degrees clockwise, so `N` becomes `NE`, `NE`
+    /// becomes `E` and so on. Note that this turns cardinal signatures in to
+    /// diagonal only ones and vice versa.
+    #[must_use]
+    pub fn rotate_45(self) -> Self {
+        const CLOCKWISE: [Adjacency; 8] = [
+            Adjacency::N,
+            Adjacency::NE,
+            Adjacency::E,
+            Adjacency::SE,
+            Adjacency::S,
+            Adjacency::SW,
+            Adjacency::W,
+            Adjacency::NW,
+        ];
+        CLOCKWISE
+            .iter()
+            .zip(CLOCKWISE.iter().cycle().skip(1))
+            .filter(|(flag, _)| self.contains(**flag))
+            .fold(Adjacency::empty(), |accum, (_, rotated)| accum | *rotated)
+    }
+
+    /// Rotates every set flag with `rotate_dir`
     #[must_use]
     pub fn rotate_to(self, direction: Self) -> Self {
         self.set_flags_vec()
@@ -222,4 +271,38 @@ mod tests {
 
         assert!(expected.iter().all(|item| result.contains(item)));
     }
+
+    #[test]
+    fn rotate_45_test() {
+        let adj = Adjacency::N | Adjacency::E | Adjacency::NW;
+        assert_eq!(
+            adj.rotate_45(),
+            Adjacency::NE | Adjacency::SE | Adjacency::N
+        );
+
+        let full_turn = (0..8).fold(adj, |accum, _| accum.rotate_45());
+        assert_eq!(full_turn, adj);
+    }
+
+    #[test]
+    fn rotate_to_diagonals() {
+        for direction in [Adjacency::NE, Adjacency::SE, Adjacency::SW, Adjacency::NW] {
+            assert_eq!(Adjacency::S.rotate_to(direction), direction);
+        }
+        assert_eq!(
+            (Adjacency::S | Adjacency::E).rotate_to(Adjacency::SE),
+            Adjacency::SE | Adjacency::NE
+        );
+    }
+
+    #[test]
+    fn try_from_bits_test() {
+        assert_eq!(Adjacency::try_from_bits(0), Ok(Adjacency::empty()));
+        let filled = Adjacency::N | Adjacency::E | Adjacency::NE;
+        assert_eq!(Adjacency::try_from_bits(filled.bits()), Ok(filled));
+        assert_eq!(
+            Adjacency::try_from_bits(Adjacency::NE.bits()),
+            Err(InvalidAdjacency::OrphanedCorner(Adjacency::NE.bits()))
+        );
+    }
 }
@@ -1,4 +1,5 @@
 use std::num::ParseIntError;
+use std::str::FromStr;
 
 use image::DynamicImage;
 use serde::{Deserialize, Serialize};
@@ -33,6 +34,47 @@ impl Color {
         }
     }
 
+    /// Composites `over` on top of this color (source over), respecting the
+    /// alpha of both
+    #[must_use]
+    pub fn blend(&self, over: Color) -> Color {
+        let src_alpha = f32::from(over.alpha) / 255.0;
+        let dst_alpha = f32::from(self.alpha) / 255.0;
+        let out_alpha = src_alpha + dst_alpha * (1.0 - src_alpha);
+        if out_alpha <= 0.0 {
+            return Color::default();
+        }
+        let channel = |src: u8, dst: u8| {
+            let blended = (f32::from(src) * src_alpha
+                + f32::from(dst) * dst_alpha * (1.0 - src_alpha))
+                / out_alpha;
+            blended.round() as u8
+        };
+        Color::new(
+            channel(over.red, self.red),
+            channel(over.green, self.green),
+            channel(over.blue, self.blue),
+            (out_alpha * 255.0).round() as u8,
+        )
+    }
+
+    /// Linearly interpolates every channel, alpha included, from this color
+    /// towards `to`. `amount` is clamped to 0 to 1, where 0 is this color and 1
+    /// is `to`.
+    #[must_use]
+    pub fn lerp(&self, to: Color, amount: f32) -> Color {
+        let amount = amount.clamp(0.0, 1.0);
+        let channel = |from: u8, to: u8| {
+            (f32::from(from) + (f32::from(to) - f32::from(from)) * amount).round() as u8
+        };
+        Color::new(
+            channel(self.red, to.red),
+            channel(self.green, to.green),
+            channel(self.blue, to.blue),
+            channel(self.alpha, to.alpha),
+        )
+    }
+
     /// Returns a color from a hex string.
     /// Accepts 3, 4, 6, and 8 digit hex strings.
     /// If the string is 3 or 4 digits, each digit is duplicated.
@@ -92,6 +134,34 @@ impl Color {
         })
     }
 
+    /// Returns the color with the given name, ignoring case. Covers the 16
+    /// basic css colors, plus the alternate names byond accepts for them
+    /// (`grey`, `cyan`, `magenta`) and `transparent`.
+    #[must_use]
+    pub fn from_named(name: &str) -> Option<Self> {
+        let color = match name.to_ascii_lowercase().as_str() {
+            "black" => Self::new_rgb(0, 0, 0),
+            "silver" => Self::new_rgb(192, 192, 192),
+            "gray" | "grey" => Self::new_rgb(128, 128, 128),
+            "white" => Self::new_rgb(255, 255, 255),
+            "maroon" => Self::new_rgb(128, 0, 0),
+            "red" => Self::new_rgb(255, 0, 0),
+            "purple" => Self::new_rgb(128, 0, 128),
+            "fuchsia" | "magenta" => Self::new_rgb(255, 0, 255),
+            "green" => Self::new_rgb(0, 128, 0),
+            "lime" => Self::new_rgb(0, 255, 0),
+            "olive" => Self::new_rgb(128, 128, 0),
+            "yellow" => Self::new_rgb(255, 255, 0),
+            "navy" => Self::new_rgb(0, 0, 128),
+            "blue" => Self::new_rgb(0, 0, 255),
+            "teal" => Self::new_rgb(0, 128, 128),
+            "aqua" | "cyan" => Self::new_rgb(0, 255, 255),
+            "transparent" => Self::new(0, 0, 0, 0),
+            _ => return None,
+        };
+        Some(color)
+    }
+
     #[must_use]
     pub fn to_hex_str(&self) -> String {
         format!(
@@ -100,6 +170,68 @@ impl Color {
         )
     }
 
+    /// Converts to hue (in degrees, 0 to 360), saturation and lightness (both
+    /// 0 to 1). Alpha is ignored.
+    #[must_use]
+    pub fn to_hsl(&self) -> (f32, f32, f32) {
+        // channels are compared as bytes, so grays are found exactly
+        let max = self.red.max(self.green).max(self.blue);
+        let min = self.red.min(self.green).min(self.blue);
+        let lightness = (f32::from(max) + f32::from(min)) / 510.0;
+        if max == min {
+            return (0.0, 0.0, lightness);
+        }
+
+        let red = f32::from(self.red) / 255.0;
+        let green = f32::from(self.green) / 255.0;
+        let blue = f32::from(self.blue) / 255.0;
+        let delta = f32::from(max - min) / 255.0;
+        let saturation = delta / (1.0 - (2.0 * lightness - 1.0).abs());
+        let hue = if max == self.red {
+            60.0 * ((green - blue) / delta).rem_euclid(6.0)
+        } else if max == self.green {
+            60.0 * ((blue - red) / delta + 2.0)
+        } else {
+            60.0 * ((red - green) / delta + 4.0)
+        };
+        (hue, saturation, lightness)
+    }
+
+    /// Creates an opaque color from hue (in degrees), saturation and lightness
+    /// (both 0 to 1). Hues outside of 0 to 360 wrap around, and saturation and
+    /// lightness are clamped. See [`Color::with_hsl`] to keep the alpha of an
+    /// existing color.
+    #[must_use]
+    pub fn from_hsl(hue: f32, saturation: f32, lightness: f32) -> Self {
+        let hue = hue.rem_euclid(360.0);
+        let saturation = saturation.clamp(0.0, 1.0);
+        let lightness = lightness.clamp(0.0, 1.0);
+
+        let chroma = (1.0 - (2.0 * lightness - 1.0).abs()) * saturation;
+        let second = chroma * (1.0 - ((hue / 60.0).rem_euclid(2.0) - 1.0).abs());
+        let (red, green, blue) = match hue {
+            hue if hue < 60.0 => (chroma, second, 0.0),
+            hue if hue < 120.0 => (second, chroma, 0.0),
+            hue if hue < 180.0 => (0.0, chroma, second),
+            hue if hue < 240.0 => (0.0, second, chroma),
+            hue if hue < 300.0 => (second, 0.0, chroma),
+            _ => (chroma, 0.0, second),
+        };
+        let offset = lightness - chroma / 2.0;
+        let channel = |value: f32| ((value + offset) * 255.0).round() as u8;
+        Self::new_rgb(channel(red), channel(green), channel(blue))
+    }
+
+    /// This color with its hue, saturation and lightness replaced, keeping its
+    /// alpha. Takes the same ranges as [`Color::from_hsl`].
+    #[must_use]
+    pub fn with_hsl(&self, hue: f32, saturation: f32, lightness: f32) -> Self {
+        Self {
+            alpha: self.alpha,
+            ..Self::from_hsl(hue, saturation, lightness)
+        }
+    }
+
     #[must_use]
     pub fn luminance(&self) -> f32 {
         (0.299 * self.red as f32 + 0.587 * self.green as f32 + 0.114 * self.blue as f32) / 255.0
@@ -112,10 +244,27 @@ impl Serialize for Color {
     }
 }
 
+/// Parses a hex color starting with #, or a color name
+impl FromStr for Color {
+    type Err = String;
+
+    fn from_str(color_str: &str) -> Result<Self, Self::Err> {
+        if color_str.starts_with('#') {
+            return Self::from_hex_str(color_str).map_err(|err| err.to_string());
+        }
+        Self::from_named(color_str).ok_or_else(|| {
+            format!(
+                "Unknown color \"{color_str}\", expected a color name or a hex color starting \
+                 with #"
+            )
+        })
+    }
+}
+
 impl<'de> Deserialize<'de> for Color {
     fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
-        let hex_str = String::deserialize(deserializer)?;
-        Self::from_hex_str(&hex_str).map_err(serde::de::Error::custom)
+        let color_str = String::deserialize(deserializer)?;
+        color_str.parse().map_err(serde::de::Error::custom)
     }
 }
 
@@ -254,4 +403,89 @@ mod tests {
         let color = Color::from_hex_str(hex).unwrap();
         assert_eq!(color, Color::new(240, 15, 15, 255));
     }
+
+    #[test]
+    fn named_colors() {
+        assert_eq!(Color::from_named("red"), Some(Color::new_rgb(255, 0, 0)));
+        assert_eq!(Color::from_named("Cyan"), Color::from_named("aqua"));
+        assert_eq!(Color::from_named("transparent"), Some(Color::default()));
+        assert_eq!(Color::from_named("reddish"), None);
+
+        let deserialize = |value: &str| Color::deserialize(toml::Value::from(value));
+        assert_eq!(deserialize("white").unwrap(), Color::new_rgb(255, 255, 255));
+        assert_eq!(deserialize("#0f0").unwrap(), Color::new_rgb(0, 255, 0));
+        assert!(deserialize("#white").is_err());
+        assert!(deserialize("ff0000").is_err());
+    }
+
+    #[test]
+    fn hsl_round_trip() {
+        let known = [
+            (Color::new_rgb(255, 0, 0), (0.0, 1.0, 0.5)),
+            (Color::new_rgb(0, 128, 0), (120.0, 1.0, 0.251)),
+            (Color::new_rgb(0, 0, 255), (240.0, 1.0, 0.5)),
+            (Color::new_rgb(255, 255, 255), (0.0, 0.0, 1.0)),
+            (Color::new_rgb(128, 128, 128), (0.0, 0.0, 0.502)),
+            (Color::new_rgb(51, 102, 153), (210.0, 0.5, 0.4)),
+            (Color::new(51, 102, 153, 128), (210.0, 0.5, 0.4)),
+        ];
+        for (color, (hue, saturation, lightness)) in known {
+            let hsl = color.to_hsl();
+            assert!((hsl.0 - hue).abs() < 0.01, "{color:?} hue was {}", hsl.0);
+            assert!(
+                (hsl.1 - saturation).abs() < 0.01,
+                "{color:?} saturation was {}",
+                hsl.1
+            );
+            assert!(
+                (hsl.2 - lightness).abs() < 0.01,
+                "{color:?} lightness was {}",
+                hsl.2
+            );
+            assert_eq!(color.with_hsl(hsl.0, hsl.1, hsl.2), color);
+        }
+
+        // from_hsl always gives an opaque color
+        let translucent = Color::new(255, 0, 0, 64);
+        let (hue, saturation, lightness) = translucent.to_hsl();
+        assert_eq!(
+            Color::from_hsl(hue, saturation, lightness),
+            Color::new_rgb(255, 0, 0)
+        );
+        assert_eq!(
+            translucent.with_hsl(hue + 120.0, saturation, lightness),
+            Color::new(0, 255, 0, 64)
+        );
+
+        assert_eq!(Color::from_hsl(360.0, 1.0, 0.5), Color::new_rgb(255, 0, 0));
+        assert_eq!(Color::from_hsl(-120.0, 1.0, 0.5), Color::new_rgb(0, 0, 255));
+    }
+
+    #[test]
+    fn lerp_test() {
+        let from = Color::new(0, 100, 200, 255);
+        let to = Color::new(255, 0, 200, 0);
+        assert_eq!(from.lerp(to, 0.0), from);
+        assert_eq!(from.lerp(to, 1.0), to);
+        assert_eq!(from.lerp(to, 0.5), Color::new(128, 50, 200, 128));
+        assert_eq!(from.lerp(to, 2.0), to);
+    }
+
+    #[test]
+    fn blend_test() {
+        let red = Color::new_rgb(255, 0, 0);
+        let blue = Color::new_rgb(0, 0, 255);
+
+        assert_eq!(red.blend(blue), blue);
+        assert_eq!(red.blend(Color::new(0, 0, 255, 0)), red);
+        assert_eq!(Color::default().blend(Color::default()), Color::default());
+        assert_eq!(
+            red.blend(Color::new(0, 0, 255, 128)),
+            Color::new(127, 0, 128, 255)
+        );
+        assert_eq!(
+            Color::new(255, 0, 0, 0).blend(Color::new(0, 0, 255, 128)),
+            Color::new(0, 0, 255, 128)
+        );
+    }
 }
@@ -1,6 +1,7 @@
 use std::fmt::{Display, Formatter};
+use std::str::FromStr;
 
-use enum_iterator::Sequence;
+use enum_iterator::{all, Sequence};
 use fixed_map::Key;
 use serde::{Deserialize, Serialize};
 
@@ -17,18 +18,31 @@ pub enum Side {
     West,
 }
 
-impl From<&str> for Side {
-    fn from(s: &str) -> Self {
+impl FromStr for Side {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
         match s {
-            "north" => Self::North,
-            "south" => Self::South,
-            "east" => Self::East,
-            "west" => Self::West,
-            _ => panic!("Invalid side: {s}"),
+            "north" => Ok(Self::North),
+            "south" => Ok(Self::South),
+            "east" => Ok(Self::East),
+            "west" => Ok(Self::West),
+            _ => {
+                Err(format!(
+                    "\"{s}\" is not a side, expected north, south, east or west"
+                ))
+            }
         }
     }
 }
 
+impl From<&str> for Side {
+    fn from(s: &str) -> Self {
+        s.parse()
+            .unwrap_or_else(|err| panic!("Invalid side: {err}"))
+    }
+}
+
 impl Display for Side {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -52,6 +66,12 @@ impl Side {
         }
     }
 
+    /// Inverse of `byond_dir`, `None` if the dir isn't a single cardinal
+    #[must_use]
+    pub fn from_byond_dir(dir: u8) -> Option<Self> {
+        all::<Self>().find(|side| side.byond_dir() == dir)
+    }
+
     /// Returns an array of directions in the order that byond specifies
     /// directions. Yes, it is correct that "South" is done before North
     #[must_use]
@@ -102,6 +122,12 @@ impl Corner {
         let (horizontal, vertical) = self.sides_of_corner();
         horizontal.byond_dir() | vertical.byond_dir()
     }
+
+    /// Inverse of `byond_dir`, `None` if the dir isn't a diagonal
+    #[must_use]
+    pub fn from_byond_dir(dir: u8) -> Option<Self> {
+        all::<Self>().find(|corner| corner.byond_dir() == dir)
+    }
 }
 
 /// Represents the five possible given states for a corner to be in when bitmask
@@ -116,19 +142,34 @@ pub enum CornerType {
     Flat,
 }
 
-impl From<&str> for CornerType {
-    fn from(value: &str) -> Self {
+impl FromStr for CornerType {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
         match value {
-            "convex" => Self::Convex,
-            "concave" => Self::Concave,
-            "horizontal" => Self::Horizontal,
-            "vertical" => Self::Vertical,
-            "flat" => Self::Flat,
-            _ => panic!("Invalid String: {value}"),
+            "convex" => Ok(Self::Convex),
+            "concave" => Ok(Self::Concave),
+            "horizontal" => Ok(Self::Horizontal),
+            "vertical" => Ok(Self::Vertical),
+            "flat" => Ok(Self::Flat),
+            _ => {
+                Err(format!(
+                    "\"{value}\" is not a corner type, expected convex, concave, horizontal, \
+                     vertical or flat"
+                ))
+            }
         }
     }
 }
 
+impl From<&str> for CornerType {
+    fn from(value: &str) -> Self {
+        value
+            .parse()
+            .unwrap_or_else(|err| panic!("Invalid String: {err}"))
+    }
+}
+
 impl Display for CornerType {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -166,3 +207,22 @@ impl CornerType {
         ]
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn byond_dir_round_trip() {
+        for side in all::<Side>() {
+            assert_eq!(Side::from_byond_dir(side.byond_dir()), Some(side));
+        }
+        for corner in all::<Corner>() {
+            assert_eq!(Corner::from_byond_dir(corner.byond_dir()), Some(corner));
+        }
+        assert_eq!(Side::from_byond_dir(5), None);
+        assert_eq!(Corner::from_byond_dir(5), Some(Corner::NorthEast));
+        assert_eq!(Corner::from_byond_dir(2), None);
+        assert_eq!(Corner::from_byond_dir(3), None);
+    }
+}
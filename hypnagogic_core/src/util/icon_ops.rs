@@ -1,8 +1,142 @@
-use dmi::icon::IconState;
-use image::{DynamicImage, GenericImageView};
+use std::collections::HashMap;
+use std::io::Write;
 
+use dmi::icon::{DmiVersion, Icon, IconState};
+use dmi::RawDmi;
+use image::codecs::gif::{GifEncoder, Repeat};
+use image::{Delay, DynamicImage, Frame, GenericImageView, ImageResult, Pixel};
+use tracing::warn;
+
+use crate::operations::error::{ProcessorError, ProcessorResult};
 use crate::util::color::Color;
 
+/// Default upper bound for either dimension of an image allocated by an
+/// operation
+pub const MAX_IMAGE_DIMENSION: u32 = 16384;
+
+/// Allocates a new blank rgba image, refusing anything larger than
+/// `MAX_IMAGE_DIMENSION` on either side.
+/// # Errors
+/// Returns `ProcessorError::ImageTooLarge` if either dimension exceeds the
+/// maximum, or `ProcessorError::DimensionOverflow` if the size of the backing
+/// buffer can't be represented
+pub fn safe_new_image(width: u32, height: u32) -> ProcessorResult<DynamicImage> {
+    safe_new_image_with_max(width, height, MAX_IMAGE_DIMENSION)
+}
+
+/// Same as `safe_new_image`, but with a caller provided maximum dimension
+/// # Errors
+/// Returns `ProcessorError::ImageTooLarge` if either dimension exceeds `max`,
+/// or `ProcessorError::DimensionOverflow` if the size of the backing buffer
+/// can't be represented
+pub fn safe_new_image_with_max(width: u32, height: u32, max: u32) -> ProcessorResult<DynamicImage> {
+    if width > max || height > max {
+        return Err(ProcessorError::ImageTooLarge { width, height, max });
+    }
+    // 4 bytes per pixel, as everything is allocated as rgba8
+    let buffer_len = u64::from(width)
+        .checked_mul(u64::from(height))
+        .and_then(|pixels| pixels.checked_mul(4))
+        .and_then(|bytes| usize::try_from(bytes).ok());
+    if buffer_len.is_none() {
+        return Err(ProcessorError::DimensionOverflow(
+            u64::from(width),
+            u64::from(height),
+        ));
+    }
+    Ok(DynamicImage::new_rgba8(width, height))
+}
+
+/// The size of a grid `columns` cells wide and `rows` cells tall, with each
+/// cell `cell_width` by `cell_height`
+/// # Errors
+/// Returns `ProcessorError::DimensionOverflow` with the attempted size if
+/// either side doesn't fit in a `u32`
+pub fn grid_size(
+    columns: u64,
+    rows: u64,
+    cell_width: u32,
+    cell_height: u32,
+) -> ProcessorResult<(u32, u32)> {
+    let width = columns.saturating_mul(u64::from(cell_width));
+    let height = rows.saturating_mul(u64::from(cell_height));
+    match (u32::try_from(width), u32::try_from(height)) {
+        (Ok(width), Ok(height)) => Ok((width, height)),
+        _ => Err(ProcessorError::DimensionOverflow(width, height)),
+    }
+}
+
+/// Builds a `DmiVersion` holding `version`.
+///
+/// The dmi crate doesn't let versions be constructed directly, only read from
+/// an existing dmi, so this round trips a tiny dmi with the version swapped in.
+/// # Errors
+/// Returns `ProcessorError::InvalidConfig` if the version would break the dmi
+/// metadata, or `ProcessorError::DmiError` if the round trip fails
+pub fn dmi_version(version: &str) -> ProcessorResult<DmiVersion> {
+    if version.is_empty() || version.contains(['\n', '\r', '#']) {
+        return Err(ProcessorError::InvalidConfig(format!(
+            "dmi_version \"{}\" isn't a valid dmi version",
+            version.escape_default()
+        )));
+    }
+    let placeholder = Icon {
+        width: 32,
+        height: 32,
+        states: vec![IconState {
+            images: vec![DynamicImage::new_rgba8(32, 32)],
+            ..Default::default()
+        }],
+        ..Default::default()
+    };
+    let mut bytes = vec![];
+    placeholder.save(&mut bytes)?;
+
+    let mut raw = RawDmi::load(bytes.as_slice())?;
+    let signature = [
+        "# BEGIN DMI".to_string(),
+        format!("version = {version}"),
+        "\twidth = 32".to_string(),
+        "\theight = 32".to_string(),
+        "state = \"\"".to_string(),
+        "\tdirs = 1".to_string(),
+        "\tframes = 1".to_string(),
+        "# END DMI\n".to_string(),
+    ]
+    .join("\n");
+    raw.chunk_ztxt = Some(dmi::ztxt::create_ztxt_chunk(signature.as_bytes())?);
+    let mut bytes = vec![];
+    raw.save(&mut bytes)?;
+
+    Ok(Icon::load(bytes.as_slice())?.version)
+}
+
+/// Lays the first frame of every state out in a grid, `columns` wide, left to
+/// right then top to bottom. Each cell is the icon's width and height.
+/// # Errors
+/// Returns `ProcessorError::ImageTooLarge` or
+/// `ProcessorError::DimensionOverflow` if the sheet is too large to allocate
+pub fn flatten_to_sheet(icon: &Icon, columns: usize) -> ProcessorResult<DynamicImage> {
+    let columns = columns.max(1);
+    let rows = icon.states.len().div_ceil(columns);
+    let (width, height) = grid_size(columns as u64, rows as u64, icon.width, icon.height)?;
+    let mut sheet = safe_new_image(width, height)?;
+
+    for (index, state) in icon.states.iter().enumerate() {
+        let Some(frame) = state.images.first() else {
+            continue;
+        };
+        // both fit, as the sheet they're placed in does
+        #[allow(clippy::cast_possible_wrap)]
+        let (x, y) = (
+            (index % columns) as i64 * i64::from(icon.width),
+            (index / columns) as i64 * i64::from(icon.height),
+        );
+        image::imageops::replace(&mut sheet, frame, x, y);
+    }
+    Ok(sheet)
+}
+
 // Removes duplicate frames from the icon state's animation, if it has any
 #[must_use]
 pub fn dedupe_frames(icon_state: IconState) -> IconState {
@@ -70,18 +204,554 @@ pub fn colors_in_image(image: &DynamicImage) -> Vec<Color> {
         .collect()
 }
 
+/// Encodes frames as a looping gif. Delays are in ticks (tenths of a second),
+/// like dmi delays, and frames without a delay get 1 tick.
+/// # Errors
+/// Errors if the gif fails to encode or write
+pub fn write_gif<W: Write>(writer: W, frames: &[DynamicImage], delays: &[f32]) -> ImageResult<()> {
+    let mut encoder = GifEncoder::new(writer);
+    encoder.set_repeat(Repeat::Infinite)?;
+    encoder.encode_frames(frames.iter().enumerate().map(|(index, frame)| {
+        let ticks = delays.get(index).copied().unwrap_or(1.0);
+        let millis = (ticks * 100.0).round() as u32;
+        Frame::from_parts(
+            frame.to_rgba8(),
+            0,
+            0,
+            Delay::from_numer_denom_ms(millis, 1),
+        )
+    }))
+}
+
+/// Saves `icon` as a dmi backed by an indexed color png, which is usually far
+/// smaller than the rgba png `Icon::save` writes. Pixels are kept exactly, so
+/// icons with more than 256 distinct colors can't be indexed. Those, and any
+/// icon where indexing doesn't actually save space, are saved as rgba.
+/// Returns whether the icon was indexed.
+/// # Errors
+/// Errors if the icon fails to encode or write
+pub fn save_indexed<W: Write>(icon: &Icon, writer: &mut W) -> ProcessorResult<bool> {
+    // saving normally first gets us both the sheet layout and the dmi metadata
+    let mut rgba_bytes = vec![];
+    icon.save(&mut rgba_bytes)?;
+    let sheet = image::load_from_memory(&rgba_bytes)?.to_rgba8();
+
+    let mut palette: HashMap<[u8; 4], u8> = HashMap::new();
+    let mut indices = Vec::with_capacity(sheet.pixels().len());
+    for pixel in sheet.pixels() {
+        if let Some(index) = palette.get(&pixel.0) {
+            indices.push(*index);
+            continue;
+        }
+        // a u8 index only has room for 256 colors
+        let Ok(index) = u8::try_from(palette.len()) else {
+            writer
+                .write_all(&rgba_bytes)
+                .map_err(dmi::error::DmiError::from)?;
+            return Ok(false);
+        };
+        palette.insert(pixel.0, index);
+        indices.push(index);
+    }
+    let mut entries: Vec<([u8; 4], u8)> = palette.into_iter().collect();
+    entries.sort_by_key(|(_, index)| *index);
+    let colors: Vec<u8> = entries
+        .iter()
+        .flat_map(|([r, g, b, _], _)| [*r, *g, *b])
+        .collect();
+    let alphas: Vec<u8> = entries.iter().map(|([.., a], _)| *a).collect();
+
+    let mut indexed_bytes = vec![];
+    let mut encoder = png::Encoder::new(&mut indexed_bytes, sheet.width(), sheet.height());
+    encoder.set_color(png::ColorType::Indexed);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.set_palette(colors);
+    encoder.set_trns(alphas);
+    encoder.set_compression(png::Compression::Best);
+    // filtering palette indices only muddies them, unfiltered rows compress better
+    encoder.set_filter(png::FilterType::NoFilter);
+    encoder.write_header()?.write_image_data(&indices)?;
+
+    let mut indexed = RawDmi::load(indexed_bytes.as_slice())?;
+    indexed.chunk_ztxt = RawDmi::load(rgba_bytes.as_slice())?.chunk_ztxt;
+    let mut indexed_bytes = vec![];
+    indexed.save(&mut indexed_bytes)?;
+
+    let is_smaller = indexed_bytes.len() < rgba_bytes.len();
+    let bytes = if is_smaller {
+        indexed_bytes
+    } else {
+        rgba_bytes
+    };
+    writer
+        .write_all(&bytes)
+        .map_err(dmi::error::DmiError::from)?;
+    Ok(is_smaller)
+}
+
+/// Whether every pixel of the image is fully transparent
+#[must_use]
+pub fn is_blank(image: &DynamicImage) -> bool {
+    image.pixels().all(|(_, _, pixel)| pixel.0[3] == 0)
+}
+
+/// Names of every state in the icon where all frames are blank, which usually
+/// means a cut went wrong
+#[must_use]
+pub fn blank_states(icon: &Icon) -> Vec<String> {
+    icon.states
+        .iter()
+        .filter(|state| state.images.iter().all(is_blank))
+        .map(|state| state.name.clone())
+        .collect()
+}
+
+/// Draws `overlay` on top of `base`, but only on pixels that are already
+/// visible in `base`, so overlays never spill out past the edges of a sprite
+pub fn overlay_opaque(base: &mut DynamicImage, overlay: &DynamicImage) {
+    let mut buffer = base.to_rgba8();
+    for (x, y, pixel) in buffer.enumerate_pixels_mut() {
+        if pixel.0[3] == 0 || !overlay.in_bounds(x, y) {
+            continue;
+        }
+        let top = overlay.get_pixel(x, y);
+        pixel.blend(&top);
+    }
+    *base = DynamicImage::ImageRgba8(buffer);
+}
+
+/// Makes every pixel either fully transparent or fully opaque, cutting off at
+/// `threshold`
+pub fn alpha_threshold(image: &mut DynamicImage, threshold: u8) {
+    let mut buffer = image.to_rgba8();
+    for image::Rgba([_, _, _, a]) in buffer.pixels_mut() {
+        *a = if *a < threshold { 0 } else { 255 };
+    }
+    *image = DynamicImage::ImageRgba8(buffer);
+}
+
+/// Replaces every pixel that is exactly `from` with `to`
+pub fn recolor(image: &mut DynamicImage, from: Color, to: Color) {
+    let mut buffer = image.to_rgba8();
+    for pixel in buffer.pixels_mut() {
+        if Color::from(pixel.0) == from {
+            pixel.0 = to.into();
+        }
+    }
+    *image = DynamicImage::ImageRgba8(buffer);
+}
+
+/// Replaces every pixel matching the first color of a pair with the second.
+/// All swaps happen at once, so a pair can be swapped with each other.
+pub fn swap_colors(image: &mut DynamicImage, swaps: &[(Color, Color)]) {
+    let mut buffer = image.to_rgba8();
+    for pixel in buffer.pixels_mut() {
+        let color = Color::from(pixel.0);
+        if let Some((_, to)) = swaps.iter().find(|(from, _)| *from == color) {
+            pixel.0 = (*to).into();
+        }
+    }
+    *image = DynamicImage::ImageRgba8(buffer);
+}
+
+/// Clears the color of fully transparent pixels, so invisible pixels don't
+/// make otherwise identical frames differ
+pub fn normalize_transparent(image: &mut DynamicImage) {
+    let mut buffer = image.to_rgba8();
+    for pixel in buffer.pixels_mut() {
+        if pixel.0[3] == 0 {
+            pixel.0 = [0, 0, 0, 0];
+        }
+    }
+    *image = DynamicImage::ImageRgba8(buffer);
+}
+
+/// Replaces every visible pixel with the closest color in `palette`
+pub fn snap_to_palette(image: &mut DynamicImage, palette: &[Color]) {
+    if palette.is_empty() {
+        return;
+    }
+    let distance = |a: [u8; 4], b: [u8; 4]| -> u32 {
+        a.iter()
+            .zip(b)
+            .map(|(a, b)| u32::from(a.abs_diff(b)).pow(2))
+            .sum()
+    };
+    let mut buffer = image.to_rgba8();
+    for pixel in buffer.pixels_mut() {
+        if pixel.0[3] == 0 {
+            continue;
+        }
+        let closest = palette
+            .iter()
+            .min_by_key(|color| distance(pixel.0, (**color).into()))
+            .unwrap();
+        pixel.0 = (*closest).into();
+    }
+    *image = DynamicImage::ImageRgba8(buffer);
+}
+
+/// Snaps the color of every visible pixel to the nearest color in `palette`
+/// by euclidean distance in rgb, keeping the pixel's own alpha. Unlike
+/// `snap_to_palette`, the palette's alpha is ignored, so translucent pixels
+/// are matched on color alone.
+pub fn quantize_to_palette(image: &mut DynamicImage, palette: &[Color]) {
+    if palette.is_empty() {
+        return;
+    }
+    let distance = |pixel: [u8; 4], color: &Color| -> u32 {
+        [
+            pixel[0].abs_diff(color.red),
+            pixel[1].abs_diff(color.green),
+            pixel[2].abs_diff(color.blue),
+        ]
+        .into_iter()
+        .map(|diff| u32::from(diff).pow(2))
+        .sum()
+    };
+    let mut buffer = image.to_rgba8();
+    for pixel in buffer.pixels_mut() {
+        if pixel.0[3] == 0 {
+            continue;
+        }
+        let closest = palette
+            .iter()
+            .min_by_key(|color| distance(pixel.0, color))
+            .unwrap();
+        pixel.0 = [closest.red, closest.green, closest.blue, pixel.0[3]];
+    }
+    *image = DynamicImage::ImageRgba8(buffer);
+}
+
+/// Result of checking a palette against the colors actually used by an image
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct PaletteCoverage {
+    /// Palette colors that matched zero pixels, likely a typo in the hex
+    pub unused: Vec<Color>,
+    /// Colors in the image that the palette doesn't cover. Fully transparent
+    /// pixels are ignored.
+    pub uncovered: Vec<Color>,
+}
+
+impl PaletteCoverage {
+    /// Warns about unused palette colors, and in strict mode errors if any
+    /// colors in the image weren't covered by the palette
+    /// # Errors
+    /// Returns `ProcessorError::UncoveredColors` if `strict` is set and there
+    /// are uncovered colors
+    pub fn verify(&self, strict: bool) -> ProcessorResult<()> {
+        for color in &self.unused {
+            warn!(
+                color = color.to_hex_str(),
+                "Palette color matched no pixels"
+            );
+        }
+        if strict && !self.uncovered.is_empty() {
+            return Err(ProcessorError::UncoveredColors(
+                self.uncovered.iter().map(Color::to_hex_str).collect(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Checks which colors of `palette` appear in `image`, and which colors of
+/// `image` are missing from `palette`
+#[must_use]
+pub fn audit_palette_coverage(image: &DynamicImage, palette: &[Color]) -> PaletteCoverage {
+    let image_colors: Vec<Color> = colors_in_image(image)
+        .into_iter()
+        .filter(|color| color.alpha != 0)
+        .collect();
+    PaletteCoverage {
+        unused: palette
+            .iter()
+            .filter(|color| !image_colors.contains(color))
+            .copied()
+            .collect(),
+        uncovered: image_colors
+            .into_iter()
+            .filter(|color| !palette.contains(color))
+            .collect(),
+    }
+}
+
 pub fn sort_colors_by_luminance(colors: &mut [Color]) {
     colors.sort_by(|a, b| a.luminance().partial_cmp(&b.luminance()).unwrap());
 }
 
+/// Picks a dark and a light color from `colors`, at the 10th and 90th
+/// percentile of luminance so a few outlying pixels don't decide the result.
+/// Returns `(darker, lighter)`.
+/// # Panics
+/// Panics if `colors` is empty
 #[must_use]
 pub fn pick_contrasting_colors(colors: &[Color]) -> (Color, Color) {
     let mut sorted_colors = colors.to_vec();
     sort_colors_by_luminance(&mut sorted_colors);
-    let len_as_f32 = colors.len() as f32;
-    let first = 0.10 * len_as_f32;
-    let first_index = (first.floor() as usize).saturating_sub(1);
-    let second = 0.90 * len_as_f32;
-    let second_index = (second.floor() as usize).saturating_sub(1);
+    let last_as_f32 = (colors.len() - 1) as f32;
+    let first_index = (0.10 * last_as_f32).round() as usize;
+    let second_index = (0.90 * last_as_f32).round() as usize;
     (sorted_colors[first_index], sorted_colors[second_index])
 }
+
+#[cfg(test)]
+mod tests {
+    use image::{Rgba, RgbaImage};
+
+    use super::*;
+
+    #[test]
+    fn safe_new_image_in_bounds() {
+        let image = safe_new_image(32, 64).unwrap();
+        assert_eq!(image.dimensions(), (32, 64));
+    }
+
+    #[test]
+    fn safe_new_image_oversize() {
+        let result = safe_new_image(MAX_IMAGE_DIMENSION + 1, 32);
+        assert!(matches!(
+            result,
+            Err(ProcessorError::ImageTooLarge {
+                width,
+                height: 32,
+                max: MAX_IMAGE_DIMENSION,
+            }) if width == MAX_IMAGE_DIMENSION + 1
+        ));
+    }
+
+    fn solid_image(color: Color) -> DynamicImage {
+        DynamicImage::ImageRgba8(RgbaImage::from_pixel(4, 4, Rgba(color.into())))
+    }
+
+    #[test]
+    fn palette_coverage_unused_color() {
+        let red = Color::new_rgb(255, 0, 0);
+        let blue = Color::new_rgb(0, 0, 255);
+        let image = solid_image(red);
+
+        let coverage = audit_palette_coverage(&image, &[red, blue]);
+        assert_eq!(coverage.unused, vec![blue]);
+        assert!(coverage.uncovered.is_empty());
+        assert!(coverage.verify(true).is_ok());
+    }
+
+    #[test]
+    fn palette_coverage_uncovered_color() {
+        let red = Color::new_rgb(255, 0, 0);
+        let image = solid_image(red);
+
+        let coverage = audit_palette_coverage(&image, &[]);
+        assert_eq!(coverage.uncovered, vec![red]);
+        assert!(coverage.verify(false).is_ok());
+        assert!(matches!(
+            coverage.verify(true),
+            Err(ProcessorError::UncoveredColors(colors)) if colors == vec![red.to_hex_str()]
+        ));
+    }
+
+    #[test]
+    fn flatten_icon_to_sheet() {
+        let colors = [
+            Color::new_rgb(255, 0, 0),
+            Color::new_rgb(0, 255, 0),
+            Color::new_rgb(0, 0, 255),
+        ];
+        let icon = Icon {
+            width: 4,
+            height: 4,
+            states: colors
+                .iter()
+                .map(|color| {
+                    IconState {
+                        images: vec![solid_image(*color)],
+                        ..Default::default()
+                    }
+                })
+                .collect(),
+            ..Default::default()
+        };
+
+        let sheet = flatten_to_sheet(&icon, 2).unwrap();
+        assert_eq!(sheet.dimensions(), (8, 8));
+        assert_eq!(Color::from(sheet.get_pixel(0, 0).0), colors[0]);
+        assert_eq!(Color::from(sheet.get_pixel(4, 0).0), colors[1]);
+        assert_eq!(Color::from(sheet.get_pixel(0, 4).0), colors[2]);
+        assert!(is_blank(&sheet.crop_imm(4, 4, 4, 4)));
+    }
+
+    #[test]
+    fn indexed_round_trip() {
+        // 16 colors scattered about, so there's something for indexing to save
+        let mut noisy = RgbaImage::from_fn(32, 32, |x, y| {
+            let hash = ((x * 73_856_093) ^ (y * 19_349_663)).wrapping_mul(2_654_435_761);
+            let shade = (hash >> 28) as u8 * 16;
+            Rgba([shade, 255 - shade, shade / 2, 255])
+        });
+        noisy.put_pixel(0, 0, Rgba([20, 40, 60, 128]));
+        noisy.put_pixel(1, 0, Rgba([0, 0, 0, 0]));
+        let icon = Icon {
+            width: 32,
+            height: 32,
+            states: vec![
+                IconState {
+                    name: "solid".to_string(),
+                    images: vec![DynamicImage::ImageRgba8(RgbaImage::from_pixel(
+                        32,
+                        32,
+                        Rgba([255, 0, 0, 255]),
+                    ))],
+                    ..Default::default()
+                },
+                IconState {
+                    name: "noisy".to_string(),
+                    images: vec![DynamicImage::ImageRgba8(noisy)],
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+        let mut rgba = vec![];
+        icon.save(&mut rgba).unwrap();
+        let mut indexed = vec![];
+        assert!(save_indexed(&icon, &mut indexed).unwrap());
+        assert!(indexed.len() < rgba.len());
+
+        let loaded = Icon::load(indexed.as_slice()).unwrap();
+        assert_eq!(loaded.states.len(), 2);
+        for (original, loaded) in icon.states.iter().zip(&loaded.states) {
+            assert_eq!(original.name, loaded.name);
+            assert_eq!(original.images[0].to_rgba8(), loaded.images[0].to_rgba8());
+        }
+    }
+
+    #[test]
+    fn too_many_colors_for_indexed() {
+        let gradient = RgbaImage::from_fn(32, 32, |x, y| Rgba([x as u8 * 8, y as u8 * 8, 0, 255]));
+        let icon = Icon {
+            width: 32,
+            height: 32,
+            states: vec![IconState {
+                images: vec![DynamicImage::ImageRgba8(gradient)],
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let mut rgba = vec![];
+        icon.save(&mut rgba).unwrap();
+        let mut written = vec![];
+        assert!(!save_indexed(&icon, &mut written).unwrap());
+        assert_eq!(written, rgba);
+    }
+
+    #[test]
+    fn gradient_quantizes_to_palette() {
+        let dark = Color::new_rgb(0, 0, 0);
+        let light = Color::new_rgb(255, 255, 255);
+        let gradient = RgbaImage::from_fn(16, 1, |x, _| {
+            let value = (x * 17) as u8;
+            Rgba([value, value, value, 255])
+        });
+        let mut image = DynamicImage::ImageRgba8(gradient);
+
+        quantize_to_palette(&mut image, &[dark, light]);
+        let mut colors = colors_in_image(&image);
+        sort_colors_by_luminance(&mut colors);
+        assert_eq!(colors, vec![dark, light]);
+    }
+
+    #[test]
+    fn quantize_keeps_alpha() {
+        let red = Color::new_rgb(255, 0, 0);
+        // closest to red in rgb, but closer to the translucent blue counting alpha
+        let translucent_blue = Color::new(0, 0, 255, 64);
+        let mut image =
+            DynamicImage::ImageRgba8(RgbaImage::from_pixel(1, 1, Rgba([150, 0, 100, 64])));
+
+        let mut snapped = image.clone();
+        snap_to_palette(&mut snapped, &[red, translucent_blue]);
+        assert_eq!(snapped.get_pixel(0, 0), Rgba([0, 0, 255, 64]));
+
+        quantize_to_palette(&mut image, &[red, translucent_blue]);
+        assert_eq!(image.get_pixel(0, 0), Rgba([255, 0, 0, 64]));
+    }
+
+    #[test]
+    fn contrasting_colors_differ() {
+        let dark = Color::new_rgb(10, 10, 10);
+        let light = Color::new_rgb(240, 240, 240);
+        assert_eq!(pick_contrasting_colors(&[light, dark]), (dark, light));
+
+        let mut grays: Vec<Color> = (0..=20)
+            .map(|value| Color::new_rgb(value, value, value))
+            .collect();
+        grays.reverse();
+        assert_eq!(
+            pick_contrasting_colors(&grays),
+            (Color::new_rgb(2, 2, 2), Color::new_rgb(18, 18, 18))
+        );
+    }
+
+    #[test]
+    fn gif_keeps_frames_and_delays() {
+        use image::codecs::gif::GifDecoder;
+        use image::AnimationDecoder;
+
+        let frames = vec![
+            solid_image(Color::new_rgb(255, 0, 0)),
+            solid_image(Color::new_rgb(0, 0, 255)),
+        ];
+        let mut bytes = vec![];
+        write_gif(&mut bytes, &frames, &[2.0]).unwrap();
+
+        let decoded = GifDecoder::new(bytes.as_slice())
+            .unwrap()
+            .into_frames()
+            .collect_frames()
+            .unwrap();
+        let delays: Vec<(u32, u32)> = decoded
+            .iter()
+            .map(|frame| frame.delay().numer_denom_ms())
+            .collect();
+        assert_eq!(delays, vec![(200, 1), (100, 1)]);
+    }
+
+    #[test]
+    fn custom_dmi_version_is_saved() {
+        let icon = Icon {
+            version: dmi_version("3.5").unwrap(),
+            width: 32,
+            height: 32,
+            states: vec![IconState {
+                images: vec![DynamicImage::new_rgba8(32, 32)],
+                ..Default::default()
+            }],
+        };
+        let mut bytes = vec![];
+        icon.save(&mut bytes).unwrap();
+
+        let reloaded = Icon::load(bytes.as_slice()).unwrap();
+        assert_eq!(reloaded.version, icon.version);
+        assert_ne!(reloaded.version, DmiVersion::default());
+        assert!(dmi_version("4.0\nstate = \"bad\"").is_err());
+    }
+
+    #[test]
+    fn safe_new_image_overflow() {
+        let result = safe_new_image_with_max(u32::MAX, u32::MAX, u32::MAX);
+        assert!(matches!(
+            result,
+            Err(ProcessorError::DimensionOverflow(width, height))
+                if width == u64::from(u32::MAX) && height == u64::from(u32::MAX)
+        ));
+    }
+
+    #[test]
+    fn grid_size_overflow() {
+        assert_eq!(grid_size(4, 2, 32, 16).unwrap(), (128, 32));
+        assert!(matches!(
+            grid_size(1 << 20, 1, 1 << 13, 32),
+            Err(ProcessorError::DimensionOverflow(width, 32)) if width == 1 << 33
+        ));
+    }
+}
@@ -0,0 +1,48 @@
+/// Small deterministic random number generator (splitmix64), so procedural
+/// operations produce the same output for the same seed on every platform.
+#[derive(Debug, Clone)]
+pub struct SeededRng {
+    state: u64,
+}
+
+impl SeededRng {
+    #[must_use]
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Returns a number in `0..bound`. `bound` must be above 0.
+    pub fn next_below(&mut self, bound: u32) -> u32 {
+        (self.next_u64() % u64::from(bound)) as u32
+    }
+
+    /// Returns true with a probability of `chance` (0.0 to 1.0)
+    pub fn chance(&mut self, chance: f32) -> bool {
+        let roll = (self.next_u64() >> 40) as f32 / (1u64 << 24) as f32;
+        roll < chance
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn same_seed_same_sequence() {
+        let mut first = SeededRng::new(42);
+        let mut second = SeededRng::new(42);
+        for _ in 0..16 {
+            assert_eq!(first.next_u64(), second.next_u64());
+        }
+        let mut other = SeededRng::new(43);
+        assert_ne!(SeededRng::new(42).next_u64(), other.next_u64());
+    }
+}